@@ -1,38 +1,150 @@
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+use rayon::prelude::*;
+
+// Large enough to keep every core busy, but small enough that huge inputs never need to be
+// resident in memory all at once
+const PARALLEL_CHUNK_LINES: usize = 1 << 16;
+
+// The streaming reader's buffer will grow beyond this if it runs into a longer line
+const STREAMING_BUFFER_SIZE: usize = 1 << 20;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
     if let Some(path) = args.get(1) {
+        let digit_words = if let Some(words_path) = args
+            .iter()
+            .position(|arg| arg == "--words")
+            .and_then(|i| args.get(i + 1))
         {
-            let sum = calibration_sum(BufReader::new(File::open(path)?)
+            let mut digit_words_string = String::new();
+            File::open(words_path)?.read_to_string(&mut digit_words_string)?;
+
+            DigitWords::from_str(digit_words_string.as_str())?
+        } else {
+            DigitWords::default()
+        };
+
+        let strategy = ExtractionStrategy {
+            combination: if let Some(combination) = args
+                .iter()
+                .position(|arg| arg == "--combination")
+                .and_then(|i| args.get(i + 1))
+            {
+                Combination::from_str(combination)?
+            } else {
+                Combination::FirstAndLast
+            },
+            overlapping_words: !args
+                .iter()
+                .skip(2)
+                .any(|arg| arg == "--no-overlapping-words"),
+        };
+
+        if args.iter().skip(2).any(|arg| arg == "--explain") {
+            for (i, line) in BufReader::new(File::open(path)?)
                 .lines()
-                .map_while(Result::ok));
+                .map_while(Result::ok)
+                .enumerate()
+            {
+                println!(
+                    "{}: {} ({})",
+                    i + 1,
+                    line,
+                    calibration_line_textual(line.as_str(), &digit_words, strategy)
+                );
+            }
+        }
+
+        let strict = args.iter().skip(2).any(|arg| arg == "--strict");
+        let parallel = args.iter().skip(2).any(|arg| arg == "--parallel");
+        let streaming = args.iter().skip(2).any(|arg| arg == "--streaming");
+
+        let modes = [strict, parallel, streaming];
+
+        if modes.iter().filter(|&&mode| mode).count() > 1 {
+            return Err("Only one of --strict, --parallel, and --streaming may be used".into());
+        }
+
+        {
+            let sum = if parallel {
+                calibration_sum_parallel(
+                    BufReader::new(File::open(path)?),
+                    strategy,
+                    PARALLEL_CHUNK_LINES,
+                )?
+            } else if streaming {
+                calibration_sum_streaming(File::open(path)?, strategy, STREAMING_BUFFER_SIZE)?
+            } else {
+                let lines = BufReader::new(File::open(path)?)
+                    .lines()
+                    .map_while(Result::ok);
+
+                if strict {
+                    calibration_sum_strict(lines, strategy)?
+                } else {
+                    calibration_sum(lines, strategy)
+                }
+            };
 
             println!("Sum of calibration values: {}", sum);
         }
 
         {
-            let sum = calibration_sum_textual(BufReader::new(File::open(path)?)
-                .lines()
-                .map_while(Result::ok));
+            let sum = if parallel {
+                calibration_sum_textual_parallel(
+                    BufReader::new(File::open(path)?),
+                    &digit_words,
+                    strategy,
+                    PARALLEL_CHUNK_LINES,
+                )?
+            } else if streaming {
+                calibration_sum_textual_streaming(
+                    File::open(path)?,
+                    &digit_words,
+                    strategy,
+                    STREAMING_BUFFER_SIZE,
+                )?
+            } else {
+                let lines = BufReader::new(File::open(path)?)
+                    .lines()
+                    .map_while(Result::ok);
+
+                if strict {
+                    calibration_sum_textual_strict(lines, &digit_words, strategy)?
+                } else {
+                    calibration_sum_textual(lines, &digit_words, strategy)
+                }
+            };
 
             println!("Sum of calibration values with text interpretation: {}", sum);
         }
 
         Ok(())
     } else {
-        Err("Usage: day01 INPUT_FILE_PATH".into())
+        Err("Usage: day01 INPUT_FILE_PATH [--words WORDS_FILE_PATH] [--combination first-and-last|sum-of-all-digits] [--no-overlapping-words] [--explain] [--strict | --parallel | --streaming]".into())
     }
 }
 
-fn calibration_value(line: &str) -> u32 {
-    if let (Some(first), Some(last)) = (line.find(char::is_numeric), line.rfind(char::is_numeric)) {
-        let bytes = line.as_bytes();
+fn calibration_value(line: &str, strategy: ExtractionStrategy) -> u32 {
+    let bytes = line.as_bytes();
 
+    // Plain digits can never overlap one another, so only the combination matters here
+    if strategy.combination == Combination::SumOfAllDigits {
+        bytes
+            .iter()
+            .filter(|b| b.is_ascii_digit())
+            .map(|b| (b - b'0') as u32)
+            .sum()
+    } else if let (Some(first), Some(last)) = (first_ascii_digit(bytes), last_ascii_digit(bytes)) {
         let first_digit = bytes[first] - b'0';
         let last_digit = bytes[last] - b'0';
 
@@ -42,54 +154,540 @@ fn calibration_value(line: &str) -> u32 {
     }
 }
 
-fn calibration_sum(lines: impl Iterator<Item = String>) -> u32 {
-    lines.map(|line| calibration_value(line.as_str())).sum()
+// Checks eight bytes at a time, falling back to a byte-by-byte scan for the last few bytes
+fn first_ascii_digit(bytes: &[u8]) -> Option<usize> {
+    let mut chunks = bytes.chunks_exact(8);
+
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let mask = ascii_digit_mask(chunk.try_into().unwrap());
+
+        if mask != 0 {
+            return Some(i * 8 + mask.trailing_zeros() as usize / 8);
+        }
+    }
+
+    let offset = bytes.len() - chunks.remainder().len();
+
+    chunks
+        .remainder()
+        .iter()
+        .position(u8::is_ascii_digit)
+        .map(|i| offset + i)
+}
+
+fn last_ascii_digit(bytes: &[u8]) -> Option<usize> {
+    let mut chunks = bytes.rchunks_exact(8);
+
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let mask = ascii_digit_mask(chunk.try_into().unwrap());
+
+        if mask != 0 {
+            return Some(bytes.len() - (i + 1) * 8 + 7 - mask.leading_zeros() as usize / 8);
+        }
+    }
+
+    // The remainder of `rchunks_exact` is at the start of the slice
+    chunks.remainder().iter().rposition(u8::is_ascii_digit)
+}
+
+// Returns a word with the high bit of each byte set if the corresponding byte of the given chunk is
+// an ASCII digit. XORing with '0' maps digits to 0-9; adding 0x76 to the low seven bits of each
+// byte then sets its high bit if it was 10 or more, and bytes with their own high bit set (i.e.
+// anything outside of ASCII) can't be digits either. No byte can carry into its neighbor.
+fn ascii_digit_mask(chunk: [u8; 8]) -> u64 {
+    let x = u64::from_le_bytes(chunk) ^ 0x3030_3030_3030_3030;
+    let t = (x & 0x7f7f_7f7f_7f7f_7f7f) + 0x7676_7676_7676_7676;
+
+    !(t | x) & 0x8080_8080_8080_8080
+}
+
+fn calibration_sum(lines: impl Iterator<Item = String>, strategy: ExtractionStrategy) -> u32 {
+    lines
+        .map(|line| calibration_value(line.as_str(), strategy))
+        .sum()
+}
+
+fn calibration_sum_strict(
+    lines: impl Iterator<Item = String>,
+    strategy: ExtractionStrategy,
+) -> Result<u32, Box<dyn Error>> {
+    strict_sum(lines, |line| {
+        first_ascii_digit(line.as_bytes())
+            .is_some()
+            .then(|| calibration_value(line, strategy))
+    })
+}
+
+fn calibration_value_textual(
+    line: &str,
+    digit_words: &DigitWords,
+    strategy: ExtractionStrategy,
+) -> u32 {
+    calibration_line_textual(line, digit_words, strategy).value
+}
+
+fn calibration_line_textual<'a>(
+    line: &'a str,
+    digit_words: &DigitWords,
+    strategy: ExtractionStrategy,
+) -> CalibrationLine<'a> {
+    let mut matches = Vec::new();
+    digit_words.for_each_match(line.as_bytes(), |digit_match| matches.push(digit_match));
+
+    let tokens: Vec<Token> = strategy
+        .select(matches)
+        .iter()
+        .map(|digit_match| Token {
+            text: &line[digit_match.start..digit_match.start + digit_match.len],
+            offset: digit_match.start,
+            digit: digit_match.digit,
+        })
+        .collect();
+
+    CalibrationLine {
+        first: tokens.first().cloned(),
+        last: tokens.last().cloned(),
+        value: strategy.combine(&tokens),
+    }
+}
+
+fn calibration_sum_textual(
+    lines: impl Iterator<Item = String>,
+    digit_words: &DigitWords,
+    strategy: ExtractionStrategy,
+) -> u32 {
+    lines
+        .map(|line| calibration_value_textual(line.as_str(), digit_words, strategy))
+        .sum()
+}
+
+fn calibration_sum_parallel(
+    reader: impl BufRead,
+    strategy: ExtractionStrategy,
+    chunk_lines: usize,
+) -> io::Result<u32> {
+    parallel_sum(reader, chunk_lines, |line| {
+        calibration_value(line, strategy)
+    })
+}
+
+fn calibration_sum_textual_parallel(
+    reader: impl BufRead,
+    digit_words: &DigitWords,
+    strategy: ExtractionStrategy,
+    chunk_lines: usize,
+) -> io::Result<u32> {
+    parallel_sum(reader, chunk_lines, |line| {
+        calibration_value_textual(line, digit_words, strategy)
+    })
+}
+
+// Reads a bounded chunk of lines at a time and spreads each chunk across all available cores
+fn parallel_sum(
+    reader: impl BufRead,
+    chunk_lines: usize,
+    calibration_value: impl Fn(&str) -> u32 + Sync,
+) -> io::Result<u32> {
+    let mut lines = reader.lines();
+    let mut chunk = Vec::with_capacity(chunk_lines);
+    let mut sum = 0;
+
+    loop {
+        chunk.clear();
+
+        for line in lines.by_ref().take(chunk_lines) {
+            chunk.push(line?);
+        }
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        sum += chunk
+            .par_iter()
+            .map(|line| calibration_value(line.as_str()))
+            .sum::<u32>();
+    }
+
+    Ok(sum)
+}
+
+fn calibration_sum_streaming(
+    reader: impl Read,
+    strategy: ExtractionStrategy,
+    buffer_size: usize,
+) -> io::Result<u32> {
+    let mut sum = 0;
+    for_each_line(reader, buffer_size, |line| {
+        sum += calibration_value(line, strategy)
+    })?;
+
+    Ok(sum)
+}
+
+fn calibration_sum_textual_streaming(
+    reader: impl Read,
+    digit_words: &DigitWords,
+    strategy: ExtractionStrategy,
+    buffer_size: usize,
+) -> io::Result<u32> {
+    let mut sum = 0;
+    for_each_line(reader, buffer_size, |line| {
+        sum += calibration_value_textual(line, digit_words, strategy)
+    })?;
+
+    Ok(sum)
+}
+
+// Passes each line to the given function as a slice of a single reusable buffer instead of
+// allocating a new `String` for every line. Partial lines at the end of the buffer get moved to the
+// front before the next read, and the buffer only grows if a single line won't fit.
+fn for_each_line(
+    mut reader: impl Read,
+    buffer_size: usize,
+    mut f: impl FnMut(&str),
+) -> io::Result<()> {
+    let mut buffer = vec![0; buffer_size.max(1)];
+    let mut filled = 0;
+
+    loop {
+        if filled == buffer.len() {
+            buffer.resize(buffer.len() * 2, 0);
+        }
+
+        let read = match reader.read(&mut buffer[filled..]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        filled += read;
+
+        let mut start = 0;
+
+        while let Some(end) = buffer[start..filled].iter().position(|&b| b == b'\n') {
+            f(as_line(&buffer[start..start + end])?);
+            start += end + 1;
+        }
+
+        if read == 0 {
+            if start < filled {
+                f(as_line(&buffer[start..filled])?);
+            }
+
+            return Ok(());
+        }
+
+        buffer.copy_within(start..filled, 0);
+        filled -= start;
+    }
+}
+
+// Mirrors `BufRead::lines`, which also drops a trailing carriage return
+fn as_line(bytes: &[u8]) -> io::Result<&str> {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+
+    std::str::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+struct CalibrationLine<'a> {
+    first: Option<Token<'a>>,
+    last: Option<Token<'a>>,
+    value: u32,
+}
+
+#[derive(Clone)]
+struct Token<'a> {
+    text: &'a str,
+    // Byte offset of the start of the token within its line
+    offset: usize,
+    digit: u32,
+}
+
+impl Display for CalibrationLine<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let (Some(first), Some(last)) = (&self.first, &self.last) {
+            write!(
+                f,
+                "first \"{}\" at {}, last \"{}\" at {}: {}",
+                first.text, first.offset, last.text, last.offset, self.value
+            )
+        } else {
+            write!(f, "no digits: {}", self.value)
+        }
+    }
 }
 
-fn calibration_value_textual(line: &str) -> u32 {
-    let chars: Vec<char> = line.chars().collect();
+fn calibration_sum_textual_strict(
+    lines: impl Iterator<Item = String>,
+    digit_words: &DigitWords,
+    strategy: ExtractionStrategy,
+) -> Result<u32, Box<dyn Error>> {
+    strict_sum(lines, |line| {
+        let calibration_line = calibration_line_textual(line, digit_words, strategy);
+
+        calibration_line
+            .first
+            .is_some()
+            .then_some(calibration_line.value)
+    })
+}
 
-    let first_digit = (0..chars.len())
-        .map(|offset| &chars[offset..])
-        .filter_map(to_digit_textual)
-        .next();
+// Sums calibration values, but rather than quietly counting lines with no digits as 0, reports the
+// (1-based) numbers of those lines as an error
+fn strict_sum(
+    lines: impl Iterator<Item = String>,
+    calibration_value: impl Fn(&str) -> Option<u32>,
+) -> Result<u32, Box<dyn Error>> {
+    let mut sum = 0;
+    let mut lines_without_digits = Vec::new();
 
-    let last_digit = (0..chars.len())
-        .rev()
-        .map(|offset| &chars[offset..])
-        .filter_map(to_digit_textual)
-        .next();
+    for (i, line) in lines.enumerate() {
+        match calibration_value(line.as_str()) {
+            Some(value) => sum += value,
+            None => lines_without_digits.push((i + 1).to_string()),
+        }
+    }
 
-    if let (Some(first), Some(last)) = (first_digit, last_digit) {
-        first * 10 + last
+    if lines_without_digits.is_empty() {
+        Ok(sum)
     } else {
-        0
+        Err(format!("No digits on lines: {}", lines_without_digits.join(", ")).into())
+    }
+}
+
+#[derive(Copy, Clone)]
+struct ExtractionStrategy {
+    combination: Combination,
+    // If false, words are read left to right and a word can't reuse letters from the one before it
+    // (so "eightwo" reads as just "eight")
+    overlapping_words: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Combination {
+    FirstAndLast,
+    SumOfAllDigits,
+}
+
+impl ExtractionStrategy {
+    // Sorts matches by where they start and drops the ones this strategy doesn't count. Matches
+    // arrive in order of where they end, not where they start, so a long word can start before a
+    // shorter one that was reported earlier; if two start together, only the longer one counts.
+    fn select(&self, mut matches: Vec<DigitMatch>) -> Vec<DigitMatch> {
+        matches.sort_by_key(|digit_match| (digit_match.start, std::cmp::Reverse(digit_match.len)));
+        matches.dedup_by_key(|digit_match| digit_match.start);
+
+        if !self.overlapping_words {
+            let mut end = 0;
+
+            matches.retain(|digit_match| {
+                let keep = digit_match.start >= end;
+
+                if keep {
+                    end = digit_match.start + digit_match.len;
+                }
+
+                keep
+            });
+        }
+
+        matches
+    }
+
+    fn combine(&self, tokens: &[Token]) -> u32 {
+        match self.combination {
+            Combination::FirstAndLast => {
+                if let (Some(first), Some(last)) = (tokens.first(), tokens.last()) {
+                    first.digit * 10 + last.digit
+                } else {
+                    0
+                }
+            }
+            Combination::SumOfAllDigits => tokens.iter().map(|token| token.digit).sum(),
+        }
+    }
+}
+
+impl Default for ExtractionStrategy {
+    fn default() -> Self {
+        ExtractionStrategy {
+            combination: Combination::FirstAndLast,
+            overlapping_words: true,
+        }
+    }
+}
+
+impl FromStr for Combination {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first-and-last" => Ok(Combination::FirstAndLast),
+            "sum-of-all-digits" => Ok(Combination::SumOfAllDigits),
+            _ => Err(format!("Unrecognized combination: {}", s).into()),
+        }
     }
 }
 
-fn to_digit_textual(slice: &[char]) -> Option<u32> {
-    match slice {
-        [n @ '0'..='9', ..] => {
-            let mut bytes = [0; 1];
-            n.encode_utf8(&mut bytes);
+// Finds digits and digit words in a single pass using an Aho-Corasick automaton, which makes the
+// cost of a scan independent of the number (and length) of words in the dictionary
+struct DigitWords {
+    nodes: Vec<MatcherNode>,
+}
+
+#[derive(Default)]
+struct MatcherNode {
+    transitions: HashMap<u8, usize>,
+    failure: usize,
+    // The length and digit of the pattern that ends at this node, if any
+    output: Option<(usize, u32)>,
+    // The nearest node along the failure chain that has its own output, if any
+    output_link: Option<usize>,
+}
+
+#[derive(Copy, Clone)]
+struct DigitMatch {
+    start: usize,
+    len: usize,
+    digit: u32,
+}
+
+impl DigitWords {
+    fn new(words: impl IntoIterator<Item = (String, u32)>) -> Self {
+        let mut nodes = vec![MatcherNode::default()];
+
+        // Plain digits always count, regardless of the dictionary
+        let patterns = (0..=9).map(|digit| (digit.to_string(), digit)).chain(words);
+
+        for (pattern, digit) in patterns {
+            let mut node = 0;
+
+            for &b in pattern.as_bytes() {
+                node = if let Some(&next) = nodes[node].transitions.get(&b) {
+                    next
+                } else {
+                    nodes.push(MatcherNode::default());
+
+                    let next = nodes.len() - 1;
+                    nodes[node].transitions.insert(b, next);
+
+                    next
+                };
+            }
+
+            nodes[node].output.get_or_insert((pattern.len(), digit));
+        }
+
+        // Fill in failure links breadth-first so every node's failure target is complete before
+        // any of its children need it
+        let mut queue: VecDeque<usize> = nodes[0].transitions.values().copied().collect();
+
+        while let Some(node) = queue.pop_front() {
+            let transitions: Vec<(u8, usize)> = nodes[node]
+                .transitions
+                .iter()
+                .map(|(&b, &child)| (b, child))
+                .collect();
+
+            for (b, child) in transitions {
+                let mut failure = nodes[node].failure;
+
+                let child_failure = loop {
+                    if let Some(&next) = nodes[failure].transitions.get(&b) {
+                        break next;
+                    } else if failure == 0 {
+                        break 0;
+                    }
+
+                    failure = nodes[failure].failure;
+                };
+
+                nodes[child].failure = child_failure;
+                nodes[child].output_link = if nodes[child_failure].output.is_some() {
+                    Some(child_failure)
+                } else {
+                    nodes[child_failure].output_link
+                };
+
+                queue.push_back(child);
+            }
+        }
+
+        DigitWords { nodes }
+    }
+
+    fn for_each_match(&self, haystack: &[u8], mut f: impl FnMut(DigitMatch)) {
+        let mut node = 0;
+
+        for (i, b) in haystack.iter().enumerate() {
+            loop {
+                if let Some(&next) = self.nodes[node].transitions.get(b) {
+                    node = next;
+                    break;
+                } else if node == 0 {
+                    break;
+                }
 
-            Some((bytes[0] - b'0') as u32)
+                node = self.nodes[node].failure;
+            }
+
+            let mut output_node = Some(node);
+
+            while let Some(current) = output_node {
+                if let Some((len, digit)) = self.nodes[current].output {
+                    f(DigitMatch {
+                        start: i + 1 - len,
+                        len,
+                        digit,
+                    });
+                }
+
+                output_node = self.nodes[current].output_link;
+            }
         }
-        ['o', 'n', 'e', ..] => Some(1),
-        ['t', 'w', 'o', ..] => Some(2),
-        ['t', 'h', 'r', 'e', 'e', ..] => Some(3),
-        ['f', 'o', 'u', 'r', ..] => Some(4),
-        ['f', 'i', 'v', 'e', ..] => Some(5),
-        ['s', 'i', 'x', ..] => Some(6),
-        ['s', 'e', 'v', 'e', 'n', ..] => Some(7),
-        ['e', 'i', 'g', 'h', 't', ..] => Some(8),
-        ['n', 'i', 'n', 'e', ..] => Some(9),
-        _ => None,
     }
 }
 
-fn calibration_sum_textual(lines: impl Iterator<Item = String>) -> u32 {
-    lines.map(|line| calibration_value_textual(line.as_str())).sum()
+impl Default for DigitWords {
+    fn default() -> Self {
+        DigitWords::new(
+            [
+                "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+            ]
+            .iter()
+            .zip(1..)
+            .map(|(word, digit)| (String::from(*word), digit)),
+        )
+    }
+}
+
+impl FromStr for DigitWords {
+    type Err = Box<dyn Error>;
+
+    // Dictionaries have one `word=digit` pair per line (e.g. `eins=1`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<(String, u32)> = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (word, digit) = line
+                    .split_once('=')
+                    .ok_or_else(|| format!("Could not parse digit word: {}", line))?;
+
+                let digit: u32 = digit.trim().parse()?;
+
+                if word.trim().is_empty() || digit > 9 {
+                    Err(format!("Invalid digit word: {}", line).into())
+                } else {
+                    Ok((String::from(word.trim()), digit))
+                }
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        Ok(DigitWords::new(words))
+    }
 }
 
 #[cfg(test)]
@@ -99,10 +697,54 @@ mod test {
 
     #[test]
     fn test_calibration_value() {
-        assert_eq!(12, calibration_value("1abc2"));
-        assert_eq!(38, calibration_value("pqr3stu8vwx"));
-        assert_eq!(15, calibration_value("a1b2c3d4e5f"));
-        assert_eq!(77, calibration_value("treb7uchet"));
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(12, calibration_value("1abc2", strategy));
+        assert_eq!(38, calibration_value("pqr3stu8vwx", strategy));
+        assert_eq!(15, calibration_value("a1b2c3d4e5f", strategy));
+        assert_eq!(77, calibration_value("treb7uchet", strategy));
+    }
+
+    #[test]
+    fn test_ascii_digit_scanning() {
+        let strategy = ExtractionStrategy::default();
+
+        let lines = [
+            "",
+            "7",
+            "abcdefgh",
+            "abcdefg1",
+            "1bcdefgh",
+            "abcdefgh9",
+            "9abcdefgh",
+            "a1b2c3d4e5f6g7h8i9",
+            "fünf٣ and 4 then ² and 5 and more text",
+            "\u{ff}\u{ff}\u{ff}\u{ff}0\u{ff}\u{ff}\u{ff}",
+            "/:/:/:/:/:/:/:/:",
+        ];
+
+        for line in lines {
+            let bytes = line.as_bytes();
+
+            assert_eq!(
+                bytes.iter().position(u8::is_ascii_digit),
+                first_ascii_digit(bytes),
+                "{}",
+                line
+            );
+
+            assert_eq!(
+                bytes.iter().rposition(u8::is_ascii_digit),
+                last_ascii_digit(bytes),
+                "{}",
+                line
+            );
+        }
+
+        assert_eq!(
+            45,
+            calibration_value("fünf٣ and 4 then ² and 5 and more text", strategy)
+        );
     }
 
     #[test]
@@ -114,18 +756,44 @@ mod test {
             treb7uchet
         "};
 
-        assert_eq!(142, calibration_sum(lines.lines().map(String::from)));
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(
+            142,
+            calibration_sum(lines.lines().map(String::from), strategy)
+        );
     }
 
     #[test]
     fn test_calibration_value_textual() {
-        assert_eq!(29, calibration_value_textual("two1nine"));
-        assert_eq!(83, calibration_value_textual("eightwothree"));
-        assert_eq!(13, calibration_value_textual("abcone2threexyz"));
-        assert_eq!(24, calibration_value_textual("xtwone3four"));
-        assert_eq!(42, calibration_value_textual("4nineeightseven2"));
-        assert_eq!(14, calibration_value_textual("zoneight234"));
-        assert_eq!(76, calibration_value_textual("7pqrstsixteen"));
+        let words = DigitWords::default();
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(29, calibration_value_textual("two1nine", &words, strategy));
+        assert_eq!(
+            83,
+            calibration_value_textual("eightwothree", &words, strategy)
+        );
+        assert_eq!(
+            13,
+            calibration_value_textual("abcone2threexyz", &words, strategy)
+        );
+        assert_eq!(
+            24,
+            calibration_value_textual("xtwone3four", &words, strategy)
+        );
+        assert_eq!(
+            42,
+            calibration_value_textual("4nineeightseven2", &words, strategy)
+        );
+        assert_eq!(
+            14,
+            calibration_value_textual("zoneight234", &words, strategy)
+        );
+        assert_eq!(
+            76,
+            calibration_value_textual("7pqrstsixteen", &words, strategy)
+        );
     }
 
     #[test]
@@ -140,6 +808,232 @@ mod test {
             7pqrstsixteen
         "};
 
-        assert_eq!(281, calibration_sum_textual(lines.lines().map(String::from)));
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(
+            281,
+            calibration_sum_textual(
+                lines.lines().map(String::from),
+                &DigitWords::default(),
+                strategy
+            )
+        );
+    }
+
+    #[test]
+    fn test_parallel_calibration_sums() {
+        let lines = indoc! {"
+            two1nine
+            eightwothree
+            abcone2threexyz
+            xtwone3four
+            4nineeightseven2
+            zoneight234
+            7pqrstsixteen
+        "};
+
+        let words = DigitWords::default();
+        let strategy = ExtractionStrategy::default();
+
+        for chunk_lines in [1, 3, 100] {
+            assert_eq!(
+                calibration_sum(lines.lines().map(String::from), strategy),
+                calibration_sum_parallel(lines.as_bytes(), strategy, chunk_lines).unwrap()
+            );
+
+            assert_eq!(
+                281,
+                calibration_sum_textual_parallel(lines.as_bytes(), &words, strategy, chunk_lines)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_streaming_calibration_sums() {
+        let lines = "two1nine\r\neightwothree\nabcone2threexyz\nxtwone3four\n\n4nineeightseven2\nzoneight234\n7pqrstsixteen";
+
+        let words = DigitWords::default();
+        let strategy = ExtractionStrategy::default();
+
+        for buffer_size in [1, 3, 16, 1024] {
+            assert_eq!(
+                calibration_sum(lines.lines().map(String::from), strategy),
+                calibration_sum_streaming(lines.as_bytes(), strategy, buffer_size).unwrap()
+            );
+
+            assert_eq!(
+                281,
+                calibration_sum_textual_streaming(lines.as_bytes(), &words, strategy, buffer_size)
+                    .unwrap()
+            );
+        }
+
+        let mut streamed_lines = Vec::new();
+
+        for_each_line("a\n\nb\r\nc\n".as_bytes(), 2, |line| {
+            streamed_lines.push(String::from(line))
+        })
+        .unwrap();
+
+        assert_eq!(vec!["a", "", "b", "c"], streamed_lines);
+
+        assert!(calibration_sum_streaming(&[b'1', 0xff, b'\n'][..], strategy, 16).is_err());
+    }
+
+    #[test]
+    fn test_strict_calibration_sums() {
+        let lines = indoc! {"
+            twonine
+            abc
+            7pqrstsixteen
+            xyz
+        "};
+
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(
+            "No digits on lines: 1, 2, 4",
+            calibration_sum_strict(lines.lines().map(String::from), strategy)
+                .unwrap_err()
+                .to_string()
+        );
+
+        assert_eq!(
+            "No digits on lines: 2, 4",
+            calibration_sum_textual_strict(
+                lines.lines().map(String::from),
+                &DigitWords::default(),
+                strategy
+            )
+            .unwrap_err()
+            .to_string()
+        );
+
+        assert_eq!(
+            Some(105),
+            calibration_sum_textual_strict(
+                ["two1nine", "7pqrstsixteen"]
+                    .iter()
+                    .map(|line| String::from(*line)),
+                &DigitWords::default(),
+                strategy
+            )
+            .ok()
+        );
+    }
+
+    #[test]
+    fn test_calibration_line_textual() {
+        let words = DigitWords::default();
+        let strategy = ExtractionStrategy::default();
+        let calibration_line = calibration_line_textual("xtwone3four", &words, strategy);
+
+        let first = calibration_line.first.unwrap();
+        let last = calibration_line.last.unwrap();
+
+        assert_eq!(("two", 1, 2), (first.text, first.offset, first.digit));
+        assert_eq!(("four", 7, 4), (last.text, last.offset, last.digit));
+        assert_eq!(24, calibration_line.value);
+
+        let calibration_line = calibration_line_textual("abc", &words, strategy);
+
+        assert!(calibration_line.first.is_none());
+        assert!(calibration_line.last.is_none());
+        assert_eq!(0, calibration_line.value);
+    }
+
+    #[test]
+    fn test_custom_digit_words() {
+        let digit_words = DigitWords::from_str(indoc! {"
+            eins=1
+            zwei=2
+            drei=3
+            fünf=5
+        "})
+        .unwrap();
+
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(
+            15,
+            calibration_value_textual("xeinsfünfy", &digit_words, strategy)
+        );
+        assert_eq!(
+            32,
+            calibration_value_textual("dreione2", &digit_words, strategy)
+        );
+        assert_eq!(
+            55,
+            calibration_value_textual("äfünf", &digit_words, strategy)
+        );
+
+        assert!(DigitWords::from_str("zehn=10").is_err());
+    }
+
+    #[test]
+    fn test_extraction_strategies() {
+        let words = DigitWords::default();
+
+        let sum_overlapping = ExtractionStrategy {
+            combination: Combination::SumOfAllDigits,
+            overlapping_words: true,
+        };
+
+        let first_and_last_exclusive = ExtractionStrategy {
+            combination: Combination::FirstAndLast,
+            overlapping_words: false,
+        };
+
+        let sum_exclusive = ExtractionStrategy {
+            combination: Combination::SumOfAllDigits,
+            overlapping_words: false,
+        };
+
+        assert_eq!(3, calibration_value("1abc2", sum_overlapping));
+        assert_eq!(15, calibration_value("a1b2c3d4e5f", sum_exclusive));
+
+        assert_eq!(
+            13,
+            calibration_value_textual("eightwothree", &words, sum_overlapping)
+        );
+        assert_eq!(
+            11,
+            calibration_value_textual("eightwothree", &words, sum_exclusive)
+        );
+        assert_eq!(
+            88,
+            calibration_value_textual("eightwo", &words, first_and_last_exclusive)
+        );
+        assert_eq!(
+            83,
+            calibration_value_textual("eightwothree", &words, first_and_last_exclusive)
+        );
+    }
+
+    #[test]
+    fn test_overlapping_digit_words() {
+        let digit_words = DigitWords::from_str(indoc! {"
+            seven=7
+            seventeen=1
+            teen=9
+            een=3
+        "})
+        .unwrap();
+
+        let strategy = ExtractionStrategy::default();
+
+        assert_eq!(
+            13,
+            calibration_value_textual("xseventeen", &digit_words, strategy)
+        );
+        assert_eq!(
+            73,
+            calibration_value_textual("sevenxeen", &digit_words, strategy)
+        );
+        assert_eq!(
+            93,
+            calibration_value_textual("teen", &digit_words, strategy)
+        );
     }
 }
@@ -1,42 +1,180 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
 use std::str::FromStr;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
-    if let Some(path) = args.get(1) {
-        let games: Vec<Game> = BufReader::new(File::open(path)?)
-            .lines()
-            .map_while(Result::ok)
-            .map(|line| Game::from_str(line.as_str()))
-            .collect::<Result<Vec<_>, _>>()?;
+    // Any number of input files may come before the first flag
+    let paths: Vec<&str> = args
+        .iter()
+        .skip(1)
+        .map(String::as_str)
+        .take_while(|arg| !arg.starts_with("--"))
+        .collect();
+
+    if !paths.is_empty() {
+        let tolerant = args.iter().skip(2).any(|arg| arg == "--tolerant");
+        let renumber = args.iter().skip(2).any(|arg| arg == "--renumber");
+
+        let games = merge_games(
+            paths
+                .iter()
+                .map(|&path| Ok((path, read_games(path, tolerant)?)))
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?,
+            renumber,
+        )?;
+
+        if let Some(format) = args
+            .iter()
+            .position(|arg| arg == "--export")
+            .and_then(|i| args.get(i + 1))
+        {
+            match format.as_str() {
+                "csv" => write_csv(&games, io::stdout().lock())?,
+                _ => return Err(format!("Unrecognized export format: {}", format).into()),
+            }
+
+            return Ok(());
+        }
+
+        let bag = Sample::from_str("12 red, 13 green, 14 blue")?;
 
         println!(
             "Sum of 'possible' game IDs: {}",
-            games
-                .iter()
-                .filter(|game| game.is_possible(12, 13, 14))
-                .map(|game| game.id)
-                .sum::<u32>()
+            GameFilter::new()
+                .matching(|game| game.is_possible(&bag))
+                .id_sum(&games)
         );
 
         println!(
             "Sum of powers of minimal cube sets: {}",
             games
                 .iter()
-                .map(|game| game.minimum_cubes_power())
+                .map(|game| game.minimum_cubes_power(&CUBE_COLORS))
                 .sum::<u32>()
         );
 
+        if args.iter().skip(2).any(|arg| arg == "--stats") {
+            print!("{}", GameStatistics::new(&games));
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--table") {
+            print!("{}", MinimumCubeTable(&games));
+        }
+
+        {
+            let mut filter = GameFilter::new();
+            let mut filtered = false;
+
+            // Bounds look like samples (e.g. `--max "12 red, 13 green"`) and may be repeated
+            for (i, arg) in args.iter().enumerate().skip(2) {
+                if arg == "--max" || arg == "--min" {
+                    let bounds = Sample::from_str(
+                        args.get(i + 1)
+                            .ok_or_else(|| format!("{} requires a value", arg))?,
+                    )?;
+
+                    for (color, n) in bounds.counts {
+                        filter = if arg == "--max" {
+                            filter.max(color.as_str(), n)
+                        } else {
+                            filter.min(color.as_str(), n)
+                        };
+                    }
+
+                    filtered = true;
+                }
+            }
+
+            if filtered {
+                println!(
+                    "Game IDs matching filter: {:?} (sum: {})",
+                    filter.ids(&games),
+                    filter.id_sum(&games)
+                );
+            }
+        }
+
         Ok(())
     } else {
-        Err("Usage: day02 INPUT_FILE_PATH".into())
+        Err("Usage: day02 INPUT_FILE_PATH... [--max SAMPLE]... [--min SAMPLE]... [--table] [--stats] [--tolerant] [--renumber] [--export csv]".into())
     }
 }
 
+fn read_games(path: &str, tolerant: bool) -> Result<Vec<Game>, Box<dyn Error>> {
+    games_from_reader(BufReader::new(File::open(path)?), tolerant)
+        .map(|game| game.map_err(|e| format!("{}, {}", path, e).into()))
+        .collect()
+}
+
+// Parses games one line at a time as the iterator advances, so callers that only need to fold over
+// games never have to hold an entire file in memory
+fn games_from_reader(
+    reader: impl BufRead,
+    tolerant: bool,
+) -> impl Iterator<Item = Result<Game, Box<dyn Error>>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(move |(_, line)| {
+            !(tolerant && line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        })
+        .map(move |(i, line)| {
+            let line = line?;
+
+            let game = if tolerant {
+                Game::from_str_tolerant(line.as_str())
+            } else {
+                Game::from_str(line.as_str())
+            };
+
+            game.map_err(|e| format!("line {}: {} ({:?})", i + 1, e, line).into())
+        })
+}
+
+// Combines games from any number of files in order. Game IDs must be unique across all files unless
+// `renumber` is set, in which case games with already-claimed IDs get new IDs above the highest ID
+// in any file.
+fn merge_games(files: Vec<(&str, Vec<Game>)>, renumber: bool) -> Result<Vec<Game>, Box<dyn Error>> {
+    let mut next_id = files
+        .iter()
+        .flat_map(|(_, games)| games.iter().map(|game| game.id))
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    let mut sources: HashMap<u32, &str> = HashMap::new();
+    let mut merged = Vec::new();
+
+    for (path, games) in files {
+        for mut game in games {
+            if let Some(&source) = sources.get(&game.id) {
+                if !renumber {
+                    return Err(format!(
+                        "Game {} in {} has the same ID as a game in {}",
+                        game.id, path, source
+                    )
+                    .into());
+                }
+
+                game.id = next_id;
+                next_id += 1;
+            }
+
+            sources.insert(game.id, path);
+            merged.push(game);
+        }
+    }
+
+    Ok(merged)
+}
+
 #[derive(Debug, Eq, PartialEq)]
 struct Game {
     id: u32,
@@ -44,36 +182,129 @@ struct Game {
 }
 
 impl Game {
-    fn is_possible(&self, red: u32, green: u32, blue: u32) -> bool {
+    // A game is possible if no sample shows more cubes of any color than the bag holds; colors that
+    // don't appear in the bag at all can't appear in any sample
+    fn is_possible(&self, bag: &Sample) -> bool {
         !self
             .samples
             .iter()
-            .any(|sample| sample.red > red || sample.green > green || sample.blue > blue)
+            .any(|sample| sample.counts.iter().any(|(color, &n)| n > bag.count(color)))
     }
 
-    fn minimum_cubes_power(&self) -> u32 {
-        let red = self
-            .samples
-            .iter()
-            .map(|sample| sample.red)
-            .max()
-            .unwrap_or(0);
+    // The smallest bag that could have produced every sample in the game, which has as many cubes
+    // of each color as the most that were ever shown at once
+    fn minimum_cube_set(&self) -> Sample {
+        let mut counts: BTreeMap<String, u32> = BTreeMap::new();
 
-        let green = self
-            .samples
-            .iter()
-            .map(|sample| sample.green)
-            .max()
-            .unwrap_or(0);
+        for sample in &self.samples {
+            for (color, &n) in &sample.counts {
+                let count = counts.entry(color.clone()).or_default();
+                *count = (*count).max(n);
+            }
+        }
 
-        let blue = self
-            .samples
+        Sample { counts }
+    }
+
+    fn minimum_cubes_power(&self, colors: &[&str]) -> u32 {
+        self.minimum_cube_set().power(colors)
+    }
+
+    // Like `from_str`, but ignores extra whitespace, empty samples, and the case of both "Game"
+    // and color names
+    fn from_str_tolerant(string: &str) -> Result<Self, Box<dyn Error>> {
+        let (game_id, samples) = string.split_once(':').ok_or("Unparseable game string")?;
+
+        let id = match game_id.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [game, id] if game.eq_ignore_ascii_case("game") => id.parse()?,
+            _ => return Err("Unparseable game ID".into()),
+        };
+
+        let samples = samples
+            .split(';')
+            .filter(|sample| !sample.trim().is_empty())
+            .map(Sample::from_str_tolerant)
+            .collect::<Result<Vec<Sample>, _>>()?;
+
+        Ok(Game { id, samples })
+    }
+}
+
+// Writes one row per sample with the game's ID, the sample's (1-based) position within its game,
+// and a column for every color that appears anywhere in any game
+fn write_csv(games: &[Game], writer: impl Write) -> Result<(), Box<dyn Error>> {
+    let colors: BTreeSet<&str> = games
+        .iter()
+        .flat_map(|game| &game.samples)
+        .flat_map(|sample| sample.counts.keys().map(String::as_str))
+        .collect();
+
+    let mut csv_writer = csv::Writer::from_writer(writer);
+
+    csv_writer.write_record(["game", "sample"].into_iter().chain(colors.iter().copied()))?;
+
+    for game in games {
+        for (i, sample) in game.samples.iter().enumerate() {
+            csv_writer.write_record(
+                [game.id.to_string(), (i + 1).to_string()]
+                    .into_iter()
+                    .chain(colors.iter().map(|color| sample.count(color).to_string())),
+            )?;
+        }
+    }
+
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+type GamePredicate<'a> = Box<dyn Fn(&Game) -> bool + 'a>;
+
+// Selects games that satisfy every one of a collection of constraints
+struct GameFilter<'a> {
+    predicates: Vec<GamePredicate<'a>>,
+}
+
+impl<'a> GameFilter<'a> {
+    fn new() -> Self {
+        GameFilter {
+            predicates: Vec::new(),
+        }
+    }
+
+    // No sample may show more than `n` cubes of the given color
+    fn max(self, color: &str, n: u32) -> Self {
+        let color = String::from(color);
+
+        self.matching(move |game| game.samples.iter().all(|sample| sample.count(&color) <= n))
+    }
+
+    // At least one sample must show `n` or more cubes of the given color
+    fn min(self, color: &str, n: u32) -> Self {
+        let color = String::from(color);
+
+        self.matching(move |game| game.samples.iter().any(|sample| sample.count(&color) >= n))
+    }
+
+    fn matching(mut self, predicate: impl Fn(&Game) -> bool + 'a) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    fn matches(&self, game: &Game) -> bool {
+        self.predicates.iter().all(|predicate| predicate(game))
+    }
+
+    fn ids(&self, games: &[Game]) -> Vec<u32> {
+        games
             .iter()
-            .map(|sample| sample.blue)
-            .max()
-            .unwrap_or(0);
+            .filter(|game| self.matches(game))
+            .map(|game| game.id)
+            .collect()
+    }
 
-        red * green * blue
+    fn id_sum(&self, games: &[Game]) -> u32 {
+        self.ids(games).iter().sum()
     }
 }
 
@@ -100,54 +331,218 @@ impl FromStr for Game {
     }
 }
 
+// Aggregates over every sample in every game
+#[derive(Debug, Eq, PartialEq)]
+struct GameStatistics {
+    games: usize,
+    samples: usize,
+    colors: BTreeMap<String, ColorStatistics>,
+    // Maps a number of samples to the number of games with exactly that many samples
+    samples_per_game: BTreeMap<usize, usize>,
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct ColorStatistics {
+    max: u32,
+    total: u32,
+}
+
+impl GameStatistics {
+    fn new(games: &[Game]) -> Self {
+        let mut colors: BTreeMap<String, ColorStatistics> = BTreeMap::new();
+        let mut samples_per_game = BTreeMap::new();
+
+        for game in games {
+            *samples_per_game.entry(game.samples.len()).or_default() += 1;
+
+            for sample in &game.samples {
+                for (color, &n) in &sample.counts {
+                    let color_statistics = colors.entry(color.clone()).or_default();
+
+                    color_statistics.max = color_statistics.max.max(n);
+                    color_statistics.total += n;
+                }
+            }
+        }
+
+        GameStatistics {
+            games: games.len(),
+            samples: games.iter().map(|game| game.samples.len()).sum(),
+            colors,
+            samples_per_game,
+        }
+    }
+
+    // Samples that don't mention a color count as having zero cubes of that color
+    fn mean(&self, color: &str) -> f64 {
+        match (self.colors.get(color), self.samples) {
+            (Some(color_statistics), samples) if samples > 0 => {
+                color_statistics.total as f64 / samples as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl Display for GameStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Games: {}, samples: {}", self.games, self.samples)?;
+        writeln!(
+            f,
+            "{:>10} {:>8} {:>8} {:>8}",
+            "color", "max", "total", "mean"
+        )?;
+
+        for (color, color_statistics) in &self.colors {
+            writeln!(
+                f,
+                "{:>10} {:>8} {:>8} {:>8.2}",
+                color,
+                color_statistics.max,
+                color_statistics.total,
+                self.mean(color)
+            )?;
+        }
+
+        writeln!(f, "Samples per game:")?;
+
+        for (samples, games) in &self.samples_per_game {
+            writeln!(f, "{:>10}: {} game(s)", samples, games)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The colors of cubes in the puzzle's bag, which are the ones that count toward a game's power
+const CUBE_COLORS: [&str; 3] = ["red", "green", "blue"];
+
 #[derive(Debug, Eq, PartialEq)]
 struct Sample {
-    red: u32,
-    green: u32,
-    blue: u32,
+    counts: BTreeMap<String, u32>,
+}
+
+impl Sample {
+    fn count(&self, color: &str) -> u32 {
+        self.counts.get(color).copied().unwrap_or(0)
+    }
+
+    // The product of the number of cubes of each of the given colors; a sample with no cubes of
+    // one of those colors has a power of zero
+    fn power(&self, colors: &[&str]) -> u32 {
+        colors.iter().map(|color| self.count(color)).product()
+    }
+
+    fn from_str_tolerant(string: &str) -> Result<Self, Box<dyn Error>> {
+        let mut counts = BTreeMap::new();
+
+        for count in string.split(',').filter(|count| !count.trim().is_empty()) {
+            if let [n, color] = count.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                let color = color.to_lowercase();
+
+                if counts.insert(color.clone(), n.parse()?).is_some() {
+                    return Err(format!("Duplicate color: {}", color).into());
+                }
+            } else {
+                return Err("Unprocessable cube count".into());
+            }
+        }
+
+        Ok(Sample { counts })
+    }
+}
+
+impl Display for Sample {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let counts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(color, n)| format!("{} {}", n, color))
+            .collect();
+
+        write!(f, "{}", counts.join(", "))
+    }
+}
+
+// Lists the minimum cube set and its power for each game, with a column for every color that
+// appears in any game
+struct MinimumCubeTable<'a>(&'a [Game]);
+
+impl Display for MinimumCubeTable<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let minimum_cube_sets: Vec<(u32, Sample)> = self
+            .0
+            .iter()
+            .map(|game| (game.id, game.minimum_cube_set()))
+            .collect();
+
+        let mut colors: Vec<&str> = minimum_cube_sets
+            .iter()
+            .flat_map(|(_, sample)| sample.counts.keys().map(String::as_str))
+            .collect();
+
+        colors.sort();
+        colors.dedup();
+
+        // Columns need to be wide enough for the longest color name and the largest power
+        let width = colors
+            .iter()
+            .map(|color| color.len())
+            .chain(
+                minimum_cube_sets
+                    .iter()
+                    .map(|(_, sample)| sample.power(&colors).to_string().len()),
+            )
+            .max()
+            .unwrap_or(0)
+            .max("power".len());
+
+        write!(f, "{:>6}", "game")?;
+
+        for color in &colors {
+            write!(f, " {:>width$}", color)?;
+        }
+
+        writeln!(f, " {:>width$}", "power")?;
+
+        for (id, minimum_cube_set) in &minimum_cube_sets {
+            write!(f, "{:>6}", id)?;
+
+            for color in &colors {
+                write!(f, " {:>width$}", minimum_cube_set.count(color))?;
+            }
+
+            writeln!(f, " {:>width$}", minimum_cube_set.power(&colors))?;
+        }
+
+        Ok(())
+    }
 }
 
 impl FromStr for Sample {
     type Err = Box<dyn Error>;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        let mut red = 0;
-        let mut green = 0;
-        let mut blue = 0;
+        let mut counts = BTreeMap::new();
 
         for count in string.split(", ") {
             if let [n, color] = count.split(' ').collect::<Vec<&str>>().as_slice() {
-                let n = n.parse()?;
-
-                match *color {
-                    "red" => {
-                        red = n;
-                    }
-
-                    "green" => {
-                        green = n;
-                    }
-
-                    "blue" => {
-                        blue = n;
-                    }
-
-                    _ => {
-                        return Err("Unexpected color".into());
-                    }
+                if counts.insert(String::from(*color), n.parse()?).is_some() {
+                    return Err(format!("Duplicate color: {}", color).into());
                 }
             } else {
                 return Err("Unprocessable cube count".into());
             }
         }
 
-        Ok(Sample { red, green, blue })
+        Ok(Sample { counts })
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use indoc::indoc;
 
     #[test]
     fn test_game_from_string() {
@@ -156,19 +551,20 @@ mod test {
                 id: 1,
                 samples: vec![
                     Sample {
-                        red: 4,
-                        green: 0,
-                        blue: 3
+                        counts: BTreeMap::from([
+                            (String::from("blue"), 3),
+                            (String::from("red"), 4)
+                        ])
                     },
                     Sample {
-                        red: 1,
-                        green: 2,
-                        blue: 6
+                        counts: BTreeMap::from([
+                            (String::from("red"), 1),
+                            (String::from("green"), 2),
+                            (String::from("blue"), 6)
+                        ])
                     },
                     Sample {
-                        red: 0,
-                        green: 2,
-                        blue: 0
+                        counts: BTreeMap::from([(String::from("green"), 2)])
                     }
                 ]
             },
@@ -176,8 +572,39 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_game_from_string_tolerant() {
+        let expected =
+            Game::from_str("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap();
+
+        for game_string in [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game  1:3 blue,  4 red;1 red , 2 green, 6 blue; 2 green;",
+            "game 1: 3 Blue, 4 RED; ; 1 red, 2 Green, 6 blue; 2 green; ",
+            "\tGame 1 :  3 blue, 4 red,; 1 red, 2 green, 6 blue;; 2 green  ",
+        ] {
+            assert_eq!(expected, Game::from_str_tolerant(game_string).unwrap());
+        }
+
+        assert_eq!(
+            Game {
+                id: 2,
+                samples: vec![]
+            },
+            Game::from_str_tolerant("Game 2:").unwrap()
+        );
+
+        assert!(Game::from_str("Game  1: 3 blue").is_err());
+        assert!(Game::from_str_tolerant("Game 1 3 blue").is_err());
+        assert!(Game::from_str_tolerant("Game: 3 blue").is_err());
+        assert!(Game::from_str_tolerant("Game 1: 3 blue green").is_err());
+        assert!(Game::from_str_tolerant("Game 1: 3 blue, 2 BLUE").is_err());
+    }
+
     #[test]
     fn test_game_is_possible() {
+        let bag = Sample::from_str("12 red, 13 green, 14 blue").unwrap();
+
         for (game_string, expect_possible) in [
             (
                 "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
@@ -202,7 +629,7 @@ mod test {
         ] {
             assert_eq!(
                 expect_possible,
-                Game::from_str(game_string).unwrap().is_possible(12, 13, 14)
+                Game::from_str(game_string).unwrap().is_possible(&bag)
             );
         }
     }
@@ -233,8 +660,224 @@ mod test {
         ] {
             assert_eq!(
                 expected_power,
-                Game::from_str(game_string).unwrap().minimum_cubes_power()
+                Game::from_str(game_string)
+                    .unwrap()
+                    .minimum_cubes_power(&CUBE_COLORS)
             );
         }
+
+        // Games that never show any cubes of one of the colors have a power of zero
+        assert_eq!(
+            0,
+            Game::from_str("Game 1: 3 blue, 4 red; 1 red, 6 blue")
+                .unwrap()
+                .minimum_cubes_power(&CUBE_COLORS)
+        );
+
+        assert_eq!(
+            0,
+            Game::from_str_tolerant("Game 2:")
+                .unwrap()
+                .minimum_cubes_power(&CUBE_COLORS)
+        );
+    }
+
+    #[test]
+    fn test_minimum_cube_set() {
+        let game =
+            Game::from_str("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap();
+
+        assert_eq!(
+            Sample::from_str("4 red, 2 green, 6 blue").unwrap(),
+            game.minimum_cube_set()
+        );
+
+        assert_eq!(
+            "6 blue, 2 green, 4 red",
+            game.minimum_cube_set().to_string()
+        );
+        assert_eq!(48, game.minimum_cube_set().power(&CUBE_COLORS));
+    }
+
+    #[test]
+    fn test_game_statistics() {
+        let games: Vec<Game> = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+            "Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green",
+        ]
+        .iter()
+        .map(|game| Game::from_str(game).unwrap())
+        .collect();
+
+        let statistics = GameStatistics::new(&games);
+
+        assert_eq!(5, statistics.games);
+        assert_eq!(14, statistics.samples);
+
+        assert_eq!(
+            BTreeMap::from([
+                (String::from("blue"), ColorStatistics { max: 15, total: 50 }),
+                (
+                    String::from("green"),
+                    ColorStatistics { max: 13, total: 48 }
+                ),
+                (String::from("red"), ColorStatistics { max: 20, total: 61 }),
+            ]),
+            statistics.colors
+        );
+
+        assert_eq!(
+            BTreeMap::from([(2, 1), (3, 4)]),
+            statistics.samples_per_game
+        );
+        assert_eq!(50.0 / 14.0, statistics.mean("blue"));
+        assert_eq!(0.0, statistics.mean("teal"));
+        assert_eq!(0.0, GameStatistics::new(&[]).mean("blue"));
+    }
+
+    #[test]
+    fn test_games_from_reader() {
+        let games = indoc! {"
+            Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+            Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+            Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+            Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+            Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+        "};
+
+        let power_sum = games_from_reader(games.as_bytes(), false).try_fold(0, |sum, game| {
+            game.map(|game| sum + game.minimum_cubes_power(&CUBE_COLORS))
+        });
+
+        assert_eq!(2286, power_sum.unwrap());
+
+        let mut games = games_from_reader("Game 1: 2 RED;\n\nGame 2 4 red\n".as_bytes(), true);
+
+        assert_eq!(
+            Game::from_str("Game 1: 2 red").unwrap(),
+            games.next().unwrap().unwrap()
+        );
+
+        assert_eq!(
+            "line 3: Unparseable game string (\"Game 2 4 red\")",
+            games.next().unwrap().unwrap_err().to_string()
+        );
+
+        assert!(games.next().is_none());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let games = vec![
+            Game::from_str("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap(),
+            Game::from_str("Game 4: 1 teal").unwrap(),
+        ];
+
+        let mut csv = Vec::new();
+        write_csv(&games, &mut csv).unwrap();
+
+        assert_eq!(
+            indoc! {"
+                game,sample,blue,green,red,teal
+                1,1,3,0,4,0
+                1,2,6,2,1,0
+                1,3,0,2,0,0
+                4,1,0,0,0,1
+            "},
+            String::from_utf8(csv).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_games() {
+        let first = || {
+            vec![
+                Game::from_str("Game 1: 3 blue").unwrap(),
+                Game::from_str("Game 2: 4 red").unwrap(),
+            ]
+        };
+
+        let second = || {
+            vec![
+                Game::from_str("Game 2: 5 green").unwrap(),
+                Game::from_str("Game 7: 1 blue").unwrap(),
+            ]
+        };
+
+        assert_eq!(
+            "Game 2 in b.txt has the same ID as a game in a.txt",
+            merge_games(vec![("a.txt", first()), ("b.txt", second())], false)
+                .unwrap_err()
+                .to_string()
+        );
+
+        assert_eq!(
+            vec![1, 2, 8, 7],
+            merge_games(vec![("a.txt", first()), ("b.txt", second())], true)
+                .unwrap()
+                .iter()
+                .map(|game| game.id)
+                .collect::<Vec<u32>>()
+        );
+
+        assert_eq!(
+            first(),
+            merge_games(vec![("a.txt", first()), ("c.txt", vec![])], false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_game_filter() {
+        let games: Vec<Game> = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+            "Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green",
+        ]
+        .iter()
+        .map(|game| Game::from_str(game).unwrap())
+        .collect();
+
+        let possible = GameFilter::new()
+            .max("red", 12)
+            .max("green", 13)
+            .max("blue", 14);
+
+        assert_eq!(vec![1, 2, 5], possible.ids(&games));
+        assert_eq!(8, possible.id_sum(&games));
+
+        assert_eq!(
+            vec![3, 4],
+            GameFilter::new().min("red", 5).min("blue", 6).ids(&games)
+        );
+
+        assert_eq!(
+            vec![2, 4],
+            GameFilter::new()
+                .max("red", 15)
+                .matching(|game| game.id % 2 == 0)
+                .ids(&games)
+        );
+
+        assert_eq!(15, GameFilter::new().id_sum(&games));
+    }
+
+    #[test]
+    fn test_arbitrary_colors() {
+        let game = Game::from_str("Game 7: 2 teal, 1 red; 5 teal; 3 mauve, 1 teal, 2 red").unwrap();
+
+        assert_eq!(5, game.samples[1].count("teal"));
+        assert_eq!(0, game.samples[1].count("red"));
+        assert_eq!(30, game.minimum_cubes_power(&["teal", "red", "mauve"]));
+        assert_eq!(0, game.minimum_cubes_power(&CUBE_COLORS));
+
+        assert!(game.is_possible(&Sample::from_str("5 teal, 2 red, 3 mauve").unwrap()));
+        assert!(!game.is_possible(&Sample::from_str("5 teal, 2 red").unwrap()));
+
+        assert!(Sample::from_str("1 red, 2 red").is_err());
     }
 }
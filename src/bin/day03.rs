@@ -1,7 +1,10 @@
-use crate::Cell::{Digit, Empty, Gear, OtherSymbol};
-use std::collections::{HashMap, HashSet};
+use crate::Cell::{Digit, Empty, Symbol};
+use advent_of_code_2023::grid_render::{write_grid, Color, Glyph};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
@@ -14,9 +17,71 @@ fn main() -> Result<(), Box<dyn Error>> {
             let mut board_string = String::new();
             File::open(path)?.read_to_string(&mut board_string)?;
 
-            EngineSchematic::from_str(board_string.as_str())?
+            if let Some(representation) = args
+                .iter()
+                .position(|arg| arg == "--representation")
+                .and_then(|i| args.get(i + 1))
+            {
+                EngineSchematic::parse(
+                    board_string.as_str(),
+                    Representation::from_str(representation)?,
+                )?
+            } else {
+                EngineSchematic::from_str(board_string.as_str())?
+            }
         };
 
+        let schematic = schematic.with_adjacency(Adjacency {
+            radius: if let Some(radius) = args
+                .iter()
+                .position(|arg| arg == "--radius")
+                .and_then(|i| args.get(i + 1))
+            {
+                radius.parse()?
+            } else {
+                1
+            },
+            diagonals: !args.iter().skip(2).any(|arg| arg == "--orthogonal"),
+        });
+
+        let custom_gear_symbols = args
+            .iter()
+            .position(|arg| arg == "--gear-symbols")
+            .and_then(|i| args.get(i + 1));
+
+        let custom_gear_arity = args
+            .iter()
+            .position(|arg| arg == "--gear-arity")
+            .and_then(|i| args.get(i + 1));
+
+        let gear_symbols = custom_gear_symbols.map(String::as_str).unwrap_or("*");
+        let gear_arity = custom_gear_arity
+            .map(|arity| arity.parse())
+            .unwrap_or(Ok(2))?;
+
+        if args.iter().skip(2).any(|arg| arg == "--visualize") {
+            print!(
+                "{}",
+                SchematicMap {
+                    schematic: &schematic,
+                    gear_symbols,
+                    gear_arity,
+                }
+            );
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--orphans") {
+            for orphan in schematic.orphan_numbers() {
+                // Lines and columns are 1-based to match what most editors show
+                println!(
+                    "Orphan number {} at line {}, column {}",
+                    orphan.value,
+                    orphan.y + 1,
+                    orphan.x + 1
+                );
+            }
+        }
+
         println!(
             "Sum of part numbers adjacent to symbols: {}",
             schematic.part_number_sum()
@@ -24,9 +89,27 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         println!("Sum of gear ratios: {}", schematic.gear_ratio_sum());
 
+        if custom_gear_symbols.is_some() || custom_gear_arity.is_some() {
+            let gear_ratios = schematic.gear_ratios(gear_symbols, gear_arity);
+
+            for (symbol, ratio) in &gear_ratios {
+                println!(
+                    "{} at ({}, {}): {}",
+                    symbol.symbol, symbol.x, symbol.y, ratio
+                );
+            }
+
+            println!(
+                "Sum of ratios for {:?} with {} adjacent parts: {}",
+                gear_symbols,
+                gear_arity,
+                gear_ratios.iter().map(|(_, ratio)| ratio).sum::<u32>()
+            );
+        }
+
         Ok(())
     } else {
-        Err("Usage: day03 INPUT_FILE_PATH".into())
+        Err("Usage: day03 INPUT_FILE_PATH [--gear-symbols SYMBOLS] [--gear-arity N] [--representation dense|sparse] [--radius N] [--orthogonal] [--visualize] [--orphans]".into())
     }
 }
 
@@ -34,178 +117,398 @@ fn main() -> Result<(), Box<dyn Error>> {
 enum Cell {
     Empty,
     Digit(u8),
-    Gear,
-    OtherSymbol,
+    Symbol(char),
+}
+
+impl From<char> for Cell {
+    fn from(c: char) -> Self {
+        match c {
+            '.' => Empty,
+            '0'..='9' => Digit(c.to_digit(10).unwrap() as u8),
+            _ => Symbol(c),
+        }
+    }
 }
 
 struct EngineSchematic {
-    cells: Vec<Cell>,
+    cells: Cells,
 
     width: usize,
     height: usize,
+
+    adjacency: Adjacency,
+}
+
+// Decides which cells count as adjacent to a number: every cell within `radius` steps of one of its
+// digits, either in any direction (a square around the number) or only in straight lines
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Adjacency {
+    radius: usize,
+    diagonals: bool,
+}
+
+impl Default for Adjacency {
+    fn default() -> Self {
+        Adjacency {
+            radius: 1,
+            diagonals: true,
+        }
+    }
 }
 
+enum Cells {
+    Dense(Vec<Cell>),
+
+    // Only non-empty cells, keyed by (y, x) so they iterate in reading order
+    Sparse(BTreeMap<(usize, usize), Cell>),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Representation {
+    Dense,
+    Sparse,
+}
+
+// Schematics with fewer than one non-empty cell in this many get a sparse representation by default
+const SPARSE_THRESHOLD: usize = 4;
+
 impl EngineSchematic {
+    fn parse(string: &str, representation: Representation) -> Result<Self, Box<dyn Error>> {
+        let rows: Vec<&str> = string.lines().collect();
+
+        let width = rows.first().map(|row| row.chars().count()).unwrap_or(0);
+        let height = rows.len();
+
+        if rows.iter().any(|row| row.chars().count() != width) {
+            return Err("Non-rectangular engine schematic".into());
+        }
+
+        let non_empty_cells = rows.iter().enumerate().flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .map(move |(x, c)| (x, y, Cell::from(c)))
+                .filter(|(_, _, cell)| *cell != Empty)
+        });
+
+        let cells = match representation {
+            Representation::Dense => {
+                let mut cells = vec![Empty; width * height];
+
+                for (x, y, cell) in non_empty_cells {
+                    cells[y * width + x] = cell;
+                }
+
+                Cells::Dense(cells)
+            }
+            Representation::Sparse => {
+                Cells::Sparse(non_empty_cells.map(|(x, y, cell)| ((y, x), cell)).collect())
+            }
+        };
+
+        Ok(EngineSchematic {
+            cells,
+            width,
+            height,
+            adjacency: Adjacency::default(),
+        })
+    }
+
+    fn with_adjacency(self, adjacency: Adjacency) -> Self {
+        EngineSchematic { adjacency, ..self }
+    }
+
     fn cell(&self, x: isize, y: isize) -> Cell {
         if x < 0 || x >= self.width as isize || y < 0 || y >= self.height as isize {
             Empty
         } else {
-            *self
-                .cells
-                .get(y as usize * self.width + x as usize)
-                .unwrap()
+            match &self.cells {
+                Cells::Dense(cells) => cells[y as usize * self.width + x as usize],
+                Cells::Sparse(cells) => cells
+                    .get(&(y as usize, x as usize))
+                    .copied()
+                    .unwrap_or(Empty),
+            }
         }
     }
 
-    fn has_adjacent_symbol(&self, x: isize, y: isize) -> bool {
-        for i in x - 1..=x + 1 {
-            for j in y - 1..=y + 1 {
-                match self.cell(i, j) {
-                    Gear | OtherSymbol => return true,
-                    _ => continue,
-                }
-            }
+    // Returns the position and contents of every non-empty cell in reading order; for sparse
+    // schematics, this never has to look at empty cells at all
+    fn non_empty_cells(&self) -> Box<dyn Iterator<Item = (usize, usize, Cell)> + '_> {
+        match &self.cells {
+            Cells::Dense(cells) => Box::new(
+                cells
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, cell)| **cell != Empty)
+                    .map(|(i, cell)| (i % self.width, i / self.width, *cell)),
+            ),
+            Cells::Sparse(cells) => Box::new(cells.iter().map(|(&(y, x), &cell)| (x, y, cell))),
         }
+    }
 
-        false
+    // Finds the symbols near a number with the given position and length according to this
+    // schematic's adjacency rules; the number's own cells are all digits, so they never need to be
+    // checked
+    fn adjacent_symbols(&self, x: usize, y: usize, len: usize) -> BTreeSet<SymbolPosition> {
+        let (x, y, len) = (x as isize, y as isize, len as isize);
+        let radius = self.adjacency.radius as isize;
+
+        let neighbors: Vec<(isize, isize)> = if self.adjacency.diagonals {
+            (y - radius..=y + radius)
+                .flat_map(|j| (x - radius..x + len + radius).map(move |i| (i, j)))
+                .filter(|&(i, j)| j != y || i < x || i >= x + len)
+                .collect()
+        } else {
+            (1..=radius)
+                .flat_map(|d| {
+                    (x..x + len)
+                        .flat_map(move |i| [(i, y - d), (i, y + d)])
+                        .chain([(x - d, y), (x + len - 1 + d, y)])
+                })
+                .collect()
+        };
+
+        neighbors
+            .into_iter()
+            .filter_map(|(i, j)| match self.cell(i, j) {
+                Symbol(symbol) => Some(SymbolPosition {
+                    x: i as usize,
+                    y: j as usize,
+                    symbol,
+                }),
+                _ => None,
+            })
+            .collect()
     }
 
-    fn part_number_sum(&self) -> u32 {
-        let mut part_number_sum = 0;
-
-        let mut current_part_number = 0;
-        let mut found_adjacent_symbol = false;
-
-        for y in 0..self.height {
-            for x in 0..self.width {
-                match self.cell(x as isize, y as isize) {
-                    Digit(n) => {
-                        if !found_adjacent_symbol {
-                            found_adjacent_symbol =
-                                self.has_adjacent_symbol(x as isize, y as isize);
-                        }
-
-                        current_part_number *= 10;
-                        current_part_number += n as u32;
-                    }
-                    _ => {
-                        if found_adjacent_symbol {
-                            part_number_sum += current_part_number;
-                        }
+    // Returns every number in the schematic in reading order, whether or not it's actually adjacent
+    // to a symbol
+    fn part_numbers(&self) -> Vec<PartNumber> {
+        let mut part_numbers = self.number_tokens();
+
+        for part_number in &mut part_numbers {
+            part_number.adjacent_symbols =
+                self.adjacent_symbols(part_number.x, part_number.y, part_number.len);
+        }
 
-                        current_part_number = 0;
-                        found_adjacent_symbol = false;
+        part_numbers
+    }
+
+    // Returns numbers that aren't adjacent to any symbol (and so aren't counted as part numbers) in
+    // reading order
+    fn orphan_numbers(&self) -> Vec<PartNumber> {
+        self.part_numbers()
+            .into_iter()
+            .filter(|part_number| part_number.adjacent_symbols.is_empty())
+            .collect()
+    }
+
+    // Gathers runs of digits into numbers in a single pass, leaving adjacent symbols for later
+    fn number_tokens(&self) -> Vec<PartNumber> {
+        let mut number_tokens: Vec<PartNumber> = Vec::new();
+
+        for (x, y, cell) in self.non_empty_cells() {
+            if let Digit(n) = cell {
+                // A digit continues the previous number only if it's immediately to its right
+                match number_tokens.last_mut() {
+                    Some(token) if token.y == y && token.x + token.len == x => {
+                        token.value *= 10;
+                        token.value += n as u32;
+                        token.len += 1;
                     }
+                    _ => number_tokens.push(PartNumber {
+                        value: n as u32,
+                        x,
+                        y,
+                        len: 1,
+                        adjacent_symbols: BTreeSet::new(),
+                    }),
                 }
             }
-
-            if found_adjacent_symbol {
-                part_number_sum += current_part_number;
-            }
-
-            current_part_number = 0;
-            found_adjacent_symbol = false;
         }
 
-        part_number_sum
+        number_tokens
     }
 
-    fn adjacent_gear_indices(&self, x: isize, y: isize) -> HashSet<usize> {
-        let mut adjacent_gear_indices = HashSet::new();
+    // Maps every symbol in the schematic (including those with no neighbors) to the part numbers
+    // adjacent to it, in reading order
+    fn symbol_index(&self) -> BTreeMap<SymbolPosition, Vec<PartNumber>> {
+        let mut symbol_index = BTreeMap::new();
 
-        for i in x - 1..=x + 1 {
-            for j in y - 1..=y + 1 {
-                if self.cell(i, j) == Gear {
-                    adjacent_gear_indices.insert(i as usize * self.width + j as usize);
-                }
+        for (x, y, cell) in self.non_empty_cells() {
+            if let Symbol(symbol) = cell {
+                symbol_index.insert(SymbolPosition { x, y, symbol }, Vec::new());
+            }
+        }
+
+        for part_number in self.part_numbers() {
+            for symbol in &part_number.adjacent_symbols {
+                symbol_index
+                    .get_mut(symbol)
+                    .unwrap()
+                    .push(part_number.clone());
             }
         }
 
-        adjacent_gear_indices
+        symbol_index
+    }
+
+    fn part_number_sum(&self) -> u32 {
+        // A part number adjacent to more than one symbol still only counts once
+        let mut counted_part_numbers = HashSet::new();
+
+        self.symbol_index()
+            .values()
+            .flatten()
+            .filter(|part_number| counted_part_numbers.insert((part_number.x, part_number.y)))
+            .map(|part_number| part_number.value)
+            .sum()
     }
 
     fn gear_ratio_sum(&self) -> u32 {
-        let mut part_numbers_by_adjacent_gear_indices = HashMap::new();
+        self.gear_ratios("*", 2)
+            .iter()
+            .map(|(_, ratio)| ratio)
+            .sum()
+    }
 
-        let mut current_part_number = 0;
+    // Treats any of the given symbols as a gear if it's adjacent to exactly `arity` part numbers,
+    // and returns the product of those part numbers for each gear in reading order
+    fn gear_ratios(&self, symbols: &str, arity: usize) -> Vec<(SymbolPosition, u32)> {
+        self.symbol_index()
+            .into_iter()
+            .filter(|(symbol, part_numbers)| {
+                symbols.contains(symbol.symbol) && part_numbers.len() == arity
+            })
+            .map(|(symbol, part_numbers)| {
+                (
+                    symbol,
+                    part_numbers
+                        .iter()
+                        .map(|part_number| part_number.value)
+                        .product(),
+                )
+            })
+            .collect()
+    }
+}
 
-        for y in 0..self.height {
-            let mut adjacent_gear_indices = HashSet::new();
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PartNumber {
+    value: u32,
 
-            for x in 0..self.width {
-                match self.cell(x as isize, y as isize) {
-                    Digit(n) => {
-                        adjacent_gear_indices
-                            .extend(self.adjacent_gear_indices(x as isize, y as isize));
+    // The position of the number's first digit; numbers always run left to right within one row
+    x: usize,
+    y: usize,
+    len: usize,
 
-                        current_part_number *= 10;
-                        current_part_number += n as u32;
-                    }
-                    _ => {
-                        for gear_index in &adjacent_gear_indices {
-                            part_numbers_by_adjacent_gear_indices
-                                .entry(*gear_index)
-                                .or_insert(Vec::new())
-                                .push(current_part_number)
-                        }
-
-                        current_part_number = 0;
-                        adjacent_gear_indices.clear();
-                    }
-                }
-            }
+    adjacent_symbols: BTreeSet<SymbolPosition>,
+}
 
-            for gear_index in &adjacent_gear_indices {
-                part_numbers_by_adjacent_gear_indices
-                    .entry(*gear_index)
-                    .or_insert(Vec::new())
-                    .push(current_part_number)
-            }
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+struct SymbolPosition {
+    x: usize,
+    y: usize,
+    symbol: char,
+}
+
+// Symbols sort in reading order
+impl Ord for SymbolPosition {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x, self.symbol).cmp(&(other.y, other.x, other.symbol))
+    }
+}
+
+impl PartialOrd for SymbolPosition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-            current_part_number = 0;
-            adjacent_gear_indices.clear();
+// Colors part numbers by whether they were counted and symbols by whether they're gears
+struct SchematicMap<'a> {
+    schematic: &'a EngineSchematic,
+    gear_symbols: &'a str,
+    gear_arity: usize,
+}
+
+impl SchematicMap<'_> {
+    const PART_NUMBER_COLOR: Color = Color::GREEN;
+    const ORPHAN_COLOR: Color = Color::RED;
+    const GEAR_COLOR: Color = Color::BOLD_YELLOW;
+    const SYMBOL_COLOR: Color = Color::CYAN;
+    const EMPTY_COLOR: Color = Color::GRAY;
+}
+
+impl Display for SchematicMap<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut digit_colors = HashMap::new();
+
+        for part_number in self.schematic.part_numbers() {
+            let color = if part_number.adjacent_symbols.is_empty() {
+                Self::ORPHAN_COLOR
+            } else {
+                Self::PART_NUMBER_COLOR
+            };
+
+            for x in part_number.x..part_number.x + part_number.len {
+                digit_colors.insert((x, part_number.y), color);
+            }
         }
 
-        part_numbers_by_adjacent_gear_indices
+        let gears: HashSet<(usize, usize)> = self
+            .schematic
+            .gear_ratios(self.gear_symbols, self.gear_arity)
             .iter()
-            .filter_map(|(_, adjacent_part_numbers)| {
-                if adjacent_part_numbers.len() == 2 {
-                    Some(adjacent_part_numbers.iter().product::<u32>())
-                } else {
-                    None
+            .map(|(gear, _)| (gear.x, gear.y))
+            .collect();
+
+        write_grid(
+            f,
+            self.schematic.width,
+            self.schematic.height,
+            |x, y| match self.schematic.cell(x as isize, y as isize) {
+                Empty => Glyph::colored('.', Self::EMPTY_COLOR),
+                Digit(n) => Glyph::colored(char::from(b'0' + n), digit_colors[&(x, y)]),
+                Symbol(symbol) if gears.contains(&(x, y)) => {
+                    Glyph::colored(symbol, Self::GEAR_COLOR)
                 }
-            })
-            .sum()
+                Symbol(symbol) => Glyph::colored(symbol, Self::SYMBOL_COLOR),
+            },
+        )
     }
 }
 
 impl FromStr for EngineSchematic {
     type Err = Box<dyn Error>;
 
+    // Chooses a representation based on how many cells are empty
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        let width = string.find('\n').unwrap_or(string.len());
-
-        let cells: Vec<Cell> = string
+        let (non_empty, total) = string
             .chars()
             .filter(|c| !c.is_whitespace())
-            .map(|c| match c {
-                '.' => Empty,
-                '0'..='9' => Digit(c.to_digit(10).unwrap() as u8),
-                '*' => Gear,
-                _ => OtherSymbol,
-            })
-            .collect();
+            .fold((0, 0), |(non_empty, total), c| {
+                (non_empty + usize::from(c != '.'), total + 1)
+            });
 
-        if cells.len() % width != 0 {
-            Err("Non-rectangular engine schematic".into())
+        let representation = if non_empty * SPARSE_THRESHOLD < total {
+            Representation::Sparse
         } else {
-            let height = cells.len() / width;
+            Representation::Dense
+        };
 
-            Ok(EngineSchematic {
-                cells,
-                width,
-                height,
-            })
+        EngineSchematic::parse(string, representation)
+    }
+}
+
+impl FromStr for Representation {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dense" => Ok(Representation::Dense),
+            "sparse" => Ok(Representation::Sparse),
+            _ => Err(format!("Unrecognized representation: {}", s).into()),
         }
     }
 }
@@ -247,4 +550,291 @@ mod test {
                 .gear_ratio_sum()
         );
     }
+
+    #[test]
+    fn test_part_numbers() {
+        let part_numbers = EngineSchematic::from_str(TEST_SCHEMATIC)
+            .unwrap()
+            .part_numbers();
+
+        assert_eq!(10, part_numbers.len());
+
+        assert_eq!(
+            PartNumber {
+                value: 467,
+                x: 0,
+                y: 0,
+                len: 3,
+                adjacent_symbols: BTreeSet::from([SymbolPosition {
+                    x: 3,
+                    y: 1,
+                    symbol: '*'
+                }]),
+            },
+            part_numbers[0]
+        );
+
+        assert_eq!(
+            PartNumber {
+                value: 114,
+                x: 5,
+                y: 0,
+                len: 3,
+                adjacent_symbols: BTreeSet::new(),
+            },
+            part_numbers[1]
+        );
+
+        assert_eq!(
+            (592, 2, 6),
+            (part_numbers[6].value, part_numbers[6].x, part_numbers[6].y)
+        );
+    }
+
+    #[test]
+    fn test_gear_ratios() {
+        let schematic = EngineSchematic::from_str(TEST_SCHEMATIC).unwrap();
+
+        assert_eq!(
+            vec![
+                (
+                    SymbolPosition {
+                        x: 3,
+                        y: 1,
+                        symbol: '*'
+                    },
+                    16345
+                ),
+                (
+                    SymbolPosition {
+                        x: 5,
+                        y: 8,
+                        symbol: '*'
+                    },
+                    451490
+                ),
+            ],
+            schematic.gear_ratios("*", 2)
+        );
+
+        assert_eq!(
+            vec![633, 617, 592],
+            schematic
+                .gear_ratios("*+#", 1)
+                .iter()
+                .map(|(_, ratio)| *ratio)
+                .collect::<Vec<u32>>()
+        );
+
+        let schematic = EngineSchematic::from_str(indoc! {"
+            .12..
+            3.#.4
+            .5...
+            ..#7.
+            6.8..
+        "})
+        .unwrap();
+
+        assert_eq!(
+            vec![(
+                SymbolPosition {
+                    x: 2,
+                    y: 1,
+                    symbol: '#'
+                },
+                12 * 5
+            )],
+            schematic.gear_ratios("#", 2)
+        );
+
+        assert_eq!(5 * 7 * 8, schematic.gear_ratios("#", 3)[0].1);
+    }
+
+    #[test]
+    fn test_schematic_map_gears() {
+        let schematic = EngineSchematic::from_str(TEST_SCHEMATIC).unwrap();
+
+        let render = |gear_symbols, gear_arity| {
+            SchematicMap {
+                schematic: &schematic,
+                gear_symbols,
+                gear_arity,
+            }
+            .to_string()
+        };
+
+        let highlighted_gear =
+            |symbol| Glyph::colored(symbol, SchematicMap::GEAR_COLOR).to_string();
+
+        let default_map = render("*", 2);
+        assert_eq!(2, default_map.matches(&highlighted_gear('*')).count());
+        assert!(!default_map.contains(&highlighted_gear('#')));
+
+        // Highlighted gears should follow the configured symbols and arity
+        let custom_map = render("#", 1);
+        assert!(!custom_map.contains(&highlighted_gear('*')));
+        assert_eq!(1, custom_map.matches(&highlighted_gear('#')).count());
+    }
+
+    #[test]
+    fn test_symbol_index() {
+        let symbol_index = EngineSchematic::from_str(TEST_SCHEMATIC)
+            .unwrap()
+            .symbol_index();
+
+        assert_eq!(
+            vec![
+                ('*', vec![467, 35]),
+                ('#', vec![633]),
+                ('*', vec![617]),
+                ('+', vec![592]),
+                ('$', vec![664]),
+                ('*', vec![755, 598]),
+            ],
+            symbol_index
+                .iter()
+                .map(|(symbol, part_numbers)| (
+                    symbol.symbol,
+                    part_numbers
+                        .iter()
+                        .map(|part_number| part_number.value)
+                        .collect::<Vec<u32>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let schematic = EngineSchematic::from_str(indoc! {"
+            .#.
+            .1.
+            ..*
+            @..
+        "})
+        .unwrap();
+
+        // The lone 1 touches two symbols but only counts once, and @ has no neighbors at all
+        assert_eq!(
+            vec![1, 1, 0],
+            schematic
+                .symbol_index()
+                .values()
+                .map(Vec::len)
+                .collect::<Vec<usize>>()
+        );
+
+        assert_eq!(1, schematic.part_number_sum());
+    }
+
+    #[test]
+    fn test_representations() {
+        for representation in [Representation::Dense, Representation::Sparse] {
+            let schematic = EngineSchematic::parse(TEST_SCHEMATIC, representation).unwrap();
+
+            assert_eq!(4361, schematic.part_number_sum());
+            assert_eq!(467835, schematic.gear_ratio_sum());
+            assert_eq!(Digit(7), schematic.cell(2, 0));
+            assert_eq!(Symbol('$'), schematic.cell(3, 8));
+            assert_eq!(Empty, schematic.cell(4, 8));
+            assert_eq!(Empty, schematic.cell(-1, 0));
+        }
+
+        assert!(matches!(
+            EngineSchematic::from_str(TEST_SCHEMATIC).unwrap().cells,
+            Cells::Dense(_)
+        ));
+
+        let sparse_schematic = EngineSchematic::from_str(indoc! {"
+            ..........
+            ....12....
+            .....*34..
+            ..........
+        "})
+        .unwrap();
+
+        assert!(matches!(sparse_schematic.cells, Cells::Sparse(_)));
+        assert_eq!(46, sparse_schematic.part_number_sum());
+        assert_eq!(408, sparse_schematic.gear_ratio_sum());
+
+        assert!(EngineSchematic::from_str("...\n..\n").is_err());
+    }
+
+    #[test]
+    fn test_number_tokens() {
+        let schematic = EngineSchematic::from_str(indoc! {"
+            12.3
+            4*56
+            ..78
+        "})
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (12, 0, 0, 2),
+                (3, 3, 0, 1),
+                (4, 0, 1, 1),
+                (56, 2, 1, 2),
+                (78, 2, 2, 2)
+            ],
+            schematic
+                .number_tokens()
+                .iter()
+                .map(|token| (token.value, token.x, token.y, token.len))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(schematic
+            .number_tokens()
+            .iter()
+            .all(|token| token.adjacent_symbols.is_empty()));
+
+        // 78 only touches the gear at one corner, but that still counts; 3 is too far away
+        assert_eq!(
+            vec![1, 0, 1, 1, 1],
+            schematic
+                .part_numbers()
+                .iter()
+                .map(|part_number| part_number.adjacent_symbols.len())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_adjacency() {
+        let schematic_string = indoc! {"
+            .......
+            .#.....
+            ..12...
+            .....$.
+            ...*...
+        "};
+
+        let adjacent_symbols = |radius, diagonals| {
+            EngineSchematic::from_str(schematic_string)
+                .unwrap()
+                .with_adjacency(Adjacency { radius, diagonals })
+                .part_numbers()[0]
+                .adjacent_symbols
+                .iter()
+                .map(|symbol| symbol.symbol)
+                .collect::<String>()
+        };
+
+        assert_eq!("#", adjacent_symbols(1, true));
+        assert_eq!("", adjacent_symbols(1, false));
+        assert_eq!("#$*", adjacent_symbols(2, true));
+        assert_eq!("*", adjacent_symbols(2, false));
+        assert_eq!("*", adjacent_symbols(3, false));
+    }
+
+    #[test]
+    fn test_orphan_numbers() {
+        assert_eq!(
+            vec![(114, 5, 0), (58, 7, 5)],
+            EngineSchematic::from_str(TEST_SCHEMATIC)
+                .unwrap()
+                .orphan_numbers()
+                .iter()
+                .map(|orphan| (orphan.value, orphan.x, orphan.y))
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -1,46 +1,267 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::str::FromStr;
+use std::time::Instant;
+
+// Below this many winning numbers, scanning a short vector is faster than building a set
+const SET_MATCHING_THRESHOLD: usize = 512;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
     if let Some(path) = args.get(1) {
+        if args.iter().skip(2).any(|arg| arg == "--streaming") {
+            println!(
+                "Total card count after expansion: {}",
+                expand_cards_streaming(BufReader::new(File::open(path)?))?
+            );
+
+            return Ok(());
+        }
+
         let cards: Vec<Card> = BufReader::new(File::open(path)?)
             .lines()
             .map_while(Result::ok)
             .map(|line| Card::from_str(line.as_str()))
             .collect::<Result<_, _>>()?;
 
+        let scoring_strategy = if let Some(scoring_strategy) = args
+            .iter()
+            .position(|arg| arg == "--scoring")
+            .and_then(|i| args.get(i + 1))
+        {
+            ScoringStrategy::from_str(scoring_strategy)?
+        } else {
+            ScoringStrategy::Doubling
+        };
+
+        let score_sum = cards.iter().try_fold(0u64, |sum, card| {
+            sum.checked_add(card.score(&scoring_strategy)?)
+                .ok_or::<Box<dyn Error>>("Overflow while summing card values".into())
+        })?;
+
+        println!("Sum of card values: {}", score_sum);
+
         println!(
-            "Sum of card values: {}",
-            cards.iter().map(Card::score).sum::<u32>()
+            "Total card count after expansion: {}",
+            expand_cards(&cards)?
         );
 
-        println!("Total card count after expansion: {}", expand_cards(&cards));
+        if args.iter().skip(2).any(|arg| arg == "--per-card") {
+            println!("card\tmatches\tcopies");
+
+            for (id, matches, copies) in Expansion::new(&cards)?.per_card() {
+                println!("{}\t{}\t{}", id, matches, copies);
+            }
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--provenance") {
+            print!("{}", Expansion::new(&cards)?);
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--benchmark") {
+            benchmark_matching();
+        }
 
         Ok(())
     } else {
-        Err("Usage: day04 INPUT_FILE_PATH".into())
+        Err(
+            "Usage: day04 INPUT_FILE_PATH [--scoring doubling|linear|table:POINTS,...] [--streaming] [--per-card] [--provenance] [--benchmark]"
+                .into(),
+        )
+    }
+}
+
+// Compares linear and set-based matching on synthetic cards of increasing size
+fn benchmark_matching() {
+    for numbers_per_card in [8, 64, 256, 512, 1024, 4096] {
+        let cards: Vec<Card> = (1..=100)
+            .map(|id| Card::synthetic(id, numbers_per_card))
+            .collect();
+
+        let start = Instant::now();
+        let linear_matches: u32 = cards.iter().map(Card::matching_numbers_linear).sum();
+        let linear_time = start.elapsed();
+
+        let start = Instant::now();
+        let set_matches: u32 = cards.iter().map(Card::matching_numbers_with_set).sum();
+        let set_time = start.elapsed();
+
+        assert_eq!(linear_matches, set_matches);
+
+        println!(
+            "{} numbers per card: linear {:?}, set {:?}",
+            numbers_per_card, linear_time, set_time
+        );
     }
 }
 
-fn expand_cards(cards: &[Card]) -> u32 {
-    // Assume that cards are serially numbered from 1 to N
-    let mut counts = vec![1; cards.len()];
+fn expand_cards(cards: &[Card]) -> Result<u64, Box<dyn Error>> {
+    Expansion::new(cards)?.total()
+}
+
+// Copies only ever propagate forward, so we only need to remember how many copies have been won of
+// the next few cards; the window never needs to be longer than the largest number of matches on
+// any one card. Cards must appear in increasing ID order, but there may be gaps in the numbering.
+fn expand_cards_streaming(reader: impl BufRead) -> Result<u64, Box<dyn Error>> {
+    let overflow = |id: u32| format!("Card count overflowed while expanding card {}", id);
+
+    // pending[i] is the number of copies won so far of card (previous_id + 1 + i)
+    let mut pending: VecDeque<u64> = VecDeque::new();
+    let mut previous_id: Option<u32> = None;
+    let mut total = 0u64;
+
+    for line in reader.lines() {
+        let card = Card::from_str(line?.as_str())?;
+
+        if let Some(previous_id) = previous_id {
+            if card.id <= previous_id {
+                return Err(format!(
+                    "Card {} follows card {}; streaming expansion requires increasing card IDs",
+                    card.id, previous_id
+                )
+                .into());
+            }
+
+            // Copies of cards that aren't in the table just don't get counted
+            let skipped = ((card.id - previous_id - 1) as usize).min(pending.len());
+            pending.drain(..skipped);
+        }
+
+        let count = pending
+            .pop_front()
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or_else(|| overflow(card.id))?;
+
+        let matching_numbers = card.matching_numbers() as usize;
+
+        if pending.len() < matching_numbers {
+            pending.resize(matching_numbers, 0);
+        }
+
+        for copies in pending.iter_mut().take(matching_numbers) {
+            *copies = copies.checked_add(count).ok_or_else(|| overflow(card.id))?;
+        }
+
+        total = total
+            .checked_add(count)
+            .ok_or("Total card count overflowed")?;
+
+        previous_id = Some(card.id);
+    }
+
+    Ok(total)
+}
+
+// Tracks not just how many copies of each card we wind up with, but which earlier cards won those
+// copies
+struct Expansion {
+    cards: BTreeMap<u32, CardCopies>,
+}
+
+struct CardCopies {
+    matches: u32,
+    count: u64,
+
+    // The IDs of the cards that contributed copies of this card and how many copies they won
+    sources: Vec<(u32, u64)>,
+}
+
+impl Expansion {
+    // Card counts grow exponentially with long chains of winning cards, so this reports an error
+    // rather than silently wrapping if they get too big to count
+    fn new(cards: &[Card]) -> Result<Self, Box<dyn Error>> {
+        let overflow = |id: u32| format!("Card count overflowed while expanding card {}", id);
 
-    for card in cards {
-        let matching_numbers = card.matching_numbers();
-        let copied_card_count = counts[(card.id - 1) as usize];
+        // Cards may come in any order and there may be gaps in the numbering, so key everything by
+        // card ID; copies of cards that aren't in the table just don't get counted
+        let mut cards_by_id: BTreeMap<u32, &Card> = BTreeMap::new();
 
-        for copied_card_id in card.id..card.id + matching_numbers {
-            counts[copied_card_id as usize] += copied_card_count;
+        for card in cards {
+            if cards_by_id.insert(card.id, card).is_some() {
+                return Err(format!("Duplicate card ID: {}", card.id).into());
+            }
         }
+
+        let mut copies: BTreeMap<u32, CardCopies> = cards_by_id
+            .iter()
+            .map(|(&id, card)| {
+                (
+                    id,
+                    CardCopies {
+                        matches: card.matching_numbers(),
+                        count: 1,
+                        sources: Vec::new(),
+                    },
+                )
+            })
+            .collect();
+
+        // Cards only ever win copies of cards with higher IDs, so each card's count is final by the
+        // time we reach it
+        for &id in cards_by_id.keys() {
+            let CardCopies {
+                matches: matching_numbers,
+                count: copied_card_count,
+                ..
+            } = copies[&id];
+
+            if matching_numbers == 0 {
+                continue;
+            }
+
+            let last_copied_card_id = id.saturating_add(matching_numbers);
+
+            for (_, copied_card) in copies.range_mut(id + 1..=last_copied_card_id) {
+                copied_card.count = copied_card
+                    .count
+                    .checked_add(copied_card_count)
+                    .ok_or_else(|| overflow(id))?;
+
+                copied_card.sources.push((id, copied_card_count));
+            }
+        }
+
+        Ok(Expansion { cards: copies })
+    }
+
+    // Yields (card ID, matching numbers, final copy count) for each card in ID order
+    fn per_card(&self) -> impl Iterator<Item = (u32, u32, u64)> + '_ {
+        self.cards
+            .iter()
+            .map(|(&id, card)| (id, card.matches, card.count))
+    }
+
+    fn total(&self) -> Result<u64, Box<dyn Error>> {
+        self.per_card()
+            .try_fold(0u64, |total, (_, _, count)| total.checked_add(count))
+            .ok_or_else(|| "Total card count overflowed".into())
     }
+}
+
+impl Display for Expansion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let copies = |n: u64| if n == 1 { "copy" } else { "copies" };
+
+        for (id, card) in &self.cards {
+            writeln!(f, "Card {}: {} {}", id, card.count, copies(card.count))?;
 
-    counts.iter().sum()
+            if !card.sources.is_empty() {
+                writeln!(f, "  1 original")?;
+
+                for (source_id, n) in &card.sources {
+                    writeln!(f, "  {} {} from card {}", n, copies(*n), source_id)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 struct Card {
@@ -52,14 +273,110 @@ struct Card {
 
 impl Card {
     fn matching_numbers(&self) -> u32 {
+        if self.winning_numbers.len() < SET_MATCHING_THRESHOLD {
+            self.matching_numbers_linear()
+        } else {
+            self.matching_numbers_with_set()
+        }
+    }
+
+    fn matching_numbers_linear(&self) -> u32 {
         self.drawn_numbers
             .iter()
             .filter(|n| self.winning_numbers.contains(n))
             .count() as u32
     }
 
-    fn score(&self) -> u32 {
-        (1 << self.matching_numbers()) >> 1
+    fn matching_numbers_with_set(&self) -> u32 {
+        let winning_numbers: HashSet<&u32> = self.winning_numbers.iter().collect();
+
+        self.drawn_numbers
+            .iter()
+            .filter(|n| winning_numbers.contains(n))
+            .count() as u32
+    }
+
+    // Builds a card whose winning and drawn numbers are spread over a range twice as large as the
+    // number of numbers, so roughly half of them match
+    fn synthetic(id: u32, numbers_per_card: usize) -> Self {
+        let range = numbers_per_card as u32 * 2;
+
+        Card {
+            id,
+            winning_numbers: (0..numbers_per_card as u32)
+                .map(|i| (i * 7 + id) % range)
+                .collect(),
+            drawn_numbers: (0..numbers_per_card as u32)
+                .map(|i| (i * 3 + id * 5) % range)
+                .collect(),
+        }
+    }
+
+    fn score(&self, strategy: &ScoringStrategy) -> Result<u64, Box<dyn Error>> {
+        strategy.score(self.matching_numbers())
+    }
+}
+
+type ScoringFunction = Box<dyn Fn(u32) -> u32>;
+
+enum ScoringStrategy {
+    // One point for the first match, then doubled for each match after that
+    Doubling,
+
+    // One point per match
+    Linear,
+
+    // Anything else; the function receives the number of matching numbers
+    Custom(ScoringFunction),
+}
+
+impl ScoringStrategy {
+    fn score(&self, matching_numbers: u32) -> Result<u64, Box<dyn Error>> {
+        match self {
+            ScoringStrategy::Doubling => match matching_numbers {
+                0 => Ok(0),
+                _ => 1u64.checked_shl(matching_numbers - 1).ok_or_else(|| {
+                    format!(
+                        "Card with {} matching numbers is worth too many points",
+                        matching_numbers
+                    )
+                    .into()
+                }),
+            },
+            ScoringStrategy::Linear => Ok(matching_numbers as u64),
+            ScoringStrategy::Custom(score) => Ok(score(matching_numbers) as u64),
+        }
+    }
+}
+
+impl FromStr for ScoringStrategy {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubling" => Ok(ScoringStrategy::Doubling),
+            "linear" => Ok(ScoringStrategy::Linear),
+            _ => {
+                // A table of points for zero matches, one match, and so on; cards with more matches
+                // than the table covers get the last entry
+                if let Some(points) = s.strip_prefix("table:") {
+                    let points: Vec<u32> = points
+                        .split(',')
+                        .map(|p| p.trim().parse())
+                        .collect::<Result<_, _>>()?;
+
+                    Ok(ScoringStrategy::Custom(Box::new(move |matching_numbers| {
+                        points
+                            .get(matching_numbers as usize)
+                            .or(points.last())
+                            .copied()
+                            .unwrap_or(0)
+                    })))
+                } else {
+                    Err(format!("Unrecognized scoring strategy: {}", s).into())
+                }
+            }
+        }
     }
 }
 
@@ -113,6 +430,15 @@ mod test {
     use super::*;
     use indoc::indoc;
 
+    const TEST_CARDS: &str = indoc! {"
+        Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+        Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+        Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+        Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+        Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+        Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+    "};
+
     #[test]
     fn test_card_score() {
         for (card, expected_score) in [
@@ -123,25 +449,300 @@ mod test {
             ("Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36", 0),
             ("Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11", 0),
         ] {
-            assert_eq!(expected_score, Card::from_str(card).unwrap().score());
+            assert_eq!(
+                expected_score,
+                Card::from_str(card)
+                    .unwrap()
+                    .score(&ScoringStrategy::Doubling)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_scoring_strategies() {
+        let cards: Vec<Card> = TEST_CARDS
+            .lines()
+            .map(Card::from_str)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let score_sum = |strategy: &ScoringStrategy| -> u64 {
+            cards.iter().map(|card| card.score(strategy).unwrap()).sum()
+        };
+
+        assert_eq!(13, score_sum(&ScoringStrategy::Doubling));
+        assert_eq!(9, score_sum(&ScoringStrategy::Linear));
+        assert_eq!(
+            4 * 4 + 2 * 2 + 2 * 2 + 1,
+            score_sum(&ScoringStrategy::Custom(Box::new(|m| m * m)))
+        );
+
+        assert_eq!(
+            13,
+            score_sum(&ScoringStrategy::from_str("doubling").unwrap())
+        );
+        assert_eq!(9, score_sum(&ScoringStrategy::from_str("linear").unwrap()));
+        assert_eq!(
+            10 + 5 + 5 + 1,
+            score_sum(&ScoringStrategy::from_str("table:0,1,5,10").unwrap())
+        );
+
+        // Large cards can be worth more than fits in 32 bits, and eventually more than fits in 64
+        let large_card = |matches: u32| -> Card {
+            let numbers: Vec<String> = (1..=matches).map(|n| n.to_string()).collect();
+            Card::from_str(&format!(
+                "Card 1: {} | {}",
+                numbers.join(" "),
+                numbers.join(" ")
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(
+            1 << 39,
+            large_card(40).score(&ScoringStrategy::Doubling).unwrap()
+        );
+        assert_eq!(
+            1 << 63,
+            large_card(64).score(&ScoringStrategy::Doubling).unwrap()
+        );
+        assert!(large_card(65).score(&ScoringStrategy::Doubling).is_err());
+        assert_eq!(65, large_card(65).score(&ScoringStrategy::Linear).unwrap());
+
+        assert!(ScoringStrategy::from_str("tripling").is_err());
+        assert!(ScoringStrategy::from_str("table:1,x").is_err());
+    }
+
+    #[test]
+    fn test_matching_strategies() {
+        for numbers_per_card in [
+            1,
+            5,
+            SET_MATCHING_THRESHOLD - 1,
+            SET_MATCHING_THRESHOLD,
+            2000,
+        ] {
+            for id in 1..=5 {
+                let card = Card::synthetic(id, numbers_per_card);
+
+                assert_eq!(
+                    card.matching_numbers_linear(),
+                    card.matching_numbers_with_set()
+                );
+
+                assert_eq!(card.matching_numbers_linear(), card.matching_numbers());
+            }
         }
+
+        let card = Card::from_str("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53").unwrap();
+        assert_eq!(4, card.matching_numbers_with_set());
     }
 
     #[test]
     fn test_expand_cards() {
+        let cards: Vec<Card> = TEST_CARDS
+            .lines()
+            .map(Card::from_str)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(30, expand_cards(&cards).unwrap());
+    }
+
+    #[test]
+    fn test_expansion_provenance() {
+        let cards: Vec<Card> = TEST_CARDS
+            .lines()
+            .map(Card::from_str)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let expansion = Expansion::new(&cards).unwrap();
+
+        assert_eq!(
+            vec![1, 2, 4, 8, 14, 1],
+            expansion
+                .cards
+                .values()
+                .map(|card| card.count)
+                .collect::<Vec<u64>>()
+        );
+
+        assert!(expansion.cards[&1].sources.is_empty());
+        assert_eq!(vec![(1, 1), (2, 2)], expansion.cards[&3].sources);
+        assert_eq!(vec![(1, 1), (3, 4), (4, 8)], expansion.cards[&5].sources);
+        assert!(expansion.cards[&6].sources.is_empty());
+
+        // Each card's copies are its original plus everything its sources contributed
+        for card in expansion.cards.values() {
+            assert_eq!(
+                card.count,
+                1 + card.sources.iter().map(|(_, n)| n).sum::<u64>()
+            );
+        }
+
+        assert_eq!(
+            indoc! {"
+                Card 1: 1 copy
+                Card 2: 2 copies
+                  1 original
+                  1 copy from card 1
+                Card 3: 4 copies
+                  1 original
+                  1 copy from card 1
+                  2 copies from card 2
+                Card 4: 8 copies
+                  1 original
+                  1 copy from card 1
+                  2 copies from card 2
+                  4 copies from card 3
+                Card 5: 14 copies
+                  1 original
+                  1 copy from card 1
+                  4 copies from card 3
+                  8 copies from card 4
+                Card 6: 1 copy
+            "},
+            expansion.to_string()
+        );
+    }
+
+    #[test]
+    fn test_expansion_per_card() {
+        let cards: Vec<Card> = TEST_CARDS
+            .lines()
+            .map(Card::from_str)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let expansion = Expansion::new(&cards).unwrap();
+
+        assert_eq!(
+            vec![
+                (1, 4, 1),
+                (2, 2, 2),
+                (3, 2, 4),
+                (4, 1, 8),
+                (5, 0, 14),
+                (6, 0, 1)
+            ],
+            expansion.per_card().collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            expansion.total().unwrap(),
+            expansion.per_card().map(|(_, _, count)| count).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_expand_cards_non_sequential() {
+        // The example cards in a different order
         let cards: Vec<Card> = indoc! {"
+            Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+            Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
             Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
-            Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
             Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
-            Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
             Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
-            Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+            Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+        "}
+        .lines()
+        .map(Card::from_str)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        assert_eq!(30, expand_cards(&cards).unwrap());
+
+        // Card 3 is missing, so copies of it are never counted
+        let cards: Vec<Card> = indoc! {"
+            Card 1: 1 2 | 1 2
+            Card 2: 1 2 | 1 2
+            Card 4: 1 | 1
+            Card 10: 1 | 2
         "}
         .lines()
         .map(Card::from_str)
         .collect::<Result<_, _>>()
         .unwrap();
 
-        assert_eq!(30, expand_cards(&cards));
+        let expansion = Expansion::new(&cards).unwrap();
+
+        assert_eq!(2, expansion.cards[&2].count);
+        assert_eq!(vec![(2, 2)], expansion.cards[&4].sources);
+        assert_eq!(1, expansion.cards[&10].count);
+        assert_eq!(1 + 2 + 3 + 1, expand_cards(&cards).unwrap());
+
+        let cards: Vec<Card> = indoc! {"
+            Card 1: 1 | 1
+            Card 1: 2 | 2
+        "}
+        .lines()
+        .map(Card::from_str)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        assert!(expand_cards(&cards).is_err());
+    }
+
+    #[test]
+    fn test_expand_cards_streaming() {
+        assert_eq!(30, expand_cards_streaming(TEST_CARDS.as_bytes()).unwrap());
+
+        let cards = indoc! {"
+            Card 1: 1 2 | 1 2
+            Card 2: 1 2 | 1 2
+            Card 4: 1 | 1
+            Card 10: 1 | 2
+        "};
+
+        assert_eq!(7, expand_cards_streaming(cards.as_bytes()).unwrap());
+
+        let cards = indoc! {"
+            Card 2: 1 | 1
+            Card 1: 1 | 1
+        "};
+
+        assert!(expand_cards_streaming(cards.as_bytes()).is_err());
+
+        for len in [10, 64, 65] {
+            let cards: String = (1..=len)
+                .map(|id| {
+                    let numbers: Vec<String> = (1..=len - id).map(|n| n.to_string()).collect();
+                    let numbers = numbers.join(" ");
+
+                    format!("Card {}: {} | {}\n", id, numbers, numbers)
+                })
+                .collect();
+
+            let cards_vec: Vec<Card> = cards.lines().map(|l| Card::from_str(l).unwrap()).collect();
+
+            assert_eq!(
+                expand_cards(&cards_vec).ok(),
+                expand_cards_streaming(cards.as_bytes()).ok()
+            );
+        }
+    }
+
+    #[test]
+    fn test_expand_cards_overflow() {
+        // Each card matches every number, and so wins a copy of every card after it; that doubles
+        // the count of each successive card
+        let chain = |len: u32| -> Vec<Card> {
+            (1..=len)
+                .map(|id| {
+                    let numbers: Vec<String> = (1..=len - id).map(|n| n.to_string()).collect();
+                    let numbers = numbers.join(" ");
+
+                    Card::from_str(format!("Card {}: {} | {}", id, numbers, numbers).as_str())
+                        .unwrap()
+                })
+                .collect()
+        };
+
+        assert_eq!((1 << 60) - 1, expand_cards(&chain(60)).unwrap());
+        assert_eq!(u64::MAX, expand_cards(&chain(64)).unwrap());
+        assert!(expand_cards(&chain(65)).is_err());
+        assert!(expand_cards(&chain(70)).is_err());
     }
 }
@@ -1,7 +1,9 @@
-use crate::Resource::*;
-use std::collections::HashSet;
+use advent_of_code_2023::interval_map::{overlap, Interval, IntervalMap};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::ops::Add;
@@ -11,13 +13,75 @@ fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
     if let Some(path) = args.get(1) {
-        let almanac = {
+        let almanac_string = {
             let mut almanac_string = String::new();
             File::open(path)?.read_to_string(&mut almanac_string)?;
 
-            Almanac::from_str(almanac_string.as_str())?
+            almanac_string
         };
 
+        // Composing all of the maps is the expensive part, so we can optionally load a combined map
+        // that was saved from an earlier run
+        let combined_range_map = if let Some(map_path) = args
+            .iter()
+            .position(|arg| arg == "--load-map")
+            .and_then(|i| args.get(i + 1))
+        {
+            let mut document_string = String::new();
+            File::open(map_path)?.read_to_string(&mut document_string)?;
+
+            let document: SavedMapDocument = serde_json::from_str(document_string.as_str())?;
+
+            if document.fingerprint != maps_fingerprint(almanac_string.as_str()) {
+                return Err("Saved map was built from a different almanac".into());
+            }
+
+            Some(RangeMap::try_from(document.range_map)?)
+        } else {
+            None
+        };
+
+        let almanac = Almanac::parse(almanac_string.as_str(), combined_range_map)?;
+
+        if let Some(map_path) = args
+            .iter()
+            .position(|arg| arg == "--save-map")
+            .and_then(|i| args.get(i + 1))
+        {
+            let document = SavedMapDocument {
+                fingerprint: maps_fingerprint(almanac_string.as_str()),
+                range_map: almanac.range_map.to_document(),
+            };
+
+            serde_json::to_writer_pretty(File::create(map_path)?, &document)?;
+        }
+
+        if let Some(format) = args
+            .iter()
+            .position(|arg| arg == "--export")
+            .and_then(|i| args.get(i + 1))
+        {
+            let trace_seed = args
+                .iter()
+                .position(|arg| arg == "--trace-seed")
+                .and_then(|i| args.get(i + 1))
+                .map(|seed| seed.parse())
+                .transpose()?;
+
+            match format.as_str() {
+                "dot" => print!(
+                    "{}",
+                    ChainDiagram {
+                        chain: &almanac.chain,
+                        trace_seed,
+                    }
+                ),
+                _ => return Err(format!("Unrecognized export format: {}", format).into()),
+            }
+
+            return Ok(());
+        }
+
         println!("Lowest seed location: {}", almanac.lowest_seed_location());
 
         println!(
@@ -25,15 +89,43 @@ fn main() -> Result<(), Box<dyn Error>> {
             almanac.lowest_seed_location_ranges()
         );
 
+        if args.iter().skip(2).any(|arg| arg == "--invert") {
+            println!(
+                "Lowest seed location using seed ranges (by inversion): {}",
+                almanac.lowest_seed_location_inverted()
+            );
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--verify") {
+            let lowest_seed_location = almanac.lowest_seed_location_ranges();
+            let brute_force_lowest_seed_location = almanac.lowest_seed_location_brute_force();
+
+            println!(
+                "Lowest seed location using seed ranges (by brute force): {}",
+                brute_force_lowest_seed_location
+            );
+
+            if lowest_seed_location != brute_force_lowest_seed_location {
+                return Err(format!(
+                    "Range-based lowest seed location ({}) does not match brute-force result ({})",
+                    lowest_seed_location, brute_force_lowest_seed_location
+                )
+                .into());
+            }
+        }
+
         Ok(())
     } else {
-        Err("Usage: day05 INPUT_FILE_PATH".into())
+        Err("Usage: day05 INPUT_FILE_PATH [--load-map MAP_PATH] [--save-map MAP_PATH] [--invert] [--verify] [--export dot [--trace-seed SEED]]".into())
     }
 }
 
 struct Almanac {
     seeds: Vec<u64>,
     range_map: RangeMap,
+
+    // The individual maps that were combined into `range_map`, in the order they're applied
+    chain: Vec<RangeMap>,
 }
 
 impl Almanac {
@@ -46,37 +138,88 @@ impl Almanac {
     }
 
     fn lowest_seed_location_ranges(&self) -> u64 {
-        let mut points_of_interest = HashSet::new();
+        self.seed_location_ranges()
+            .iter()
+            .map(|&(start, _)| start)
+            .min()
+            .unwrap()
+    }
 
-        for chunk in self.seeds.chunks_exact(2) {
-            if let &[start, length] = chunk {
-                // The start of the seed range itself is a candidate for a local minimum
-                points_of_interest.insert(start);
+    // Treats seeds as (start, length) pairs and maps each whole seed range through the almanac,
+    // returning the location ranges (as start-inclusive, end-exclusive pairs) those seeds land in
+    fn seed_location_ranges(&self) -> Vec<(u64, u64)> {
+        self.seed_ranges()
+            .iter()
+            .flat_map(|&(start, end)| self.range_map.map_range(start, end))
+            .collect()
+    }
 
-                // Add any range boundaries (i.e. discontinuities) that occur within a seed range
+    // Alternatively, walk upward through location space, one block at a time, until we find a block
+    // that some seed in one of the seed ranges maps into
+    fn lowest_seed_location_inverted(&self) -> u64 {
+        let seed_ranges = self.seed_ranges();
+
+        let mut boundaries: Vec<u64> = self
+            .range_map
+            .interval_map
+            .intervals()
+            .iter()
+            .flat_map(|range| {
+                [
+                    range.start,
+                    range.end,
+                    range.shift(range.start),
+                    range.shift(range.end),
+                ]
+            })
+            .chain([0, u64::MAX])
+            .collect();
+
+        boundaries.sort();
+        boundaries.dedup();
+
+        boundaries
+            .windows(2)
+            .find_map(|window| {
                 self.range_map
-                    .ranges
+                    .invert(window[0], window[1])
                     .iter()
-                    .flat_map(|range| vec![range.start, range.end])
-                    .filter(|&point| point >= start && point < start + length)
-                    .for_each(|point| {
-                        points_of_interest.insert(point);
-                    });
-            }
-        }
+                    .flat_map(|&inverted_range| {
+                        seed_ranges
+                            .iter()
+                            .filter_map(move |&seed_range| overlap(inverted_range, seed_range))
+                    })
+                    .map(|(seed, _)| self.range_map.map(seed))
+                    .min()
+            })
+            .unwrap()
+    }
 
-        points_of_interest
-            .iter()
-            .map(|&point| self.range_map.map(point))
+    // Checks every single seed in every seed range against every individual map in the chain; this
+    // is very slow for real inputs, but doesn't depend on any of the range-splitting logic
+    fn lowest_seed_location_brute_force(&self) -> u64 {
+        self.seed_ranges()
+            .into_par_iter()
+            .flat_map(|(start, end)| start..end)
+            .map(|seed| {
+                self.chain
+                    .iter()
+                    .fold(seed, |value, range_map| range_map.map(value))
+            })
             .min()
             .unwrap()
     }
-}
 
-impl FromStr for Almanac {
-    type Err = Box<dyn Error>;
+    fn seed_ranges(&self) -> Vec<(u64, u64)> {
+        self.seeds
+            .chunks_exact(2)
+            .map(|chunk| (chunk[0], chunk[0] + chunk[1]))
+            .collect()
+    }
 
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
+    // Parses an almanac, composing its maps into a single map unless a previously-combined map is
+    // provided
+    fn parse(string: &str, combined_range_map: Option<RangeMap>) -> Result<Self, Box<dyn Error>> {
         let mut blocks = string.split("\n\n");
 
         let seeds = if let Some(seeds_block) = blocks.next() {
@@ -94,53 +237,266 @@ impl FromStr for Almanac {
 
         let range_maps: Vec<RangeMap> = blocks.map(RangeMap::from_str).collect::<Result<_, _>>()?;
 
-        let mut combined_range_map = range_maps
+        // The chain starts with the only resource that isn't the destination of any other map
+        let initial_range_maps: Vec<&RangeMap> = range_maps
             .iter()
-            .find(|range_map| range_map.source == Seed)
-            .ok_or("Could not find initial seed-to-* range map")?
-            .to_owned();
+            .filter(|range_map| {
+                !range_maps
+                    .iter()
+                    .any(|other| other.destination == range_map.source)
+            })
+            .collect();
+
+        let mut chain = if let [initial_range_map] = initial_range_maps.as_slice() {
+            vec![(*initial_range_map).clone()]
+        } else {
+            return Err("Could not find a unique starting range map".into());
+        };
 
         while let Some(next_range_map) = range_maps
             .iter()
-            .find(|&next_range_map| next_range_map.source == combined_range_map.destination) {
+            .find(|&next_range_map| next_range_map.source == chain[chain.len() - 1].destination) {
+
+            chain.push(next_range_map.clone());
+
+            if chain.len() > range_maps.len() {
+                return Err("Range maps form a cycle".into());
+            }
+        }
 
-            combined_range_map = &combined_range_map + next_range_map;
+        if chain.len() != range_maps.len() {
+            return Err("Range maps do not form a single chain".into());
         }
 
+        let source = &chain[0].source;
+        let destination = &chain[chain.len() - 1].destination;
+
+        let range_map = if let Some(combined_range_map) = combined_range_map {
+            if combined_range_map.source != *source || combined_range_map.destination != *destination
+            {
+                return Err(format!(
+                    "Combined map goes from {} to {}, but almanac goes from {} to {}",
+                    combined_range_map.source.0,
+                    combined_range_map.destination.0,
+                    source.0,
+                    destination.0
+                )
+                .into());
+            }
+
+            combined_range_map
+        } else {
+            chain[1..]
+                .iter()
+                .fold(chain[0].clone(), |combined_range_map, next_range_map| {
+                    &combined_range_map + next_range_map
+                })
+        };
+
         Ok(Almanac {
             seeds,
-            range_map: combined_range_map,
+            range_map,
+            chain,
         })
     }
 }
 
+impl FromStr for Almanac {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Almanac::parse(string, None)
+    }
+}
+
 #[derive(Clone)]
 struct RangeMap {
     source: Resource,
     destination: Resource,
 
-    ranges: Vec<Range>,
+    interval_map: IntervalMap,
 }
 
 impl RangeMap {
     fn map(&self, value: u64) -> u64 {
-        self.ranges
-            .iter()
-            .filter_map(|range| range.map(value))
-            .next()
-            .unwrap_or(value)
+        self.interval_map.map(value)
     }
 
-    // What input value leads to the given output value?
-    fn invert(&self, value: u64) -> u64 {
-        self.ranges
-            .iter()
-            .filter_map(|range| range.invert(value))
-            .next()
-            .unwrap_or(value)
+    fn map_range(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        self.interval_map.map_range(start, end)
+    }
+
+    fn invert(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        self.interval_map.invert(start, end)
+    }
+
+    fn to_document(&self) -> RangeMapDocument {
+        RangeMapDocument {
+            source: self.source.0.clone(),
+            destination: self.destination.0.clone(),
+            ranges: self
+                .interval_map
+                .intervals()
+                .iter()
+                .map(|interval| RangeDocument {
+                    start: interval.start,
+                    end: interval.end,
+                    offset: interval.offset,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<RangeMapDocument> for RangeMap {
+    type Error = Box<dyn Error>;
+
+    fn try_from(document: RangeMapDocument) -> Result<Self, Self::Error> {
+        Ok(RangeMap {
+            source: Resource::from_str(document.source.as_str())?,
+            destination: Resource::from_str(document.destination.as_str())?,
+
+            interval_map: IntervalMap::new(
+                document
+                    .ranges
+                    .iter()
+                    .map(|range| Interval {
+                        start: range.start,
+                        end: range.end,
+                        offset: range.offset,
+                    })
+                    .collect(),
+            )?,
+        })
+    }
+}
+
+// Renders the chain of maps as a Graphviz diagram with one node per resource and one row per range;
+// if a seed is given, highlights the path it takes from one resource to the next
+struct ChainDiagram<'a> {
+    chain: &'a [RangeMap],
+    trace_seed: Option<u64>,
+}
+
+impl ChainDiagram<'_> {
+    const TRACE_COLOR: &'static str = "red";
+}
+
+impl Display for ChainDiagram<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "digraph almanac {{")?;
+        writeln!(f, "    rankdir=LR;")?;
+        writeln!(f, "    node [shape=record, fontname=\"monospace\"];")?;
+        writeln!(f)?;
+
+        let mut value = self.trace_seed;
+
+        for range_map in self.chain {
+            let traced_port = value.map(|value| {
+                range_map
+                    .interval_map
+                    .intervals()
+                    .iter()
+                    .position(|interval| interval.map(value).is_some())
+                    .map_or(String::from("identity"), |i| format!("r{}", i))
+            });
+
+            let mut rows: Vec<String> = range_map
+                .interval_map
+                .intervals()
+                .iter()
+                .enumerate()
+                .map(|(i, interval)| {
+                    format!(
+                        "<r{}> [{}, {}) {:+}",
+                        i, interval.start, interval.end, interval.offset
+                    )
+                })
+                .collect();
+
+            rows.insert(0, range_map.source.0.clone());
+            rows.push(String::from("<identity> otherwise unchanged"));
+
+            writeln!(
+                f,
+                "    {} [label=\"{}\"];",
+                range_map.source.0,
+                rows.join("|")
+            )?;
+
+            if let (Some(port), Some(input)) = (traced_port, value) {
+                let output = range_map.map(input);
+
+                writeln!(
+                    f,
+                    "    {}:{} -> {} [label=\"{} to {}\", color={}, fontcolor={}, penwidth=2];",
+                    range_map.source.0,
+                    port,
+                    range_map.destination.0,
+                    input,
+                    output,
+                    Self::TRACE_COLOR,
+                    Self::TRACE_COLOR
+                )?;
+
+                value = Some(output);
+            } else {
+                writeln!(
+                    f,
+                    "    {} -> {};",
+                    range_map.source.0, range_map.destination.0
+                )?;
+            }
+        }
+
+        if let Some(last) = self.chain.last() {
+            match value {
+                Some(location) => writeln!(
+                    f,
+                    "    {} [label=\"{}|{}\", color={}];",
+                    last.destination.0,
+                    last.destination.0,
+                    location,
+                    Self::TRACE_COLOR
+                )?,
+                None => writeln!(f, "    {};", last.destination.0)?,
+            }
+        }
+
+        writeln!(f, "}}")
     }
 }
 
+// Identifies the maps in an almanac (but not its seeds) so we don't use a saved map with the wrong
+// almanac; this is a 64-bit FNV-1a hash, which is stable from one run to the next
+fn maps_fingerprint(almanac: &str) -> u64 {
+    let maps = almanac.split_once("\n\n").map_or("", |(_, maps)| maps);
+
+    maps.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedMapDocument {
+    fingerprint: u64,
+    range_map: RangeMapDocument,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RangeMapDocument {
+    source: String,
+    destination: String,
+    ranges: Vec<RangeDocument>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RangeDocument {
+    start: u64,
+    end: u64,
+    offset: i64,
+}
+
 impl FromStr for RangeMap {
     type Err = Box<dyn Error>;
 
@@ -171,7 +527,7 @@ impl FromStr for RangeMap {
             source,
             destination,
 
-            ranges: lines.map(Range::from_str).collect::<Result<_, _>>()?,
+            interval_map: IntervalMap::new(lines.map(parse_range).collect::<Result<_, _>>()?)?,
         })
     }
 }
@@ -179,137 +535,52 @@ impl FromStr for RangeMap {
 impl Add for &RangeMap {
     type Output = RangeMap;
 
-    #[allow(clippy::suspicious_arithmetic_impl)]
     fn add(self, addend: Self) -> Self::Output {
         if self.destination != addend.source {
             panic!("Incompatible resource types");
         }
 
-        let mut boundaries = HashSet::new();
-        boundaries.insert(0);
-        boundaries.insert(u64::MAX);
-
-        self.ranges.iter().for_each(|range| {
-            boundaries.insert(range.start);
-            boundaries.insert(range.end);
-        });
-
-        addend.ranges.iter().for_each(|range| {
-            boundaries.insert(self.invert(range.start));
-            boundaries.insert(self.invert(range.end));
-        });
-
-        let mut boundaries = Vec::from_iter(boundaries);
-        boundaries.sort();
-
-        let ranges = boundaries
-            .windows(2)
-            .filter_map(|window| {
-                if let [start, end] = window {
-                    let offset = addend.map(self.map(*start)) as i64 - *start as i64;
-
-                    Some(Range {
-                        start: *start,
-                        end: *end,
-                        offset,
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        // TODO: "Defragment" ranges by merging adjacent ranges with identical offsets
-
         RangeMap {
-            source: self.source,
-            destination: addend.destination,
+            source: self.source.clone(),
+            destination: addend.destination.clone(),
 
-            ranges,
+            interval_map: self.interval_map.compose(&addend.interval_map),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum Resource {
-    Seed,
-    Soil,
-    Fertilizer,
-    Water,
-    Light,
-    Temperature,
-    Humidity,
-    Location,
-}
+// Resources are identified by name
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Resource(String);
 
 impl FromStr for Resource {
     type Err = Box<dyn Error>;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        match string {
-            "seed" => Ok(Seed),
-            "soil" => Ok(Soil),
-            "fertilizer" => Ok(Fertilizer),
-            "water" => Ok(Water),
-            "light" => Ok(Light),
-            "temperature" => Ok(Temperature),
-            "humidity" => Ok(Humidity),
-            "location" => Ok(Location),
-            _ => Err("Unrecognized resource".into()),
-        }
-    }
-}
-
-#[derive(Copy, Clone)]
-struct Range {
-    start: u64,
-
-    // End exclusive
-    end: u64,
-
-    offset: i64,
-}
-
-impl Range {
-    fn map(&self, value: u64) -> Option<u64> {
-        if value >= self.start && value < self.end {
-            Some((value as i64 + self.offset) as u64)
-        } else {
-            None
+        if string.is_empty() || string.contains(char::is_whitespace) {
+            return Err(format!("Unrecognized resource: \"{}\"", string).into());
         }
-    }
 
-    // What input value, if any, leads to the given output value?
-    fn invert(&self, value: u64) -> Option<u64> {
-        if value >= (self.start as i64 + self.offset) as u64
-            && value < (self.end as i64 + self.offset) as u64
-        {
-            Some((value as i64 - self.offset) as u64)
-        } else {
-            None
-        }
+        Ok(Resource(String::from(string)))
     }
 }
 
-impl FromStr for Range {
-    type Err = Box<dyn Error>;
-
-    fn from_str(line: &str) -> Result<Self, Self::Err> {
-        if let [destination_start, source_start, length] =
-            line.split(' ').collect::<Vec<&str>>().as_slice()
-        {
-            let source_start = source_start.parse()?;
-            let destination_start: u64 = destination_start.parse()?;
-            let length: u64 = length.parse()?;
-
-            Ok(Range {
-                start: source_start,
-                end: source_start + length,
-                offset: destination_start as i64 - source_start as i64,
-            })
-        } else {
-            Err("Could not parse range string".into())
-        }
+// Parses a "destination_start source_start length" line from an almanac
+fn parse_range(line: &str) -> Result<Interval, Box<dyn Error>> {
+    if let [destination_start, source_start, length] =
+        line.split(' ').collect::<Vec<&str>>().as_slice()
+    {
+        let source_start = source_start.parse()?;
+        let destination_start: u64 = destination_start.parse()?;
+        let length: u64 = length.parse()?;
+
+        Ok(Interval {
+            start: source_start,
+            end: source_start + length,
+            offset: destination_start as i64 - source_start as i64,
+        })
+    } else {
+        Err("Could not parse range string".into())
     }
 }
 
@@ -379,20 +650,116 @@ mod test {
     #[test]
     fn test_range_invert() {
         {
-            let range = Range::from_str("50 98 2").unwrap();
+            let range = parse_range("50 98 2").unwrap();
 
             assert_eq!(Some(99), range.invert(51));
             assert_eq!(None, range.invert(49));
         }
 
         {
-            let range = Range::from_str("52 50 48").unwrap();
+            let range = parse_range("52 50 48").unwrap();
 
             assert_eq!(Some(50), range.invert(52));
             assert_eq!(None, range.invert(100));
         }
     }
 
+    #[test]
+    fn test_range_map_sorted() {
+        let range_map = RangeMap::from_str(indoc! {"
+            seed-to-soil map:
+            52 50 48
+            0 0 0
+            50 98 2
+            10 110 5
+        "})
+        .unwrap();
+
+        assert_eq!(
+            vec![50, 98, 110],
+            range_map
+                .interval_map
+                .intervals()
+                .iter()
+                .map(|range| range.start)
+                .collect::<Vec<u64>>()
+        );
+
+        for (expected, value) in [(49, 49), (52, 50), (99, 97), (50, 98), (51, 99)] {
+            assert_eq!(expected, range_map.map(value));
+        }
+
+        for (expected, value) in [(100, 100), (109, 109), (10, 110), (14, 114), (115, 115)] {
+            assert_eq!(expected, range_map.map(value));
+        }
+
+        assert_eq!(u64::MAX, range_map.map(u64::MAX));
+
+        assert!(RangeMap::from_str(indoc! {"
+            seed-to-soil map:
+            50 98 2
+            52 50 49
+        "})
+        .is_err());
+    }
+
+    #[test]
+    fn test_range_map_map_range() {
+        let range_map = RangeMap::from_str(indoc! {"
+            seed-to-soil map:
+            50 98 2
+            52 50 48
+        "})
+        .unwrap();
+
+        assert_eq!(vec![(10, 20)], range_map.map_range(10, 20));
+        assert_eq!(vec![(52, 62)], range_map.map_range(50, 60));
+        assert_eq!(
+            vec![(40, 50), (52, 100), (50, 52), (100, 110)],
+            range_map.map_range(40, 110)
+        );
+        assert!(range_map.map_range(60, 60).is_empty());
+
+        // Every value in the input range should land in exactly one of the output ranges
+        let mapped_ranges = range_map.map_range(0, 200);
+
+        for seed in 0..200 {
+            let soil = range_map.map(seed);
+
+            assert_eq!(
+                1,
+                mapped_ranges
+                    .iter()
+                    .filter(|&&(start, end)| soil >= start && soil < end)
+                    .count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_seed_location_ranges() {
+        let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
+        let location_ranges = almanac.seed_location_ranges();
+
+        assert_eq!(
+            14 + 13,
+            location_ranges
+                .iter()
+                .map(|(start, end)| end - start)
+                .sum::<u64>()
+        );
+
+        for chunk in almanac.seeds.chunks_exact(2) {
+            for seed in chunk[0]..chunk[0] + chunk[1] {
+                let location = almanac.range_map.map(seed);
+
+                assert!(location_ranges
+                    .iter()
+                    .any(|&(start, end)| location >= start && location < end));
+            }
+        }
+    }
+
     #[test]
     fn test_range_map_invert() {
         let range_map = RangeMap::from_str(indoc! {"
@@ -402,10 +769,195 @@ mod test {
         "})
         .unwrap();
 
-        assert_eq!(99, range_map.invert(51));
-        assert_eq!(49, range_map.invert(49));
-        assert_eq!(50, range_map.invert(52));
-        assert_eq!(100, range_map.invert(100));
+        assert_eq!(vec![(99, 100)], range_map.invert(51, 52));
+        assert_eq!(vec![(49, 50)], range_map.invert(49, 50));
+        assert_eq!(vec![(50, 51)], range_map.invert(52, 53));
+        assert_eq!(vec![(100, 101)], range_map.invert(100, 101));
+
+        assert_eq!(
+            vec![(0, 50), (50, 98), (98, 100), (100, 200)],
+            range_map.invert(0, 200)
+        );
+
+        // Both 5 (unmapped) and 10 map to 5
+        let range_map = RangeMap::from_str(indoc! {"
+            seed-to-soil map:
+            5 10 5
+        "})
+        .unwrap();
+
+        assert_eq!(vec![(5, 6), (10, 11)], range_map.invert(5, 6));
+        assert_eq!(vec![(0, 10), (10, 15)], range_map.invert(0, 10));
+    }
+
+    #[test]
+    fn test_range_map_add_shared_preimage() {
+        // 5 maps to itself and 10 maps to 5, so a boundary at 5 in the addend has two preimages
+        let first = RangeMap::from_str(indoc! {"
+            seed-to-soil map:
+            5 10 5
+        "})
+        .unwrap();
+
+        let second = RangeMap::from_str(indoc! {"
+            soil-to-fertilizer map:
+            100 5 3
+        "})
+        .unwrap();
+
+        let combined = &first + &second;
+
+        for seed in 0..=20 {
+            assert_eq!(second.map(first.map(seed)), combined.map(seed));
+        }
+    }
+
+    #[test]
+    fn test_arbitrary_resource_chain() {
+        // Made-up resources, listed out of order
+        let almanac = Almanac::from_str(indoc! {"
+            seeds: 1 5 10
+
+            sprout-to-harvest map:
+            100 20 10
+
+            bean-to-sprout map:
+            20 0 5
+
+            harvest-to-market map:
+            0 100 1
+            1 101 1000
+        "})
+        .unwrap();
+
+        assert_eq!(
+            Resource::from_str("bean").unwrap(),
+            almanac.range_map.source
+        );
+        assert_eq!(
+            Resource::from_str("market").unwrap(),
+            almanac.range_map.destination
+        );
+        assert_eq!(3, almanac.chain.len());
+
+        // 1 -> 21 -> 101 -> 1
+        assert_eq!(1, almanac.range_map.map(1));
+        assert_eq!(1, almanac.lowest_seed_location());
+
+        // Two separate chains
+        assert!(Almanac::from_str(indoc! {"
+            seeds: 1
+
+            a-to-b map:
+            0 1 1
+
+            c-to-d map:
+            0 1 1
+        "})
+        .is_err());
+
+        // No start
+        assert!(Almanac::from_str(indoc! {"
+            seeds: 1
+
+            a-to-b map:
+            0 1 1
+
+            b-to-a map:
+            0 1 1
+        "})
+        .is_err());
+    }
+
+    #[test]
+    fn test_range_map_document() {
+        let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
+
+        let json = serde_json::to_string(&almanac.range_map.to_document()).unwrap();
+        let combined_range_map =
+            RangeMap::try_from(serde_json::from_str::<RangeMapDocument>(&json).unwrap()).unwrap();
+
+        assert_eq!(
+            almanac.range_map.interval_map,
+            combined_range_map.interval_map
+        );
+
+        let loaded_almanac = Almanac::parse(TEST_ALMANAC_STRING, Some(combined_range_map)).unwrap();
+
+        assert_eq!(35, loaded_almanac.lowest_seed_location());
+        assert_eq!(46, loaded_almanac.lowest_seed_location_ranges());
+
+        // A combined map from some other almanac shouldn't be accepted
+        let seed_to_soil = RangeMap::from_str(indoc! {"
+            seed-to-soil map:
+            50 98 2
+            52 50 48
+        "})
+        .unwrap();
+
+        assert!(Almanac::parse(TEST_ALMANAC_STRING, Some(seed_to_soil)).is_err());
+    }
+
+    #[test]
+    fn test_maps_fingerprint() {
+        let fingerprint = maps_fingerprint(TEST_ALMANAC_STRING);
+
+        // Different seeds, same maps
+        assert_eq!(
+            fingerprint,
+            maps_fingerprint(&TEST_ALMANAC_STRING.replace("seeds: 79 14 55 13", "seeds: 1 2"))
+        );
+
+        assert_ne!(
+            fingerprint,
+            maps_fingerprint(&TEST_ALMANAC_STRING.replace("50 98 2", "50 98 1"))
+        );
+    }
+
+    #[test]
+    fn test_chain_diagram() {
+        let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
+
+        let diagram = ChainDiagram {
+            chain: &almanac.chain,
+            trace_seed: None,
+        }
+        .to_string();
+
+        assert!(diagram.starts_with("digraph almanac {"));
+        assert!(diagram.contains(
+            "    seed [label=\"seed|<r0> [50, 98) +2|<r1> [98, 100) -48|<identity> otherwise unchanged\"];"
+        ));
+        assert!(diagram.contains("    seed -> soil;"));
+        assert!(diagram.contains("    humidity -> location;"));
+        assert!(!diagram.contains("red"));
+
+        let diagram = ChainDiagram {
+            chain: &almanac.chain,
+            trace_seed: Some(79),
+        }
+        .to_string();
+
+        // Seed 79 -> soil 81 -> fertilizer 81 -> water 81 -> light 74 -> temperature 78 ->
+        // humidity 78 -> location 82
+        assert!(diagram.contains("    seed:r0 -> soil [label=\"79 to 81\""));
+        assert!(diagram.contains("    soil:identity -> fertilizer [label=\"81 to 81\""));
+        assert!(diagram.contains("    location [label=\"location|82\", color=red];"));
+    }
+
+    #[test]
+    fn test_lowest_seed_location_brute_force() {
+        let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
+
+        assert_eq!(7, almanac.chain.len());
+        assert_eq!(46, almanac.lowest_seed_location_brute_force());
+    }
+
+    #[test]
+    fn test_lowest_seed_location_inverted() {
+        let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
+
+        assert_eq!(46, almanac.lowest_seed_location_inverted());
     }
 
     #[test]
@@ -427,8 +979,14 @@ mod test {
 
         let seed_to_fertilizer = &seed_to_soil + &soil_to_fertilizer;
 
-        assert_eq!(Seed, seed_to_fertilizer.source);
-        assert_eq!(Fertilizer, seed_to_fertilizer.destination);
+        assert_eq!(
+            Resource::from_str("seed").unwrap(),
+            seed_to_fertilizer.source
+        );
+        assert_eq!(
+            Resource::from_str("fertilizer").unwrap(),
+            seed_to_fertilizer.destination
+        );
 
         for seed in 0..=100 {
             assert_eq!(
@@ -438,6 +996,39 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_range_map_add_defragment() {
+        // Swaps 10..20 and 20..30; composing it with itself an even number of times should wind up
+        // with a single identity range no matter how many times we do it
+        let swap = RangeMap::from_str(indoc! {"
+            seed-to-seed map:
+            20 10 10
+            10 20 10
+        "})
+        .unwrap();
+
+        let mut combined = swap.clone();
+
+        for i in 1..=20 {
+            combined = &combined + &swap;
+
+            if i % 2 == 1 {
+                assert_eq!(1, combined.interval_map.intervals().len());
+                assert_eq!(0, combined.interval_map.intervals()[0].offset);
+            } else {
+                // [0, 10), [10, 20), [20, 30), and [30, u64::MAX)
+                assert_eq!(4, combined.interval_map.intervals().len());
+            }
+        }
+
+        let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
+
+        for window in almanac.range_map.interval_map.intervals().windows(2) {
+            assert_eq!(window[0].end, window[1].start);
+            assert_ne!(window[0].offset, window[1].offset);
+        }
+    }
+
     #[test]
     fn test_lowest_seed_location_ranges() {
         let almanac = Almanac::from_str(TEST_ALMANAC_STRING).unwrap();
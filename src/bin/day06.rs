@@ -1,46 +1,76 @@
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufRead, Read};
+use std::iter;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
     if let Some(path) = args.get(1) {
+        let verify = args.iter().skip(2).any(|arg| arg == "--verify");
+
+        let races_string = {
+            let mut races_string = String::new();
+            File::open(path)?.read_to_string(&mut races_string)?;
+
+            races_string
+        };
+
+        if let Some(format) = args
+            .iter()
+            .position(|arg| arg == "--export")
+            .and_then(|i| args.get(i + 1))
         {
-            let races = {
-                let mut races_string = String::new();
-                File::open(path)?.read_to_string(&mut races_string)?;
+            let races: Vec<Race> =
+                Race::races_from_reader(races_string.as_bytes(), ParseMode::Separate)
+                    .collect::<Result<_, _>>()?;
 
-                Race::races_from_str(races_string.as_str())?
-            };
+            match format.as_str() {
+                "gnuplot" => print!("{}", DistanceChart(&races)),
+                _ => return Err(format!("Unrecognized export format: {}", format).into()),
+            }
 
-            println!(
-                "Product of ways to beat record: {}",
-                races
-                    .iter()
-                    .map(|race| race.ways_to_beat_record())
-                    .product::<u32>()
-            );
+            return Ok(());
         }
 
         {
-            let race = {
-                let mut races_string = String::new();
-                File::open(path)?.read_to_string(&mut races_string)?;
+            let mut product = 1u64;
 
-                Race::long_race_from_str(races_string.as_str())?
-            };
+            for race in Race::races_from_reader(races_string.as_bytes(), ParseMode::Separate) {
+                let race = race?;
+
+                if verify {
+                    race.verify()?;
+                }
+
+                product = product
+                    .checked_mul(race.ways_to_beat_record())
+                    .ok_or("Product of ways to beat record overflowed")?;
+            }
+
+            println!("Product of ways to beat record: {}", product);
+        }
+
+        {
+            let race = Race::races_from_reader(races_string.as_bytes(), ParseMode::Kerned)
+                .next()
+                .ok_or("Could not find a long race")??;
 
             println!(
                 "Ways to beat record in a long race: {}",
                 race.ways_to_beat_record()
             );
+
+            if verify {
+                race.verify()?;
+            }
         }
 
         Ok(())
     } else {
-        Err("Usage: day06 INPUT_FILE_PATH".into())
+        Err("Usage: day06 INPUT_FILE_PATH [--verify] [--export gnuplot]".into())
     }
 }
 
@@ -49,82 +79,236 @@ struct Race {
     distance: u64,
 }
 
+// Decides whether the numbers on each line are separate races or, because of bad kerning, the
+// digits of a single long race
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ParseMode {
+    Separate,
+    Kerned,
+}
+
 impl Race {
-    fn races_from_str(string: &str) -> Result<Vec<Race>, Box<dyn Error>> {
-        let mut lines = string.lines();
+    // Reads races from pairs of "Time:" and "Distance:" lines, one pair at a time; there may be any
+    // number of pairs, and each pair may have any number of races as long as its time and distance
+    // lines have the same number of values. In kerned mode, each pair of lines is a single race.
+    // Blank lines are ignored.
+    fn races_from_reader(
+        reader: impl BufRead,
+        mode: ParseMode,
+    ) -> impl Iterator<Item = Result<Race, Box<dyn Error>>> {
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()));
 
-        let times: Vec<u64> = if let Some(times_line) = lines.next() {
-            if let Some(times) = times_line.strip_prefix("Time:") {
-                times
-                    .split(' ')
-                    .filter(|time| !time.is_empty())
-                    .map(|time| time.parse())
-                    .collect::<Result<_, _>>()?
-            } else {
-                return Err("Could not parse times".into());
+        let mut failed = false;
+
+        iter::from_fn(move || {
+            if failed {
+                return None;
             }
-        } else {
-            return Err("Could not find line with times".into());
-        };
 
-        let distances: Vec<u64> = if let Some(times_line) = lines.next() {
-            if let Some(times) = times_line.strip_prefix("Distance:") {
-                times
+            let (i, times_line) = lines.next()?;
+
+            let races = times_line.map_err(|e| e.into()).and_then(|times_line| {
+                let times = Race::parse_values(times_line.as_str(), "Time:", mode)
+                    .map_err(|e| format!("line {}: {}", i + 1, e))?;
+
+                let (j, distances_line) = lines
+                    .next()
+                    .ok_or_else(|| format!("line {}: Times have no matching distances", i + 1))?;
+
+                let distances = Race::parse_values(distances_line?.as_str(), "Distance:", mode)
+                    .map_err(|e| format!("line {}: {}", j + 1, e))?;
+
+                if times.len() != distances.len() {
+                    return Err(format!(
+                        "line {}: Found {} distances, but line {} has {} times",
+                        j + 1,
+                        distances.len(),
+                        i + 1,
+                        times.len()
+                    )
+                    .into());
+                }
+
+                Ok(times
+                    .into_iter()
+                    .zip(distances)
+                    .map(|(time, distance)| Race { time, distance })
+                    .collect::<Vec<Race>>())
+            });
+
+            failed = races.is_err();
+
+            Some(races)
+        })
+        .flat_map(|races: Result<Vec<Race>, Box<dyn Error>>| match races {
+            Ok(races) => races.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        })
+    }
+
+    fn parse_values(line: &str, prefix: &str, mode: ParseMode) -> Result<Vec<u64>, Box<dyn Error>> {
+        if let Some(values) = line.strip_prefix(prefix) {
+            match mode {
+                ParseMode::Separate => Ok(values
                     .split(' ')
-                    .filter(|time| !time.is_empty())
-                    .map(|time| time.parse())
-                    .collect::<Result<_, _>>()?
-            } else {
-                return Err("Could not parse distances".into());
+                    .filter(|value| !value.is_empty())
+                    .map(|value| value.parse())
+                    .collect::<Result<_, _>>()?),
+                ParseMode::Kerned => Ok(vec![values.replace(' ', "").parse()?]),
             }
         } else {
-            return Err("Could not find distances line".into());
-        };
+            Err(format!("Expected a line starting with \"{}\"", prefix).into())
+        }
+    }
 
-        Ok(times
-            .iter()
-            .zip(distances.iter())
-            .map(|(&time, &distance)| Race { time, distance })
-            .collect())
+    // Distances can get much bigger than times (up to T² / 4), so work in u128 to avoid overflow
+    fn distance(&self, charge_time: u64) -> u128 {
+        charge_time as u128 * (self.time - charge_time) as u128
     }
 
-    fn long_race_from_str(string: &str) -> Result<Race, Box<dyn Error>> {
-        let mut lines = string.lines();
+    fn ways_to_beat_record(&self) -> u64 {
+        self.winning_window()
+            .map_or(0, |(first_charge_time, last_charge_time)| {
+                last_charge_time - first_charge_time + 1
+            })
+    }
 
-        let time: u64 = if let Some(time_line) = lines.next() {
-            if let Some(times) = time_line.strip_prefix("Time:") {
-                times.replace(' ', "").parse()?
-            } else {
-                return Err("Could not parse times".into());
-            }
-        } else {
-            return Err("Could not find line with times".into());
-        };
+    // Returns the shortest and longest charge times that beat the record, if any do.
+    //
+    // The distance for a charge time c is c * (T - c), so we beat the record D whenever
+    // c² - Tc + D < 0, which is to say when c is strictly between (T ± √(T² - 4D)) / 2.
+    fn winning_window(&self) -> Option<(u64, u64)> {
+        let time = self.time as u128;
+        let distance = self.distance as u128;
 
-        let distance: u64 = if let Some(distance_line) = lines.next() {
-            if let Some(times) = distance_line.strip_prefix("Distance:") {
-                times.replace(' ', "").parse()?
-            } else {
-                return Err("Could not parse distance".into());
-            }
-        } else {
-            return Err("Could not find distance line".into());
-        };
+        // If the discriminant is negative, even the best possible charge time doesn't beat the
+        // record
+        let discriminant = (time * time).checked_sub(4 * distance)?;
+
+        // The integer square root may be a little off from the real root, so start with an estimate
+        // of the first winning charge time and nudge it into place
+        let mut first_charge_time = ((time - discriminant.isqrt()) / 2) as u64;
+
+        while first_charge_time <= self.time / 2 && self.distance(first_charge_time) <= distance {
+            first_charge_time += 1;
+        }
+
+        if first_charge_time > self.time / 2 {
+            return None;
+        }
 
-        Ok(Race { time, distance })
+        while first_charge_time > 0 && self.distance(first_charge_time - 1) > distance {
+            first_charge_time -= 1;
+        }
+
+        // Winning charge times are symmetric around T / 2
+        Some((first_charge_time, self.time - first_charge_time))
     }
 
-    fn ways_to_beat_record(&self) -> u32 {
+    fn ways_to_beat_record_brute_force(&self) -> u64 {
         (1..self.time)
-            .map(|charge_time| charge_time * (self.time - charge_time))
-            .filter(|&distance| distance > self.distance)
-            .count() as u32
+            .map(|charge_time| self.distance(charge_time))
+            .filter(|&distance| distance > self.distance as u128)
+            .count() as u64
+    }
+
+    fn verify(&self) -> Result<(), Box<dyn Error>> {
+        let ways_to_beat_record = self.ways_to_beat_record();
+        let brute_force_ways_to_beat_record = self.ways_to_beat_record_brute_force();
+
+        if ways_to_beat_record == brute_force_ways_to_beat_record {
+            Ok(())
+        } else {
+            Err(format!(
+                "Race with time {} and distance {}: found {} ways to beat record, but brute force found {}",
+                self.time, self.distance, ways_to_beat_record, brute_force_ways_to_beat_record
+            )
+            .into())
+        }
+    }
+}
+
+// Emits distance as a function of charge time for each race as gnuplot data, with one data set (in
+// gnuplot terms, one "index") per race. Columns are charge time, distance, the record, and whether
+// the charge time beats the record, so something like
+// `plot 'races.dat' index 0 using 1:2 with lines, '' index 0 using 1:3 with lines` draws the curve
+// with the record overlaid.
+struct DistanceChart<'a>(&'a [Race]);
+
+impl DistanceChart<'_> {
+    // Long races get sampled rather than plotted point by point
+    const MAX_POINTS: u64 = 1000;
+}
+
+impl Display for DistanceChart<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, race) in self.0.iter().enumerate() {
+            if i > 0 {
+                // Two blank lines separate data sets
+                writeln!(f)?;
+                writeln!(f)?;
+            }
+
+            let winning_window = race.winning_window();
+
+            match winning_window {
+                Some((first, last)) => writeln!(
+                    f,
+                    "# Race {}: time {}, record {}, winning charge times {} to {}",
+                    i + 1,
+                    race.time,
+                    race.distance,
+                    first,
+                    last
+                )?,
+                None => writeln!(
+                    f,
+                    "# Race {}: time {}, record {}, no winning charge times",
+                    i + 1,
+                    race.time,
+                    race.distance
+                )?,
+            }
+
+            writeln!(f, "# charge_time distance record beats_record")?;
+
+            let step = race.time.div_ceil(Self::MAX_POINTS).max(1);
+
+            // Always include the edges of the winning window so the crossover is visible
+            let mut charge_times: Vec<u64> = (0..=race.time).step_by(step as usize).collect();
+            charge_times.push(race.time);
+
+            if let Some((first, last)) = winning_window {
+                charge_times.extend([first - 1, first, last, last + 1]);
+            }
+
+            charge_times.sort();
+            charge_times.dedup();
+
+            for charge_time in charge_times {
+                let distance = race.distance(charge_time);
+
+                writeln!(
+                    f,
+                    "{} {} {} {}",
+                    charge_time,
+                    distance,
+                    race.distance,
+                    u8::from(distance > race.distance as u128)
+                )?;
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::Race;
+    use super::*;
     use indoc::indoc;
 
     const TEST_RACES_STRING: &str = indoc! {"
@@ -134,17 +318,240 @@ mod test {
 
     #[test]
     fn test_ways_to_beat_record() {
-        let races = Race::races_from_str(TEST_RACES_STRING).unwrap();
+        let races: Vec<Race> =
+            Race::races_from_reader(TEST_RACES_STRING.as_bytes(), ParseMode::Separate)
+                .collect::<Result<_, _>>()
+                .unwrap();
 
-        assert_eq!(4, races.get(0).unwrap().ways_to_beat_record());
+        assert_eq!(4, races.first().unwrap().ways_to_beat_record());
         assert_eq!(8, races.get(1).unwrap().ways_to_beat_record());
         assert_eq!(9, races.get(2).unwrap().ways_to_beat_record());
     }
 
+    #[test]
+    fn test_races_from_reader() {
+        let races: Vec<Race> = Race::races_from_reader(
+            indoc! {"
+                Time:      7  15   30
+                Distance:  9  40  200
+
+                Time: 1 2 3 4
+                Distance: 5 6 7 8
+                Time: 9
+                Distance: 10
+            "}
+            .as_bytes(),
+            ParseMode::Separate,
+        )
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (7, 9),
+                (15, 40),
+                (30, 200),
+                (1, 5),
+                (2, 6),
+                (3, 7),
+                (4, 8),
+                (9, 10)
+            ],
+            races
+                .iter()
+                .map(|race| (race.time, race.distance))
+                .collect::<Vec<_>>()
+        );
+
+        // Races before the error still come through, but nothing after it
+        let races: Vec<Result<Race, Box<dyn Error>>> = Race::races_from_reader(
+            indoc! {"
+                Time:      7  15
+                Distance:  9  40
+                Time:      7  15   30
+                Distance:  9  40
+                Time:      7
+                Distance:  9
+            "}
+            .as_bytes(),
+            ParseMode::Separate,
+        )
+        .collect();
+
+        assert_eq!(3, races.len());
+        assert!(races[0].is_ok());
+        assert!(races[1].is_ok());
+        assert_eq!(
+            "line 4: Found 2 distances, but line 3 has 3 times",
+            races[2].as_ref().err().unwrap().to_string()
+        );
+
+        for string in [
+            "Time: 7 15\n",
+            "Distance: 9 40\n",
+            "Time: 7\nTime: 9\n",
+            "Time: 7 x\nDistance: 9 40\n",
+        ] {
+            assert!(
+                Race::races_from_reader(string.as_bytes(), ParseMode::Separate)
+                    .any(|race| race.is_err())
+            );
+        }
+    }
+
+    #[test]
+    fn test_distance_chart() {
+        let races: Vec<Race> =
+            Race::races_from_reader(TEST_RACES_STRING.as_bytes(), ParseMode::Separate)
+                .collect::<Result<_, _>>()
+                .unwrap();
+
+        let chart = DistanceChart(&races[..1]).to_string();
+
+        assert_eq!(
+            indoc! {"
+                # Race 1: time 7, record 9, winning charge times 2 to 5
+                # charge_time distance record beats_record
+                0 0 9 0
+                1 6 9 0
+                2 10 9 1
+                3 12 9 1
+                4 12 9 1
+                5 10 9 1
+                6 6 9 0
+                7 0 9 0
+            "},
+            chart
+        );
+
+        let chart = DistanceChart(&races).to_string();
+
+        assert_eq!(3, chart.matches("# Race").count());
+        assert_eq!(2, chart.matches("\n\n\n").count());
+
+        // Long races are sampled, but still include the edges of the winning window
+        let race = Race::races_from_reader(TEST_RACES_STRING.as_bytes(), ParseMode::Kerned)
+            .next()
+            .unwrap()
+            .unwrap();
+        let chart = DistanceChart(std::slice::from_ref(&race)).to_string();
+
+        assert!(chart.lines().count() <= DistanceChart::MAX_POINTS as usize + 8);
+        assert!(chart.contains("\n14 1001224 940200 1\n"));
+        assert!(chart.contains("\n13 929721 940200 0\n"));
+        assert!(chart.contains("\n71530 0 940200 0\n"));
+    }
+
+    #[test]
+    fn test_races_from_reader_kerned() {
+        let races: Vec<Race> = Race::races_from_reader(
+            indoc! {"
+                Time:      7  15   30
+                Distance:  9  40  200
+                Time: 1 2
+                Distance: 3 4 5
+            "}
+            .as_bytes(),
+            ParseMode::Kerned,
+        )
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        assert_eq!(
+            vec![(71530, 940200), (12, 345)],
+            races
+                .iter()
+                .map(|race| (race.time, race.distance))
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn test_ways_to_beat_record_long_race() {
-        let race = Race::long_race_from_str(TEST_RACES_STRING).unwrap();
+        let race = Race::races_from_reader(TEST_RACES_STRING.as_bytes(), ParseMode::Kerned)
+            .next()
+            .unwrap()
+            .unwrap();
 
         assert_eq!(71503, race.ways_to_beat_record());
     }
+
+    #[test]
+    fn test_winning_window() {
+        let races: Vec<Race> =
+            Race::races_from_reader(TEST_RACES_STRING.as_bytes(), ParseMode::Separate)
+                .collect::<Result<_, _>>()
+                .unwrap();
+
+        assert_eq!(
+            vec![Some((2, 5)), Some((4, 11)), Some((11, 19))],
+            races
+                .iter()
+                .map(|race| race.winning_window())
+                .collect::<Vec<_>>()
+        );
+
+        // The best we can do is tie the record
+        assert_eq!(
+            None,
+            Race {
+                time: 10,
+                distance: 25
+            }
+            .winning_window()
+        );
+
+        assert_eq!(
+            Some((5, 5)),
+            Race {
+                time: 10,
+                distance: 24
+            }
+            .winning_window()
+        );
+    }
+
+    #[test]
+    fn test_ways_to_beat_record_extreme() {
+        // Only charging for exactly half the time reaches 2^64, which would wrap to 0 in a u64
+        let race = Race {
+            time: 1 << 33,
+            distance: u64::MAX,
+        };
+
+        assert_eq!(1 << 64, race.distance(1 << 32));
+        assert_eq!(1, race.ways_to_beat_record());
+
+        // Everything but 0, 1, T - 1, and T beats the record
+        let race = Race {
+            time: u64::MAX,
+            distance: u64::MAX,
+        };
+
+        assert_eq!(u64::MAX - 3, race.ways_to_beat_record());
+
+        let race = Race {
+            time: u64::MAX,
+            distance: 0,
+        };
+
+        assert_eq!(u64::MAX - 1, race.ways_to_beat_record());
+    }
+
+    #[test]
+    fn test_ways_to_beat_record_closed_form() {
+        for time in 0..60 {
+            for distance in 0..=(time * time / 4 + 2) {
+                let race = Race { time, distance };
+
+                assert_eq!(
+                    race.ways_to_beat_record_brute_force(),
+                    race.ways_to_beat_record(),
+                    "time {}, distance {}",
+                    time,
+                    distance
+                );
+            }
+        }
+    }
 }
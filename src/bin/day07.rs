@@ -1,9 +1,10 @@
 use crate::Card::*;
 use crate::HandType::*;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
 use std::env;
 use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::str::FromStr;
@@ -18,115 +19,188 @@ fn main() -> Result<(), Box<dyn Error>> {
             .map(|line| Hand::from_str(line.as_str()))
             .collect::<Result<Vec<_>, _>>()?;
 
-        println!("Total winnings: {}", total_winnings(&hands));
+        // Identical hands have no well-defined order relative to one another, so their ranks (and
+        // therefore winnings) are ambiguous
+        let duplicates: Vec<String> = duplicate_hands(&hands)
+            .iter()
+            .map(|(hand, line_numbers)| {
+                format!(
+                    "Hand {} appears on lines {}",
+                    hand,
+                    line_numbers
+                        .iter()
+                        .map(|line_number| line_number.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                )
+            })
+            .collect();
+
+        if !duplicates.is_empty() {
+            if args.iter().skip(2).any(|arg| arg == "--strict") {
+                return Err(duplicates.join("\n").into());
+            }
+
+            duplicates
+                .iter()
+                .for_each(|duplicate| eprintln!("Warning: {}", duplicate));
+        }
+
+        let rules = match args
+            .iter()
+            .position(|arg| arg == "--rules")
+            .and_then(|i| args.get(i + 1))
+        {
+            Some(rules) => Some(Rules::from_str(rules)?),
+            None => None,
+        };
+
+        if args.iter().skip(2).any(|arg| arg == "--leaderboard") {
+            println!("rank\thand\ttype\tbid\twinnings");
+
+            for ranked_hand in ranked_hands(&hands, rules.as_ref().unwrap_or(&Rules::CamelCards)) {
+                println!(
+                    "{}\t{}\t{:?}\t{}\t{}",
+                    ranked_hand.rank,
+                    ranked_hand.hand,
+                    ranked_hand.hand_type,
+                    ranked_hand.hand.bid,
+                    ranked_hand.winnings
+                );
+            }
+
+            return Ok(());
+        }
+
+        if let Some(rules) = rules {
+            println!("Total winnings: {}", total_winnings(&hands, &rules));
+
+            return Ok(());
+        }
+
+        // Jacks are wild by default, but any card (or no card at all) can act as the joker
+        let joker_rules = match args
+            .iter()
+            .position(|arg| arg == "--joker")
+            .and_then(|i| args.get(i + 1))
+            .map(String::as_str)
+        {
+            Some("none") => Rules::CamelCards,
+            Some(joker) => Rules::CamelCardsWithJokers(parse_card(joker)?),
+            None => Rules::CamelCardsWithJokers(Jack),
+        };
+
+        println!(
+            "Total winnings: {}",
+            total_winnings(&hands, &Rules::CamelCards)
+        );
 
         println!(
             "Total winnings with jokers: {}",
-            total_winnings_with_jokers(&hands)
+            total_winnings(&hands, &joker_rules)
         );
 
         Ok(())
     } else {
-        Err("Usage: day07 INPUT_FILE_PATH".into())
+        Err(
+            "Usage: day07 INPUT_FILE_PATH [--joker CARD|none] [--rules camel|jokers[:CARD]|poker|aces-low|order:CARDS] [--leaderboard] [--strict]"
+                .into(),
+        )
     }
 }
 
-fn total_winnings(hands: &[Hand]) -> u64 {
-    let mut sorted_hands = Vec::from_iter(hands);
-    sorted_hands.sort();
+fn total_winnings(hands: &[Hand], rules: &Rules) -> u64 {
+    ranked_hands(hands, rules).fold(0, |total, ranked_hand| total + ranked_hand.winnings)
+}
 
-    sorted_hands
+// Finds groups of hands with identical cards and the (one-based) line numbers on which they appear
+fn duplicate_hands(hands: &[Hand]) -> Vec<(&Hand, Vec<usize>)> {
+    let mut line_numbers_by_cards: HashMap<&[Card], Vec<usize>> = HashMap::new();
+
+    hands.iter().enumerate().for_each(|(i, hand)| {
+        line_numbers_by_cards
+            .entry(hand.cards.as_slice())
+            .or_default()
+            .push(i + 1)
+    });
+
+    let mut duplicates: Vec<(&Hand, Vec<usize>)> = line_numbers_by_cards
+        .into_values()
+        .filter(|line_numbers| line_numbers.len() > 1)
+        .map(|line_numbers| (&hands[line_numbers[0] - 1], line_numbers))
+        .collect();
+
+    duplicates.sort_by_key(|(_, line_numbers)| line_numbers[0]);
+    duplicates
+}
+
+// Yields hands from weakest (rank 1) to strongest under the given rules
+fn ranked_hands<'a>(hands: &'a [Hand], rules: &Rules) -> impl Iterator<Item = RankedHand<'a>> {
+    let mut typed_hands: Vec<(HandType, &Hand)> = hands
         .iter()
-        .rev()
+        .map(|hand| (rules.hand_type(hand), hand))
+        .collect();
+
+    typed_hands.sort_by_cached_key(|(_, hand)| rules.sort_key(hand));
+
+    typed_hands
+        .into_iter()
         .enumerate()
-        .map(|(i, hand)| (hands.len() - i) as u64 * hand.bid)
-        .sum()
+        .map(|(i, (hand_type, hand))| RankedHand {
+            rank: i + 1,
+            hand,
+            hand_type,
+            winnings: (i + 1) as u64 * hand.bid,
+        })
 }
 
-fn total_winnings_with_jokers(hands: &[Hand]) -> u64 {
-    let mut sorted_hands = Vec::from_iter(hands);
-    sorted_hands.sort_by(|a, b| a.cmp_with_jokers(b));
+struct RankedHand<'a> {
+    rank: usize,
+    hand: &'a Hand,
 
-    sorted_hands
-        .iter()
-        .rev()
-        .enumerate()
-        .map(|(i, hand)| (hands.len() - i) as u64 * hand.bid)
-        .sum()
+    // The hand's type under the rules used to rank it, which may differ from its natural type
+    hand_type: HandType,
+
+    winnings: u64,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 struct Hand {
-    cards: [Card; 5],
+    cards: Vec<Card>,
     bid: u64,
+
+    // Sorting compares hand types over and over, so it's worth figuring out the type just once
+    hand_type: HandType,
 }
 
 impl Hand {
-    fn hand_type(&self) -> HandType {
-        let mut counts_by_card_type = HashMap::new();
+    fn new(cards: Vec<Card>, bid: u64) -> Self {
+        let hand_type = HandType::from_cards(&cards);
 
-        self.cards
-            .iter()
-            .for_each(|card| *counts_by_card_type.entry(card).or_insert(0u32) += 1);
-
-        let mut non_zero_counts = counts_by_card_type
-            .values()
-            .filter(|&&count| count > 0)
-            .copied()
-            .collect::<Vec<u32>>();
-
-        non_zero_counts.sort_by(|a, b| b.cmp(a));
-
-        match non_zero_counts.as_slice() {
-            [5] => FiveOfAKind,
-            [4, 1] => FourOfAKind,
-            [3, 2] => FullHouse,
-            [3, 1, 1] => ThreeOfAKind,
-            [2, 2, 1] => TwoPair,
-            [2, 1, 1, 1] => OnePair,
-            [1, 1, 1, 1, 1] => HighCard,
-            _ => unreachable!(),
+        Hand {
+            cards,
+            bid,
+            hand_type,
         }
     }
 
-    fn strongest_hand_type_with_jokers(&self) -> HandType {
-        static REPLACEMENTS: [Card; 12] = [
-            Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Queen, King, Ace,
-        ];
-
-        REPLACEMENTS
-            .iter()
-            .map(|replacement| {
-                let replaced_cards = self
-                    .cards
-                    .iter()
-                    .map(|card| if card == &Jack { replacement } else { card })
-                    .copied()
-                    .collect::<Vec<Card>>()
-                    .try_into()
-                    .expect("Could not convert hand to an array of five cards");
-
-                Hand {
-                    cards: replaced_cards,
-                    bid: 0,
-                }.hand_type()
-            })
-            .max()
-            .unwrap()
+    fn hand_type(&self) -> HandType {
+        self.hand_type
     }
 
-    fn cmp_with_jokers(&self, other: &Self) -> Ordering {
-        let hand_type_ordering =
-            self.strongest_hand_type_with_jokers().cmp(&other.strongest_hand_type_with_jokers());
+    fn strongest_hand_type_with_jokers(&self, joker: Card) -> HandType {
+        let jokers = self.cards.iter().filter(|&&card| card == joker).count() as u32;
+        let mut counts = card_counts(self.cards.iter().filter(|&&card| card != joker));
 
-        if hand_type_ordering == Ordering::Equal {
-            (0..self.cards.len())
-                .map(|i| self.cards[i].cmp_with_jokers(&other.cards[i]))
-                .find(|ordering| ordering != &Ordering::Equal)
-                .unwrap_or(Ordering::Equal)
+        // Jokers always do the most good by joining the largest group of other cards; if there
+        // are no other cards, the jokers form a group of their own
+        if let Some(largest) = counts.iter_mut().max() {
+            *largest += jokers;
         } else {
-            hand_type_ordering
+            counts.push(jokers);
         }
+
+        HandType::from_counts(counts)
     }
 }
 
@@ -136,18 +210,24 @@ impl PartialOrd for Hand {
     }
 }
 
+// Hands are naturally ordered according to the standard camel cards rules; this is equivalent to
+// comparing `Rules::CamelCards` sort keys, but uses the cached hand type and doesn't allocate
 impl Ord for Hand {
     fn cmp(&self, other: &Self) -> Ordering {
-        let hand_type_ordering = self.hand_type().cmp(&other.hand_type());
+        let card_order = CardOrder::default();
+
+        self.hand_type.cmp(&other.hand_type).then_with(|| {
+            self.cards
+                .iter()
+                .map(|&card| card_order.strength(card))
+                .cmp(other.cards.iter().map(|&card| card_order.strength(card)))
+        })
+    }
+}
 
-        if hand_type_ordering == Ordering::Equal {
-            (0..self.cards.len())
-                .map(|i| self.cards[i].cmp(&other.cards[i]))
-                .find(|ordering| ordering != &Ordering::Equal)
-                .unwrap_or(Ordering::Equal)
-        } else {
-            hand_type_ordering
-        }
+impl Display for Hand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.cards.iter().try_for_each(|card| write!(f, "{}", card))
     }
 }
 
@@ -156,16 +236,18 @@ impl FromStr for Hand {
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
         if let [cards, bid] = string.split(' ').collect::<Vec<&str>>().as_slice() {
-            let cards: [Card; 5] = cards
+            let cards = cards
                 .chars()
-                .filter_map(|c| Card::try_from(c).ok())
-                .collect::<Vec<Card>>()
-                .try_into()
-                .map_err(|_| "Could not convert hand to an array of five cards")?;
+                .map(Card::try_from)
+                .collect::<Result<Vec<Card>, _>>()?;
+
+            if cards.is_empty() {
+                return Err("Hands must have at least one card".into());
+            }
 
             let bid = bid.parse()?;
 
-            Ok(Hand { cards, bid })
+            Ok(Hand::new(cards, bid))
         } else {
             Err("Could not parse hand".into())
         }
@@ -190,26 +272,30 @@ enum Card {
 }
 
 impl Card {
-    fn strength_with_jokers(&self) -> u8 {
-        match self {
-            Jack => 1,
-            Two => 2,
-            Three => 3,
-            Four => 4,
-            Five => 5,
-            Six => 6,
-            Seven => 7,
-            Eight => 8,
-            Nine => 9,
-            Ten => 10,
-            Queen => 11,
-            King => 12,
-            Ace => 13,
-        }
-    }
+    const ALL: [Card; 13] = [
+        Two, Three, Four, Five, Six, Seven, Eight, Nine, Ten, Jack, Queen, King, Ace,
+    ];
+}
 
-    fn cmp_with_jokers(&self, other: &Self) -> Ordering {
-        self.strength_with_jokers().cmp(&other.strength_with_jokers())
+impl Display for Card {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Two => '2',
+            Three => '3',
+            Four => '4',
+            Five => '5',
+            Six => '6',
+            Seven => '7',
+            Eight => '8',
+            Nine => '9',
+            Ten => 'T',
+            Jack => 'J',
+            Queen => 'Q',
+            King => 'K',
+            Ace => 'A',
+        };
+
+        write!(f, "{}", c)
     }
 }
 
@@ -236,7 +322,159 @@ impl TryFrom<char> for Card {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+// A ranking of individual cards, stored as the strength of each card indexed by its natural rank
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct CardOrder {
+    strengths: [u8; 13],
+}
+
+impl CardOrder {
+    fn new(weakest_to_strongest: &[Card]) -> Result<Self, Box<dyn Error>> {
+        let mut strengths = [None; 13];
+
+        for (strength, &card) in weakest_to_strongest.iter().enumerate() {
+            if strengths[card as usize].replace(strength as u8).is_some() {
+                return Err(format!("Card {} appears more than once in card order", card).into());
+            }
+        }
+
+        if let Some(missing) = Card::ALL
+            .iter()
+            .find(|&&card| strengths[card as usize].is_none())
+        {
+            return Err(format!("Card {} is missing from card order", missing).into());
+        }
+
+        Ok(CardOrder {
+            strengths: strengths.map(|strength| strength.unwrap()),
+        })
+    }
+
+    // The natural order, except that the given card is the weakest of all
+    fn with_lowest(lowest: Card) -> Self {
+        let mut weakest_to_strongest = vec![lowest];
+        weakest_to_strongest.extend(Card::ALL.iter().filter(|&&card| card != lowest));
+
+        CardOrder::new(&weakest_to_strongest).unwrap()
+    }
+
+    fn strength(&self, card: Card) -> u8 {
+        self.strengths[card as usize]
+    }
+}
+
+impl Default for CardOrder {
+    fn default() -> Self {
+        CardOrder::new(&Card::ALL).unwrap()
+    }
+}
+
+impl FromStr for CardOrder {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        CardOrder::new(
+            &string
+                .chars()
+                .map(Card::try_from)
+                .collect::<Result<Vec<Card>, _>>()?,
+        )
+    }
+}
+
+fn parse_card(string: &str) -> Result<Card, Box<dyn Error>> {
+    if let [c] = string.chars().collect::<Vec<char>>().as_slice() {
+        Card::try_from(*c)
+    } else {
+        Err(format!("Unrecognized card: {}", string).into())
+    }
+}
+
+// The different ways hands can be ranked against one another; all rule sets rank hands by type
+// first, but differ in how they break ties and (for jokers) how they determine a hand's type
+#[derive(Debug, Eq, PartialEq)]
+enum Rules {
+    // Ties are broken by comparing cards in the order they were dealt
+    CamelCards,
+
+    // Like camel cards, but the given card is wild and is the weakest card in tie-breaks
+    CamelCardsWithJokers(Card),
+
+    // Ties are broken by comparing the largest groups of cards first, then the highest cards
+    Poker,
+
+    // Like camel cards, but aces are the weakest card in tie-breaks
+    AcesLow,
+
+    // Like camel cards, but ties are broken with an arbitrary card order
+    CustomOrder(CardOrder),
+}
+
+impl Rules {
+    fn hand_type(&self, hand: &Hand) -> HandType {
+        match self {
+            Rules::CamelCardsWithJokers(joker) => hand.strongest_hand_type_with_jokers(*joker),
+            _ => hand.hand_type(),
+        }
+    }
+
+    // The order used to compare individual cards when breaking ties between hands of the same type
+    fn card_order(&self) -> CardOrder {
+        match self {
+            Rules::CamelCards | Rules::Poker => CardOrder::default(),
+            Rules::CamelCardsWithJokers(joker) => CardOrder::with_lowest(*joker),
+            Rules::AcesLow => CardOrder::with_lowest(Ace),
+            Rules::CustomOrder(card_order) => *card_order,
+        }
+    }
+
+    // Produces a key that sorts hands from weakest to strongest under these rules
+    fn sort_key(&self, hand: &Hand) -> (HandType, Vec<u8>) {
+        let card_order = self.card_order();
+        let mut cards = hand.cards.clone();
+
+        if self == &Rules::Poker {
+            let counts_by_card = cards.iter().fold(HashMap::new(), |mut counts, card| {
+                *counts.entry(*card).or_insert(0u32) += 1;
+                counts
+            });
+
+            cards.sort_by_key(|&card| Reverse((counts_by_card[&card], card_order.strength(card))));
+        }
+
+        (
+            self.hand_type(hand),
+            cards
+                .iter()
+                .map(|&card| card_order.strength(card))
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for Rules {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "camel" => Ok(Rules::CamelCards),
+            "jokers" => Ok(Rules::CamelCardsWithJokers(Jack)),
+            "poker" => Ok(Rules::Poker),
+            "aces-low" => Ok(Rules::AcesLow),
+            _ => {
+                if let Some(joker) = string.strip_prefix("jokers:") {
+                    Ok(Rules::CamelCardsWithJokers(parse_card(joker)?))
+                } else if let Some(card_order) = string.strip_prefix("order:") {
+                    Ok(Rules::CustomOrder(CardOrder::from_str(card_order)?))
+                } else {
+                    Err(format!("Unrecognized rules: {}", string).into())
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 enum HandType {
     HighCard,
     OnePair,
@@ -247,6 +485,39 @@ enum HandType {
     FiveOfAKind,
 }
 
+impl HandType {
+    fn from_cards(cards: &[Card]) -> Self {
+        Self::from_counts(card_counts(cards.iter()))
+    }
+
+    // Determines a hand type from the number of cards in each group of identical cards; only the
+    // two largest groups matter, so this works for hands of any size
+    fn from_counts(mut counts: Vec<u32>) -> Self {
+        counts.sort_by(|a, b| b.cmp(a));
+
+        let largest = counts.first().copied().unwrap_or(0);
+        let second_largest = counts.get(1).copied().unwrap_or(0);
+
+        match (largest, second_largest) {
+            (5.., _) => FiveOfAKind,
+            (4, _) => FourOfAKind,
+            (3, 2..) => FullHouse,
+            (3, _) => ThreeOfAKind,
+            (2, 2) => TwoPair,
+            (2, _) => OnePair,
+            _ => HighCard,
+        }
+    }
+}
+
+fn card_counts<'a>(cards: impl Iterator<Item = &'a Card>) -> Vec<u32> {
+    let mut counts_by_card_type = HashMap::new();
+
+    cards.for_each(|card| *counts_by_card_type.entry(card).or_insert(0u32) += 1);
+
+    counts_by_card_type.into_values().collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -270,6 +541,25 @@ mod test {
         assert_eq!(FourOfAKind, Hand::from_str("QQQQK 1").unwrap().hand_type());
         assert_eq!(FullHouse, Hand::from_str("AAA22 1").unwrap().hand_type());
         assert_eq!(HighCard, Hand::from_str("A2345 1").unwrap().hand_type());
+
+        // Other hand sizes
+        assert_eq!(HighCard, Hand::from_str("A 1").unwrap().hand_type());
+        assert_eq!(OnePair, Hand::from_str("AA2 1").unwrap().hand_type());
+        assert_eq!(FullHouse, Hand::from_str("AAA2227 1").unwrap().hand_type());
+        assert_eq!(TwoPair, Hand::from_str("AA22334 1").unwrap().hand_type());
+        assert_eq!(
+            FiveOfAKind,
+            Hand::from_str("AAAAAA2 1").unwrap().hand_type()
+        );
+        assert_eq!(
+            FiveOfAKind,
+            Hand::from_str("JJJJJJ2 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+
+        assert!(Hand::from_str(" 1").is_err());
+        assert!(Hand::from_str("AXA22 1").is_err());
     }
 
     #[test]
@@ -286,7 +576,76 @@ mod test {
         .collect::<Result<_, _>>()
         .unwrap();
 
-        assert_eq!(6440, total_winnings(&hands));
+        assert_eq!(6440, total_winnings(&hands, &Rules::CamelCards));
+    }
+
+    #[test]
+    fn test_duplicate_hands() {
+        let hands: Vec<Hand> = indoc! {"
+            32T3K 765
+            T55J5 684
+            32T3K 28
+            KTJJT 220
+            T55J5 483
+            32T3K 1
+        "}
+        .lines()
+        .map(Hand::from_str)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        let duplicates: Vec<(String, Vec<usize>)> = duplicate_hands(&hands)
+            .into_iter()
+            .map(|(hand, line_numbers)| (hand.to_string(), line_numbers))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (String::from("32T3K"), vec![1, 3, 6]),
+                (String::from("T55J5"), vec![2, 5]),
+            ],
+            duplicates
+        );
+
+        assert!(duplicate_hands(&hands[0..2]).is_empty());
+    }
+
+    #[test]
+    fn test_ranked_hands() {
+        let hands: Vec<Hand> = indoc! {"
+            32T3K 765
+            T55J5 684
+            KK677 28
+            KTJJT 220
+            QQQJA 483
+        "}
+        .lines()
+        .map(Hand::from_str)
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        let ranked: Vec<(usize, String, HandType, u64)> =
+            ranked_hands(&hands, &Rules::CamelCardsWithJokers(Jack))
+                .map(|ranked_hand| {
+                    (
+                        ranked_hand.rank,
+                        ranked_hand.hand.to_string(),
+                        ranked_hand.hand_type,
+                        ranked_hand.winnings,
+                    )
+                })
+                .collect();
+
+        assert_eq!(
+            vec![
+                (1, String::from("32T3K"), OnePair, 765),
+                (2, String::from("KK677"), TwoPair, 56),
+                (3, String::from("T55J5"), FourOfAKind, 2052),
+                (4, String::from("QQQJA"), FourOfAKind, 1932),
+                (5, String::from("KTJJT"), FourOfAKind, 1100),
+            ],
+            ranked
+        );
     }
 
     #[test]
@@ -303,6 +662,147 @@ mod test {
         .collect::<Result<_, _>>()
         .unwrap();
 
-        assert_eq!(5905, total_winnings_with_jokers(&hands));
+        assert_eq!(
+            5905,
+            total_winnings(&hands, &Rules::CamelCardsWithJokers(Jack))
+        );
+    }
+
+    #[test]
+    fn test_strongest_hand_type_with_jokers() {
+        let hand = Hand::from_str("T55J5 684").unwrap();
+
+        assert_eq!(FourOfAKind, hand.strongest_hand_type_with_jokers(Jack));
+        assert_eq!(FourOfAKind, hand.strongest_hand_type_with_jokers(Ten));
+        assert_eq!(
+            FiveOfAKind,
+            Hand::from_str("22A2A 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Two)
+        );
+        assert_eq!(ThreeOfAKind, hand.strongest_hand_type_with_jokers(Ace));
+
+        assert_eq!(
+            FiveOfAKind,
+            Hand::from_str("JJJJJ 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+        assert_eq!(
+            FiveOfAKind,
+            Hand::from_str("JJJJ2 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+        assert_eq!(
+            FourOfAKind,
+            Hand::from_str("JJJ23 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+        assert_eq!(
+            FullHouse,
+            Hand::from_str("2J323 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+        assert_eq!(
+            OnePair,
+            Hand::from_str("2J345 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+        assert_eq!(
+            HighCard,
+            Hand::from_str("23456 1")
+                .unwrap()
+                .strongest_hand_type_with_jokers(Jack)
+        );
+    }
+
+    #[test]
+    fn test_card_order() {
+        let natural = CardOrder::default();
+
+        assert!(natural.strength(Two) < natural.strength(Jack));
+        assert!(natural.strength(Jack) < natural.strength(Queen));
+        assert_eq!(12, natural.strength(Ace));
+
+        // Jokers sit below twos
+        let jokers_low = CardOrder::with_lowest(Jack);
+
+        assert_eq!(0, jokers_low.strength(Jack));
+        assert!(jokers_low.strength(Jack) < jokers_low.strength(Two));
+        assert!(jokers_low.strength(Ten) < jokers_low.strength(Queen));
+        assert_eq!(jokers_low, CardOrder::from_str("J23456789TQKA").unwrap());
+
+        let reversed = CardOrder::from_str("AKQJT98765432").unwrap();
+        assert!(reversed.strength(Ace) < reversed.strength(Two));
+
+        assert!(CardOrder::from_str("23456789TJQK").is_err());
+        assert!(CardOrder::from_str("223456789TJQKA").is_err());
+        assert!(CardOrder::from_str("23456789TJQKX").is_err());
+    }
+
+    #[test]
+    fn test_rules() {
+        let cmp = |rules: &Rules, a: &str, b: &str| {
+            rules
+                .sort_key(&Hand::from_str(a).unwrap())
+                .cmp(&rules.sort_key(&Hand::from_str(b).unwrap()))
+        };
+
+        // Twos are wild, so they're the weakest card in tie-breaks, but jacks are ordinary
+        let twos_wild = Rules::CamelCardsWithJokers(Two);
+
+        assert_eq!(Ordering::Less, cmp(&twos_wild, "2KKKQ 1", "3KKKK 1"));
+        assert_eq!(Ordering::Greater, cmp(&twos_wild, "JKKKK 1", "TKKKK 1"));
+        assert_eq!(Ordering::Greater, cmp(&twos_wild, "2KKKK 1", "3KKKK 1"));
+
+        assert_eq!(
+            Ordering::Greater,
+            cmp(&Rules::CamelCards, "AKKKK 1", "2KKKK 1")
+        );
+        assert_eq!(Ordering::Less, cmp(&Rules::AcesLow, "AKKKK 1", "2KKKK 1"));
+
+        // Jokers make the best hand they can, but lose tie-breaks even to twos
+        let jokers = Rules::CamelCardsWithJokers(Jack);
+
+        assert_eq!(Ordering::Less, cmp(&jokers, "JKKK2 1", "QQQQ2 1"));
+        assert_eq!(Ordering::Less, cmp(&jokers, "JKKK2 1", "2KKKJ 1"));
+
+        // A custom order changes tie-breaks, but not hand types
+        let reversed = Rules::from_str("order:AKQJT98765432").unwrap();
+
+        assert_eq!(Ordering::Greater, cmp(&reversed, "2KKKK 1", "AKKKK 1"));
+        assert_eq!(Ordering::Less, cmp(&reversed, "2KKKQ 1", "AKKKK 1"));
+
+        assert_eq!(
+            Ordering::Less,
+            Hand::from_str("2KKKK 1")
+                .unwrap()
+                .cmp(&Hand::from_str("AKKKK 1").unwrap())
+        );
+
+        // Poker compares the bigger group first, no matter where it appears in the hand
+        assert_eq!(
+            Ordering::Greater,
+            cmp(&Rules::CamelCards, "A2233 1", "K2244 1")
+        );
+        assert_eq!(Ordering::Less, cmp(&Rules::Poker, "A2233 1", "K2244 1"));
+        assert_eq!(Ordering::Greater, cmp(&Rules::Poker, "3332A 1", "2223A 1"));
+        assert_eq!(Ordering::Equal, cmp(&Rules::Poker, "34567 1", "76543 1"));
+
+        assert_eq!(
+            Rules::CamelCardsWithJokers(Two),
+            Rules::from_str("jokers:2").unwrap()
+        );
+        assert_eq!(Rules::Poker, Rules::from_str("poker").unwrap());
+        assert_eq!(
+            Rules::CustomOrder(CardOrder::with_lowest(Jack)),
+            Rules::from_str("order:J23456789TQKA").unwrap()
+        );
+        assert!(Rules::from_str("jokers:X").is_err());
+        assert!(Rules::from_str("bridge").is_err());
     }
 }
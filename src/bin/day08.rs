@@ -1,5 +1,7 @@
+use rayon::prelude::*;
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
@@ -16,92 +18,575 @@ fn main() -> Result<(), Box<dyn Error>> {
             NetworkMap::from_str(map_string.as_str())?
         };
 
+        if args.iter().skip(2).any(|arg| arg == "--dot") {
+            print!("{}", NetworkDiagram(&network_map));
+            return Ok(());
+        }
+
+        if let Some(i) = args.iter().position(|arg| arg == "--between") {
+            if let (Some(start_suffix), Some(end_suffix)) = (args.get(i + 1), args.get(i + 2)) {
+                println!(
+                    "Steps from nodes ending in {} to nodes ending in {}: {}",
+                    start_suffix,
+                    end_suffix,
+                    network_map.steps_between(
+                        |name| name.ends_with(start_suffix.as_str()),
+                        |name| name.ends_with(end_suffix.as_str())
+                    )?
+                );
+
+                return Ok(());
+            } else {
+                return Err("--between requires a start suffix and an end suffix".into());
+            }
+        }
+
+        let cycle_analysis = network_map.cycle_analysis(is_ghost_start, is_ghost_exit);
+
+        if args.iter().skip(2).any(|arg| arg == "--report") {
+            print!("{}", cycle_analysis);
+            println!();
+            println!(
+                "Ghost steps to exit: {}",
+                cycle_analysis.ghost_steps_to_exit()?
+            );
+
+            return Ok(());
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--diagnose") {
+            let mut assumption_holds = true;
+
+            for cycle in &cycle_analysis.cycles {
+                println!(
+                    "{}: enters a {}-step cycle after {} steps; exits after {:?} steps",
+                    cycle.start, cycle.cycle_length, cycle.cycle_start, cycle.exit_offsets
+                );
+
+                for problem in cycle.lcm_assumption_problems() {
+                    println!("  WARNING: {}", problem);
+                    assumption_holds = false;
+                }
+            }
+
+            if assumption_holds {
+                println!("LCM assumption holds");
+            } else {
+                println!("LCM assumption DOES NOT HOLD; ghost steps need the general solution");
+            }
+
+            return Ok(());
+        }
+
+        if cycle_analysis
+            .cycles
+            .iter()
+            .any(|cycle| !cycle.lcm_assumption_problems().is_empty())
+        {
+            eprintln!(
+                "WARNING: ghost paths don't have the structure the usual LCM shortcut relies on; \
+                 falling back to a general solution (run with --diagnose for details)"
+            );
+        }
+
         println!(
             "Human steps between AAA and ZZZ: {}",
             network_map.human_steps_to_exit().unwrap()
         );
 
-        println!(
-            "Ghost steps between AAA and ZZZ: {}",
-            network_map.ghost_steps_to_exit().unwrap()
-        );
+        // If the analysis says the ghosts never line up, we may still want to check that by
+        // simulation before reporting the error
+        let ghost_steps = network_map.ghost_steps_to_exit();
+
+        if let Ok(steps) = ghost_steps {
+            println!("Ghost steps between AAA and ZZZ: {}", steps);
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--verify") {
+            let max_steps = match args
+                .iter()
+                .position(|arg| arg == "--max-steps")
+                .and_then(|i| args.get(i + 1))
+            {
+                Some(max_steps) => max_steps.parse()?,
+                None => DEFAULT_MAX_SIMULATION_STEPS,
+            };
+
+            let simulated_ghost_steps =
+                network_map.steps_between_simulated(is_ghost_start, is_ghost_exit, max_steps);
+
+            match simulated_ghost_steps {
+                Some(steps) => {
+                    println!("Ghost steps between AAA and ZZZ (by simulation): {}", steps)
+                }
+                None => println!(
+                    "Ghost steps between AAA and ZZZ (by simulation): more than {}",
+                    max_steps
+                ),
+            }
+
+            let analytic_ghost_steps = ghost_steps.as_ref().ok().copied();
+
+            if simulated_ghost_steps != analytic_ghost_steps.filter(|&steps| steps <= max_steps) {
+                return Err(format!(
+                    "Analytic ghost steps ({}) do not match simulated result",
+                    analytic_ghost_steps.map_or(String::from("never"), |steps| steps.to_string())
+                )
+                .into());
+            }
+        }
+
+        ghost_steps?;
 
         Ok(())
     } else {
-        Err("Usage: day08 INPUT_FILE_PATH".into())
+        Err("Usage: day08 INPUT_FILE_PATH [--report] [--diagnose] [--dot] [--verify [--max-steps MAX_STEPS]] [--between START_SUFFIX END_SUFFIX]".into())
     }
 }
 
+const DEFAULT_MAX_SIMULATION_STEPS: u64 = 100_000_000;
+const SIMULATION_CHUNK_SIZE: u64 = 1 << 16;
+
 struct NetworkMap {
     directions: Vec<Direction>,
-    nodes: HashMap<String, (String, String)>,
+
+    // Node names are interned so we can navigate by index instead of hashing strings at every
+    // step; a node's index is its position in this list
+    names: Vec<String>,
+
+    // The left and right destinations of each node, indexed by node index
+    edges: Vec<(u32, u32)>,
 }
 
 impl NetworkMap {
-    fn human_steps_to_exit(&self) -> Option<u32> {
-        let mut position = "AAA";
-        let mut steps = 0;
-        let mut directions = self.directions.iter().cycle();
+    fn name(&self, node: u32) -> &str {
+        &self.names[node as usize]
+    }
 
-        while position != "ZZZ" {
-            if let Some(destinations) = self.nodes.get(position) {
-                position = match directions.next().unwrap() {
-                    Direction::Left => &destinations.0,
-                    Direction::Right => &destinations.1,
-                };
+    // Finds all of the nodes that match the given predicate, sorted by name
+    fn matching_nodes(&self, predicate: impl Fn(&str) -> bool) -> Vec<u32> {
+        let mut nodes: Vec<u32> = (0..self.names.len() as u32)
+            .filter(|&node| predicate(self.name(node)))
+            .collect();
 
-                steps += 1;
-            } else {
-                return None;
-            }
+        nodes.sort_by_key(|&node| self.name(node));
+        nodes
+    }
+
+    // Flags every node that matches the given predicate so predicates only need to be evaluated
+    // once per node instead of once per step
+    fn flag_nodes(&self, predicate: impl Fn(&str) -> bool) -> Vec<bool> {
+        self.names.iter().map(|name| predicate(name)).collect()
+    }
+
+    // Finds the node reached by taking the step at the given index in the direction list
+    fn next(&self, node: u32, direction_index: usize) -> u32 {
+        let (left, right) = self.edges[node as usize];
+
+        match self.directions[direction_index] {
+            Direction::Left => left,
+            Direction::Right => right,
         }
+    }
 
-        Some(steps)
+    fn human_steps_to_exit(&self) -> Option<u64> {
+        self.steps_between(|name| name == "AAA", |name| name == "ZZZ")
+            .ok()
     }
 
-    fn steps_to_first_ghost_exit(&self, start: &str) -> Result<u64, Box<dyn Error>> {
+    fn ghost_steps_to_exit(&self) -> Result<u64, Box<dyn Error>> {
+        self.steps_between(is_ghost_start, is_ghost_exit)
+    }
+
+    // Finds the number of steps until ghosts starting on every node that matches the start
+    // predicate are all on nodes that match the end predicate at the same time
+    fn steps_between(
+        &self,
+        start_predicate: impl Fn(&str) -> bool,
+        end_predicate: impl Fn(&str) -> bool,
+    ) -> Result<u64, Box<dyn Error>> {
+        self.cycle_analysis(start_predicate, end_predicate)
+            .ghost_steps_to_exit()
+    }
+
+    // Walks from the given start node until the ghost is in the same place at the same point in the
+    // direction list as it was at some earlier step, at which point it's doomed to repeat itself
+    fn ghost_cycle(&self, start: u32, exits: &[bool]) -> GhostCycle {
+        // Keyed by node index * direction count + direction index; a map, rather than a flat list
+        // of every possible state, since ghosts typically only visit a small fraction of them
+        let mut first_visits: HashMap<usize, u64> = HashMap::new();
+
+        let mut exit_offsets = Vec::new();
         let mut position = start;
         let mut steps = 0;
-        let mut directions = self.directions.iter().cycle();
 
         loop {
-            if let Some(destinations) = self.nodes.get(position) {
-                position = match directions.next().unwrap() {
-                    Direction::Left => &destinations.0,
-                    Direction::Right => &destinations.1,
+            let direction_index = steps as usize % self.directions.len();
+            let state = position as usize * self.directions.len() + direction_index;
+
+            if let Some(&cycle_start) = first_visits.get(&state) {
+                return GhostCycle {
+                    start: String::from(self.name(start)),
+                    cycle_start,
+                    cycle_length: steps - cycle_start,
+                    exit_offsets,
                 };
+            }
 
-                steps += 1;
-            } else {
-                return Err("Destination node not found".into());
+            first_visits.insert(state, steps);
+
+            if exits[position as usize] {
+                exit_offsets.push(steps);
             }
 
-            if position.ends_with('Z') {
-                break Ok(steps);
+            position = self.next(position, direction_index);
+            steps += 1;
+        }
+    }
+
+    // Finds the cycle for a ghost starting on each node that matches the start predicate (sorted
+    // by start node), treating nodes that match the end predicate as exits
+    fn cycle_analysis(
+        &self,
+        start_predicate: impl Fn(&str) -> bool,
+        end_predicate: impl Fn(&str) -> bool,
+    ) -> CycleAnalysis {
+        let exits = self.flag_nodes(end_predicate);
+
+        let cycles = self
+            .matching_nodes(start_predicate)
+            .into_iter()
+            .map(|start| self.ghost_cycle(start, &exits))
+            .collect();
+
+        CycleAnalysis { cycles }
+    }
+
+    // Finds the number of steps until all ghosts are on exits at the same time by actually moving
+    // all of the ghosts, which is slow, but doesn't depend on any clever analysis. Ghosts move in
+    // parallel in chunks of steps, and then we look for a step in the chunk where every ghost was
+    // on an exit. Returns `None` if the ghosts don't all reach exits within `max_steps` steps.
+    fn steps_between_simulated(
+        &self,
+        start_predicate: impl Fn(&str) -> bool,
+        end_predicate: impl Fn(&str) -> bool,
+        max_steps: u64,
+    ) -> Option<u64> {
+        let exits = self.flag_nodes(end_predicate);
+        let mut positions = self.matching_nodes(start_predicate);
+        let mut steps = 0;
+
+        // With no ghosts, every ghost is trivially on an exit after every step, which isn't a
+        // meaningful answer
+        if positions.is_empty() {
+            return None;
+        }
+
+        while steps < max_steps {
+            let chunk_size = SIMULATION_CHUNK_SIZE.min(max_steps - steps);
+
+            let walks: Vec<(u32, Vec<bool>)> = positions
+                .par_iter()
+                .map(|&position| self.walk(position, steps, chunk_size, &exits))
+                .collect();
+
+            if let Some(i) =
+                (0..chunk_size as usize).find(|&i| walks.iter().all(|(_, exits)| exits[i]))
+            {
+                return Some(steps + i as u64 + 1);
             }
+
+            positions = walks.into_iter().map(|(position, _)| position).collect();
+            steps += chunk_size;
+        }
+
+        None
+    }
+
+    // Takes the given number of steps starting from the given position (and having already taken
+    // `steps_taken` steps), returning the final position and whether the ghost was on an exit
+    // after each step
+    fn walk(&self, start: u32, steps_taken: u64, steps: u64, exits: &[bool]) -> (u32, Vec<bool>) {
+        let mut position = start;
+        let mut on_exit = Vec::with_capacity(steps as usize);
+
+        for step in steps_taken..steps_taken + steps {
+            position = self.next(position, (step % self.directions.len() as u64) as usize);
+
+            on_exit.push(exits[position as usize]);
         }
+
+        (position, on_exit)
     }
+}
 
+fn is_ghost_start(name: &str) -> bool {
+    name.ends_with('A')
+}
+
+fn is_ghost_exit(name: &str) -> bool {
+    name.ends_with('Z')
+}
+
+// Renders a network map as a Graphviz DOT graph with start nodes and exit nodes highlighted
+struct NetworkDiagram<'a>(&'a NetworkMap);
+
+impl NetworkDiagram<'_> {
+    const START_COLOR: &'static str = "palegreen";
+    const EXIT_COLOR: &'static str = "lightpink";
+}
+
+impl Display for NetworkDiagram<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph network {{")?;
+        writeln!(f, "    node [fontname=\"monospace\"];")?;
+        writeln!(f)?;
+
+        let mut nodes: Vec<(&str, (&str, &str))> = self
+            .0
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(node, &(left, right))| {
+                (
+                    self.0.name(node as u32),
+                    (self.0.name(left), self.0.name(right)),
+                )
+            })
+            .collect();
+
+        nodes.sort();
+
+        for (node, _) in &nodes {
+            if is_ghost_start(node) {
+                writeln!(
+                    f,
+                    "    \"{}\" [style=filled, fillcolor={}];",
+                    node,
+                    Self::START_COLOR
+                )?;
+            } else if is_ghost_exit(node) {
+                writeln!(
+                    f,
+                    "    \"{}\" [style=filled, fillcolor={}];",
+                    node,
+                    Self::EXIT_COLOR
+                )?;
+            }
+        }
+
+        writeln!(f)?;
+
+        for (node, (left, right)) in &nodes {
+            if left == right {
+                writeln!(f, "    \"{}\" -> \"{}\" [label=\"L/R\"];", node, left)?;
+            } else {
+                writeln!(f, "    \"{}\" -> \"{}\" [label=\"L\"];", node, left)?;
+                writeln!(f, "    \"{}\" -> \"{}\" [label=\"R\"];", node, right)?;
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+// The cycles of all of the ghosts in a network; this is everything we need to know to figure out
+// when all of the ghosts will be on exits at the same time
+struct CycleAnalysis {
+    cycles: Vec<GhostCycle>,
+}
+
+impl CycleAnalysis {
     fn ghost_steps_to_exit(&self) -> Result<u64, Box<dyn Error>> {
+        let cycles = &self.cycles;
+
         // Weeeell this is frustrating. This problem's solution appears to depend on noticing that
         // the inputs have been specially crafted such that each "ghost" travels in a long cycle,
-        // and each each contains exactly one exit (i.e. there's no bouncing between exits). That
-        // means the exit time is the LCM of all of the cycle lengths.
-        //
-        // To make THAT easier, it turns out that all of the cycle lengths all have exactly two
-        // prime factors, and one of those prime factors is common to all of the cycle lengths.
-        let cycle_lengths: Vec<u64> = self
-            .nodes
-            .keys()
-            .filter(|position| position.ends_with('A'))
-            .map(|position| self.steps_to_first_ghost_exit(position))
-            .collect::<Result<_, _>>()?;
-
-        cycle_lengths
+        // and each each contains exactly one exit (i.e. there's no bouncing between exits) that
+        // the ghost reaches exactly one cycle length after it starts. That means the exit time is
+        // the LCM of all of the cycle lengths. More generally, the LCM works whenever each ghost
+        // reaches exits at exact multiples of some period.
+        if let Some(exit_periods) = cycles
+            .iter()
+            .map(GhostCycle::exit_period)
+            .collect::<Option<Vec<u64>>>()
+        {
+            return exit_periods
+                .into_iter()
+                .reduce(least_common_multiple)
+                .ok_or("Could not calculate cycle lengths".into());
+        }
+
+        // Otherwise, we need to do things the hard way. Before every ghost has entered its cycle,
+        // ghosts may pass through exits that they'll never see again, so just check those steps
+        // directly.
+        let all_cycles_start = cycles
+            .iter()
+            .map(|cycle| cycle.cycle_start)
+            .max()
+            .ok_or("No ghosts found")?
+            .max(1);
+
+        if let Some(steps) =
+            (1..all_cycles_start).find(|&steps| cycles.iter().all(|cycle| cycle.is_exit(steps)))
+        {
+            return Ok(steps);
+        }
+
+        // Once every ghost is in its cycle, each exit in each ghost's cycle gives us a congruence
+        // (i.e. "the number of steps is equal to some offset modulo the cycle length"), and we can
+        // combine those with the Chinese Remainder Theorem. Ghosts may have more than one exit in
+        // their cycles, so we need to consider every combination of exits.
+        let solutions = cycles.iter().fold(vec![(0, 1)], |solutions, cycle| {
+            solutions
+                .iter()
+                .flat_map(|&solution| {
+                    cycle.cyclic_exit_offsets().filter_map(move |offset| {
+                        combine_congruences(
+                            solution,
+                            (offset % cycle.cycle_length, cycle.cycle_length),
+                        )
+                    })
+                })
+                .collect()
+        });
+
+        solutions
+            .iter()
+            .map(|&(residue, modulus)| {
+                // Find the first step at or after the point where all ghosts are in their cycles
+                // that satisfies all of the congruences
+                let behind = (modulus + residue - all_cycles_start % modulus) % modulus;
+                all_cycles_start + behind
+            })
+            .min()
+            .ok_or("Ghosts never reach exits at the same time".into())
+    }
+}
+
+impl Display for CycleAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let start_width = self
+            .cycles
+            .iter()
+            .map(|cycle| cycle.start.len())
+            .chain([5])
+            .max()
+            .unwrap();
+
+        writeln!(
+            f,
+            "{:<start_width$}  {:>11}  {:>12}  Exit offsets",
+            "Start", "Cycle start", "Cycle length"
+        )?;
+
+        for cycle in &self.cycles {
+            writeln!(
+                f,
+                "{:<start_width$}  {:>11}  {:>12}  {}",
+                cycle.start,
+                cycle.cycle_start,
+                cycle.cycle_length,
+                cycle
+                    .exit_offsets
+                    .iter()
+                    .map(|offset| offset.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+struct GhostCycle {
+    start: String,
+
+    // The number of steps before the ghost enters its cycle
+    cycle_start: u64,
+
+    cycle_length: u64,
+
+    // The steps (before the end of the first trip around the cycle) at which the ghost is on an
+    // exit; offsets before the cycle start are never repeated. Note that this may include step 0
+    // if the ghost starts on an exit, since it may come back to its start later.
+    exit_offsets: Vec<u64>,
+}
+
+impl GhostCycle {
+    // If this ghost reaches an exit after some number of steps and then again after exactly every
+    // multiple of that number of steps (and never in between), returns that number of steps. This
+    // is the property that makes the LCM shortcut work. Cycles are detected on (node, direction)
+    // pairs, so a cycle can contain the same exit several times if the underlying loop through the
+    // network is shorter than the list of directions.
+    fn exit_period(&self) -> Option<u64> {
+        let end = self.cycle_start + self.cycle_length;
+        let period = (1..=end).find(|&steps| self.is_exit(steps))?;
+
+        if self.cycle_length.is_multiple_of(period)
+            && (1..=end).all(|steps| self.is_exit(steps) == steps.is_multiple_of(period))
+        {
+            Some(period)
+        } else {
+            None
+        }
+    }
+
+    // Explains the ways in which this cycle breaks the assumptions behind the LCM shortcut
+    fn lcm_assumption_problems(&self) -> Vec<String> {
+        if self.exit_period().is_some() {
+            return Vec::new();
+        }
+
+        let mut problems = Vec::new();
+
+        let transient_exits = self
+            .exit_offsets
+            .iter()
+            .filter(|&&offset| offset > 0 && offset < self.cycle_start)
+            .count();
+
+        if transient_exits > 0 {
+            problems.push(format!(
+                "passes through {} exit(s) before entering its cycle",
+                transient_exits
+            ));
+        }
+
+        // Exits before the cycle are enough to break the shortcut on their own, so only look for
+        // problems within the cycle if there weren't any
+        match self.cyclic_exit_offsets().collect::<Vec<u64>>().as_slice() {
+            [] => problems.push(String::from("never reaches an exit once in its cycle")),
+            _ if transient_exits > 0 => {}
+            [offset] => problems.push(format!(
+                "first reaches its exit after {} steps, but its cycle is {} steps long",
+                offset, self.cycle_length
+            )),
+            offsets => problems.push(format!(
+                "reaches {} exits in each trip around its cycle, but not at evenly-spaced steps",
+                offsets.len()
+            )),
+        }
+
+        problems
+    }
+
+    fn cyclic_exit_offsets(&self) -> impl Iterator<Item = u64> + '_ {
+        self.exit_offsets
             .iter()
             .copied()
-            .reduce(least_common_multiple)
-            .ok_or("Could not calculate cycle lengths".into())
+            .filter(|&offset| offset >= self.cycle_start)
+    }
+
+    fn is_exit(&self, steps: u64) -> bool {
+        let steps = if steps < self.cycle_start {
+            steps
+        } else {
+            self.cycle_start + (steps - self.cycle_start) % self.cycle_length
+        };
+
+        self.exit_offsets.contains(&steps)
     }
 }
 
@@ -109,29 +594,81 @@ impl FromStr for NetworkMap {
     type Err = Box<dyn Error>;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        if let [directions, nodes] = string.split("\n\n").collect::<Vec<&str>>().as_slice() {
+        if let Some((directions, nodes)) = string.split_once("\n\n") {
             let directions = directions
+                .trim()
                 .chars()
                 .map(Direction::try_from)
                 .collect::<Result<_, _>>()?;
 
-            let nodes = nodes
+            let nodes: Vec<(String, (String, String))> = nodes
                 .lines()
-                .map(|line| {
-                    (
-                        String::from(&line[0..3]),
-                        (String::from(&line[7..10]), String::from(&line[12..15])),
-                    )
-                })
-                .collect();
+                .filter(|line| !line.trim().is_empty())
+                .map(parse_node)
+                .collect::<Result<_, _>>()?;
+
+            let mut names = Vec::new();
+            let mut indices = HashMap::new();
+
+            for (node, _) in &nodes {
+                indices.entry(node.clone()).or_insert_with(|| {
+                    names.push(node.clone());
+                    names.len() as u32 - 1
+                });
+            }
 
-            Ok(NetworkMap { directions, nodes })
+            let mut edges = vec![(0, 0); names.len()];
+
+            for (node, (left, right)) in &nodes {
+                let [left, right] = [left, right].map(|destination| {
+                    indices
+                        .get(destination)
+                        .copied()
+                        .ok_or_else(|| format!("Destination node {} is not defined", destination))
+                });
+
+                edges[indices[node] as usize] = (left?, right?);
+            }
+
+            Ok(NetworkMap {
+                directions,
+                names,
+                edges,
+            })
         } else {
             Err("Could not parse directions and node map".into())
         }
     }
 }
 
+// Parses a line like "AAA = (BBB, CCC)" into a node name and its left and right destinations
+fn parse_node(line: &str) -> Result<(String, (String, String)), Box<dyn Error>> {
+    let (node, destinations) = line
+        .split_once('=')
+        .ok_or_else(|| format!("Could not parse node: {}", line))?;
+
+    let (left, right) = destinations
+        .trim()
+        .strip_prefix('(')
+        .and_then(|destinations| destinations.strip_suffix(')'))
+        .and_then(|destinations| destinations.split_once(','))
+        .ok_or_else(|| format!("Could not parse destinations for node: {}", line))?;
+
+    let [node, left, right] = [node, left, right].map(str::trim);
+
+    if let Some(name) = [node, left, right]
+        .iter()
+        .find(|name| name.is_empty() || !name.chars().all(char::is_alphanumeric))
+    {
+        return Err(format!("Invalid node name \"{}\" in line: {}", name, line).into());
+    }
+
+    Ok((
+        String::from(node),
+        (String::from(left), String::from(right)),
+    ))
+}
+
 enum Direction {
     Left,
     Right,
@@ -167,11 +704,54 @@ fn least_common_multiple(a: u64, b: u64) -> u64 {
     (a * b) / greatest_common_divisor(a, b)
 }
 
+// Finds x and y such that ax + by = gcd(a, b)
+fn extended_greatest_common_divisor(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (gcd, x, y) = extended_greatest_common_divisor(b, a % b);
+        (gcd, y, x - (a / b) * y)
+    }
+}
+
+// Combines two congruences, each expressed as (residue, modulus), into a single congruence that
+// satisfies both via the Chinese Remainder Theorem; the moduli need not be coprime, but if they
+// aren't, the congruences may have no common solution
+fn combine_congruences(a: (u64, u64), b: (u64, u64)) -> Option<(u64, u64)> {
+    let (a_residue, a_modulus) = (a.0 as i128, a.1 as i128);
+    let (b_residue, b_modulus) = (b.0 as i128, b.1 as i128);
+
+    let (gcd, x, _) = extended_greatest_common_divisor(a_modulus, b_modulus);
+
+    if (b_residue - a_residue) % gcd != 0 {
+        return None;
+    }
+
+    let modulus = a_modulus / gcd * b_modulus;
+    let residue = (a_residue + (b_residue - a_residue) / gcd * x % (b_modulus / gcd) * a_modulus)
+        .rem_euclid(modulus);
+
+    Some((residue as u64, modulus as u64))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use indoc::indoc;
 
+    const TEST_GHOST_NETWORK: &str = indoc! {"
+        LR
+
+        11A = (11B, XXX)
+        11B = (XXX, 11Z)
+        11Z = (11B, XXX)
+        22A = (22B, XXX)
+        22B = (22C, 22C)
+        22C = (22Z, 22Z)
+        22Z = (22B, 22B)
+        XXX = (XXX, XXX)
+    "};
+
     #[test]
     fn test_human_steps_to_exit() {
         {
@@ -207,21 +787,303 @@ mod test {
 
     #[test]
     fn test_ghost_steps_to_exit() {
+        let node_map = NetworkMap::from_str(TEST_GHOST_NETWORK).unwrap();
+
+        assert_eq!(6, node_map.ghost_steps_to_exit().unwrap());
+
+        assert!(node_map
+            .cycle_analysis(is_ghost_start, is_ghost_exit)
+            .cycles
+            .iter()
+            .all(|cycle| cycle.lcm_assumption_problems().is_empty()));
+
+        // 22A loops through three nodes, but the direction list has two entries, so the detected
+        // cycle is six steps long and contains two exits
+        let cycle = node_map.ghost_cycle(
+            node_map.matching_nodes(|name| name == "22A")[0],
+            &node_map.flag_nodes(is_ghost_exit),
+        );
+        assert_eq!(6, cycle.cycle_length);
+        assert_eq!(vec![3, 6], cycle.exit_offsets);
+        assert_eq!(Some(3), cycle.exit_period());
+    }
+
+    #[test]
+    fn test_ghost_steps_to_exit_general() {
+        // The first ghost reaches an exit after one step and then every three steps after that,
+        // and the second reaches an exit every two steps, so the LCM of the steps to the first
+        // exit is wrong
+        let node_map = NetworkMap::from_str(indoc! {"
+                L
+
+                11A = (11Z, 11Z)
+                11Z = (11B, 11B)
+                11B = (11C, 11C)
+                11C = (11Z, 11Z)
+                22A = (22B, 22B)
+                22B = (22Z, 22Z)
+                22Z = (22B, 22B)
+            "})
+        .unwrap();
+
+        let cycle = node_map.ghost_cycle(
+            node_map.matching_nodes(|name| name == "11A")[0],
+            &node_map.flag_nodes(is_ghost_exit),
+        );
+        assert_eq!(1, cycle.cycle_start);
+        assert_eq!(3, cycle.cycle_length);
+        assert_eq!(vec![1], cycle.exit_offsets);
+
+        assert_eq!(4, node_map.ghost_steps_to_exit().unwrap());
+        assert_eq!(1, cycle.lcm_assumption_problems().len());
+
+        // A ghost that starts on an exit in its cycle comes back to that exit later
+        let cycle = node_map.ghost_cycle(
+            node_map.matching_nodes(|name| name == "11Z")[0],
+            &node_map.flag_nodes(is_ghost_exit),
+        );
+        assert_eq!(0, cycle.cycle_start);
+        assert_eq!(vec![0], cycle.exit_offsets);
+        assert!(cycle.is_exit(3));
+        assert!(!cycle.is_exit(4));
+
+        // The second ghost passes through an exit before falling into a cycle with no exits
         let node_map = NetworkMap::from_str(indoc! {"
                 LR
 
-                11A = (11B, XXX)
-                11B = (XXX, 11Z)
-                11Z = (11B, XXX)
-                22A = (22B, XXX)
+                11A = (11Z, 11Z)
+                11Z = (11B, 11B)
+                11B = (11Z, 11Z)
+                22A = (22Z, 22Z)
+                22Z = (22B, 22B)
+                22B = (22B, 22B)
+            "})
+        .unwrap();
+
+        assert_eq!(1, node_map.ghost_steps_to_exit().unwrap());
+
+        // Exits that never line up
+        let node_map = NetworkMap::from_str(indoc! {"
+                L
+
+                11A = (11B, 11B)
+                11B = (11Z, 11Z)
+                11Z = (11B, 11B)
+                22A = (22Z, 22Z)
+                22Z = (22B, 22B)
+                22B = (22Z, 22Z)
+            "})
+        .unwrap();
+
+        assert!(node_map.ghost_steps_to_exit().is_err());
+    }
+
+    #[test]
+    fn test_combine_congruences() {
+        assert_eq!(
+            Some((23, 105)),
+            combine_congruences((2, 3), (3, 5)).and_then(|c| combine_congruences(c, (2, 7)))
+        );
+        assert_eq!(Some((10, 12)), combine_congruences((2, 4), (4, 6)));
+        assert_eq!(None, combine_congruences((1, 4), (2, 6)));
+        assert_eq!(Some((0, 6)), combine_congruences((0, 2), (0, 3)));
+    }
+
+    #[test]
+    fn test_ghost_steps_to_exit_simulated() {
+        let node_map = NetworkMap::from_str(TEST_GHOST_NETWORK).unwrap();
+
+        assert_eq!(
+            Some(6),
+            node_map.steps_between_simulated(is_ghost_start, is_ghost_exit, 100)
+        );
+        assert_eq!(
+            Some(6),
+            node_map.steps_between_simulated(is_ghost_start, is_ghost_exit, 6)
+        );
+        assert_eq!(
+            None,
+            node_map.steps_between_simulated(is_ghost_start, is_ghost_exit, 5)
+        );
+        assert_eq!(
+            None,
+            node_map.steps_between_simulated(|name| name.ends_with('Q'), is_ghost_exit, 100)
+        );
+
+        // Make sure we find answers that span chunks
+        let node_map = NetworkMap::from_str(indoc! {"
+                L
+
+                11A = (11B, 11B)
+                11B = (11Z, 11Z)
+                11Z = (11B, 11B)
+                22A = (22B, 22B)
                 22B = (22C, 22C)
                 22C = (22Z, 22Z)
                 22Z = (22B, 22B)
-                XXX = (XXX, XXX)
             "})
         .unwrap();
 
-        assert_eq!(6, node_map.ghost_steps_to_exit().unwrap());
+        assert_eq!(
+            Some(node_map.ghost_steps_to_exit().unwrap()),
+            node_map.steps_between_simulated(
+                is_ghost_start,
+                is_ghost_exit,
+                SIMULATION_CHUNK_SIZE * 3
+            )
+        );
+    }
+
+    #[test]
+    fn test_cycle_analysis() {
+        let node_map = NetworkMap::from_str(TEST_GHOST_NETWORK).unwrap();
+
+        let cycle_analysis = node_map.cycle_analysis(is_ghost_start, is_ghost_exit);
+
+        assert_eq!(
+            vec![("11A", 1, 2, vec![2]), ("22A", 1, 6, vec![3, 6]),],
+            cycle_analysis
+                .cycles
+                .iter()
+                .map(|cycle| (
+                    cycle.start.as_str(),
+                    cycle.cycle_start,
+                    cycle.cycle_length,
+                    cycle.exit_offsets.clone()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(6, cycle_analysis.ghost_steps_to_exit().unwrap());
+
+        assert_eq!(
+            indoc! {"
+                Start  Cycle start  Cycle length  Exit offsets
+                11A              1             2  2
+                22A              1             6  3, 6
+            "},
+            cycle_analysis.to_string()
+        );
+    }
+
+    #[test]
+    fn test_steps_between() {
+        let node_map = NetworkMap::from_str(TEST_GHOST_NETWORK).unwrap();
+
+        assert_eq!(
+            6,
+            node_map
+                .steps_between(is_ghost_start, is_ghost_exit)
+                .unwrap()
+        );
+
+        // 11Z and 22B both reach nodes ending in Z after two steps
+        assert_eq!(
+            2,
+            node_map
+                .steps_between(
+                    |name| name == "11Z" || name == "22B",
+                    |name| name.ends_with('Z')
+                )
+                .unwrap()
+        );
+        assert_eq!(
+            1,
+            node_map
+                .steps_between(|name| name == "22B", |name| name.ends_with('C'))
+                .unwrap()
+        );
+
+        // 11B falls into XXX forever
+        assert!(node_map
+            .steps_between(|name| name.ends_with('B'), |name| name.ends_with('Z'))
+            .is_err());
+
+        // A predicate that matches no start nodes can't produce an answer
+        assert!(node_map
+            .steps_between(|name| name.ends_with('Q'), |name| name.ends_with('Z'))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_node() {
+        assert_eq!(
+            (
+                String::from("AAA"),
+                (String::from("BBB"), String::from("CCC"))
+            ),
+            parse_node("AAA = (BBB, CCC)").unwrap()
+        );
+
+        assert_eq!(
+            (
+                String::from("start1A"),
+                (String::from("B"), String::from("exit22Z"))
+            ),
+            parse_node("  start1A=( B ,exit22Z )  ").unwrap()
+        );
+
+        assert!(parse_node("AAA (BBB, CCC)").is_err());
+        assert!(parse_node("AAA = BBB, CCC").is_err());
+        assert!(parse_node("AAA = (BBB CCC)").is_err());
+        assert!(parse_node(" = (BBB, CCC)").is_err());
+        assert!(parse_node("A-A = (BBB, CCC)").is_err());
+    }
+
+    #[test]
+    fn test_parse_arbitrary_node_names() {
+        let node_map = NetworkMap::from_str(indoc! {"
+                LR
+
+                A = (LongB, X)
+                LongB = (X, ZZZ)
+                ZZZ = (ZZZ, ZZZ)
+                X = (X, X)
+
+                AAA = (LongB, LongB)
+            "})
+        .unwrap();
+
+        assert_eq!(Some(2), node_map.human_steps_to_exit());
+        assert_eq!(2, node_map.ghost_steps_to_exit().unwrap());
+
+        // Every destination needs a definition of its own
+        assert!(NetworkMap::from_str(indoc! {"
+                LR
+
+                AAA = (BBB, ZZZ)
+                ZZZ = (ZZZ, ZZZ)
+            "})
+        .is_err());
+    }
+
+    #[test]
+    fn test_network_diagram() {
+        let node_map = NetworkMap::from_str(indoc! {"
+                LLR
+
+                AAA = (BBB, BBB)
+                BBB = (AAA, ZZZ)
+                ZZZ = (ZZZ, ZZZ)
+            "})
+        .unwrap();
+
+        assert_eq!(
+            indoc! {r#"
+                digraph network {
+                    node [fontname="monospace"];
+
+                    "AAA" [style=filled, fillcolor=palegreen];
+                    "ZZZ" [style=filled, fillcolor=lightpink];
+
+                    "AAA" -> "BBB" [label="L/R"];
+                    "BBB" -> "AAA" [label="L"];
+                    "BBB" -> "ZZZ" [label="R"];
+                    "ZZZ" -> "ZZZ" [label="L/R"];
+                }
+            "#},
+            NetworkDiagram(&node_map).to_string()
+        );
     }
 
     #[test]
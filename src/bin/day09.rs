@@ -1,5 +1,6 @@
 use std::env;
 use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::str::FromStr;
@@ -14,72 +15,341 @@ fn main() -> Result<(), Box<dyn Error>> {
             .map(|line| Sequence::from_str(line.as_str()))
             .collect::<Result<Vec<_>, _>>()?;
 
-        println!(
-            "Sum of next values: {}",
-            sequences
-                .iter()
-                .map(|sequence| sequence.next())
-                .sum::<Result<i32, _>>()?
-        );
+        if args.iter().skip(2).any(|arg| arg == "--degrees") {
+            for (i, sequence) in sequences.iter().enumerate() {
+                match sequence.degree() {
+                    Ok(degree) => println!("Line {}: degree {}", i + 1, degree),
+                    Err(e) => println!("Line {}: {}", i + 1, e),
+                }
+            }
 
-        println!(
-            "Sum of previous values: {}",
-            sequences
-                .iter()
-                .map(|sequence| sequence.previous())
-                .sum::<Result<i32, _>>()?
-        );
+            println!();
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--pyramid") {
+            for sequence in &sequences {
+                println!("{}", sequence.difference_pyramid()?);
+            }
+        }
+
+        if let Some(n) = args
+            .iter()
+            .position(|arg| arg == "--extend")
+            .and_then(|i| args.get(i + 1))
+        {
+            let n: usize = n.parse()?;
+
+            for sequence in &sequences {
+                let extended: Vec<String> = sequence
+                    .previous_n(n)?
+                    .iter()
+                    .chain(sequence.values.iter())
+                    .chain(sequence.next_n(n)?.iter())
+                    .map(|value| value.to_string())
+                    .collect();
+
+                println!("{}", extended.join(" "));
+            }
+
+            println!();
+        }
+
+        let steps: usize = args
+            .iter()
+            .position(|arg| arg == "--steps")
+            .and_then(|i| args.get(i + 1))
+            .map(|steps| steps.parse())
+            .transpose()?
+            .unwrap_or(1);
+
+        if steps == 1 {
+            println!(
+                "Sum of next values: {}",
+                checked_sum(sequences.iter().map(|sequence| sequence.next()))?
+            );
+
+            println!(
+                "Sum of previous values: {}",
+                checked_sum(sequences.iter().map(|sequence| sequence.previous()))?
+            );
+        } else {
+            println!(
+                "Sum of values {} steps after each sequence: {}",
+                steps,
+                checked_sum(sequences.iter().map(|sequence| sequence.value_after(steps)))?
+            );
+
+            println!(
+                "Sum of values {} steps before each sequence: {}",
+                steps,
+                checked_sum(
+                    sequences
+                        .iter()
+                        .map(|sequence| sequence.value_before(steps))
+                )?
+            );
+        }
 
         Ok(())
     } else {
-        Err("Usage: day09 INPUT_FILE_PATH".into())
+        Err("Usage: day09 INPUT_FILE_PATH [--steps K] [--extend N] [--pyramid] [--degrees]".into())
     }
 }
 
+// Sequences can get steep quickly, so values are 64-bit integers by default, but can be widened to
+// 128-bit integers with the `day09-i128` feature
+#[cfg(not(feature = "day09-i128"))]
+type Value = i64;
+
+#[cfg(feature = "day09-i128")]
+type Value = i128;
+
 struct Sequence {
-    values: Vec<i32>,
+    values: Vec<Value>,
 }
 
 impl Sequence {
-    fn next(&self) -> Result<i32, Box<dyn Error>> {
-        Self::derive_next(&self.values)
+    fn next(&self) -> Result<Value, Box<dyn Error>> {
+        self.value_after(1)
     }
 
-    fn previous(&self) -> Result<i32, Box<dyn Error>> {
-        Self::derive_previous(&self.values)
+    fn previous(&self) -> Result<Value, Box<dyn Error>> {
+        self.value_before(1)
     }
 
-    fn derive(values: &[i32]) -> Result<Vec<i32>, Box<dyn Error>> {
-        let derivative: Vec<i32> = values
-            .windows(2)
-            .filter_map(|pair| if let [a, b] = pair { Some(b - a) } else { None })
-            .collect();
+    // Finds the value `steps` places after the last value in the sequence directly with Newton's
+    // backward difference formula: f(x + k) = sum over j of C(k + j - 1, j) * ∇^j f(x), where the
+    // j-th backward difference is the last value of the j-th level of differences
+    fn value_after(&self, steps: usize) -> Result<Value, Box<dyn Error>> {
+        let (_, last_differences) = self.edge_differences()?;
 
-        if derivative.is_empty() {
-            Err("Empty derived sequence".into())
-        } else {
-            Ok(derivative)
+        Self::newton_sum(&last_differences, steps, false)
+            .ok_or_else(|| "Overflow while extrapolating next value".into())
+    }
+
+    // Finds the value `steps` places before the first value in the sequence; this is the same as
+    // extrapolating forward, but with the forward differences at the start of the sequence and
+    // alternating signs: f(x - k) = sum over j of (-1)^j * C(k + j - 1, j) * Δ^j f(x)
+    fn value_before(&self, steps: usize) -> Result<Value, Box<dyn Error>> {
+        let (first_differences, _) = self.edge_differences()?;
+
+        Self::newton_sum(&first_differences, steps, true)
+            .ok_or_else(|| "Overflow while extrapolating previous value".into())
+    }
+
+    fn newton_sum(differences: &[Value], steps: usize, alternate: bool) -> Option<Value> {
+        let steps = Value::try_from(steps).ok()?;
+
+        let mut sum: Value = 0;
+        let mut coefficient: Value = 1;
+
+        for (j, difference) in differences.iter().enumerate() {
+            if j > 0 {
+                // C(k + j - 1, j) = C(k + j - 2, j - 1) * (k + j - 1) / j, and the division is
+                // always exact
+                let j = j as Value;
+                coefficient = coefficient.checked_mul(steps.checked_add(j - 1)?)? / j;
+            }
+
+            let term = coefficient.checked_mul(*difference)?;
+
+            sum = if alternate && j % 2 == 1 {
+                sum.checked_sub(term)?
+            } else {
+                sum.checked_add(term)?
+            };
+        }
+
+        Some(sum)
+    }
+
+    // Returns the first and last values of each level of differences (starting with the values
+    // themselves), stopping before the first level that's all zeros; differences are taken in
+    // place in a single buffer rather than allocating a new vector for each level
+    fn edge_differences(&self) -> Result<(Vec<Value>, Vec<Value>), Box<dyn Error>> {
+        let mut first_differences = Vec::new();
+        let mut last_differences = Vec::new();
+
+        let mut level = self.values.clone();
+        let mut len = level.len();
+
+        while !level[..len].iter().all(|&v| v == 0) {
+            first_differences.push(level[0]);
+            last_differences.push(level[len - 1]);
+
+            if len == 1 {
+                return Err(not_converged(first_differences.len() - 1));
+            }
+
+            for i in 0..len - 1 {
+                level[i] = level[i + 1]
+                    .checked_sub(level[i])
+                    .ok_or("Overflow while computing differences")?;
+            }
+
+            len -= 1;
+        }
+
+        Ok((first_differences, last_differences))
+    }
+
+    // Extrapolates the next `n` values in order
+    fn next_n(&self, n: usize) -> Result<Vec<Value>, Box<dyn Error>> {
+        // Everything below the bottom of the pyramid is zero, too
+        let mut extension = vec![0; n];
+
+        for level in self.difference_pyramid()?.converged_levels()?.iter().rev() {
+            let mut value = *level.last().unwrap();
+
+            extension = extension
+                .iter()
+                .map(|difference| {
+                    value = value.checked_add(*difference)?;
+                    Some(value)
+                })
+                .collect::<Option<_>>()
+                .ok_or("Overflow while extrapolating next value")?;
         }
+
+        Ok(extension)
     }
 
-    fn derive_next(values: &[i32]) -> Result<i32, Box<dyn Error>> {
-        if values.iter().all(|&v| v == 0) {
-            return Ok(0);
+    // Extrapolates the `n` values before the start of the sequence; like the values themselves, the
+    // returned values are in sequence order (i.e. the value immediately before the first value of
+    // the sequence is last)
+    fn previous_n(&self, n: usize) -> Result<Vec<Value>, Box<dyn Error>> {
+        // Working outward from the start of the sequence, so the nearest value comes first here
+        let mut extension = vec![0; n];
+
+        for level in self.difference_pyramid()?.converged_levels()?.iter().rev() {
+            let mut value = *level.first().unwrap();
+
+            extension = extension
+                .iter()
+                .map(|difference| {
+                    value = value.checked_sub(*difference)?;
+                    Some(value)
+                })
+                .collect::<Option<_>>()
+                .ok_or("Overflow while extrapolating previous value")?;
         }
 
-        let derivative = Self::derive(values)?;
+        extension.reverse();
+        Ok(extension)
+    }
+
+    fn difference_pyramid(&self) -> Result<DifferencePyramid, Box<dyn Error>> {
+        let mut levels = vec![self.values.clone()];
+
+        while !levels.last().unwrap().iter().all(|&v| v == 0) {
+            // A single non-zero value means we've run out of differences to take before reaching a
+            // level of all zeros, and so the sequence isn't a polynomial of any degree we can infer
+            // from the values we have; keep what we've got so far so callers can see why
+            if levels.last().unwrap().len() == 1 {
+                break;
+            }
 
-        Ok(values.last().unwrap() + Self::derive_next(&derivative)?)
+            levels.push(Self::derive(levels.last().unwrap())?);
+        }
+
+        Ok(DifferencePyramid { levels })
     }
 
-    fn derive_previous(values: &[i32]) -> Result<i32, Box<dyn Error>> {
-        if values.iter().all(|&v| v == 0) {
-            return Ok(0);
+    fn derive(values: &[Value]) -> Result<Vec<Value>, Box<dyn Error>> {
+        values
+            .windows(2)
+            .filter_map(|pair| {
+                if let [a, b] = pair {
+                    Some(b.checked_sub(*a))
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| "Overflow while computing differences".into())
+    }
+
+    // The degree of the polynomial that generates this sequence (treating a sequence of all zeros
+    // as a constant)
+    fn degree(&self) -> Result<usize, Box<dyn Error>> {
+        self.difference_pyramid()?.degree()
+    }
+}
+
+fn not_converged(levels: usize) -> Box<dyn Error> {
+    format!(
+        "Sequence did not converge after {} {}",
+        levels,
+        if levels == 1 { "level" } else { "levels" }
+    )
+    .into()
+}
+
+fn checked_sum(
+    mut values: impl Iterator<Item = Result<Value, Box<dyn Error>>>,
+) -> Result<Value, Box<dyn Error>> {
+    values.try_fold(0, |sum: Value, value| {
+        sum.checked_add(value?)
+            .ok_or_else(|| "Overflow while summing values".into())
+    })
+}
+
+// The sequence's values followed by each successive level of differences, ending with the first
+// level that's all zeros or, if the differences never converge, a level with a single value
+struct DifferencePyramid {
+    levels: Vec<Vec<Value>>,
+}
+
+impl DifferencePyramid {
+    fn converged(&self) -> bool {
+        self.levels.last().unwrap().iter().all(|&v| v == 0)
+    }
+
+    fn converged_levels(&self) -> Result<&[Vec<Value>], Box<dyn Error>> {
+        if self.converged() {
+            Ok(&self.levels)
+        } else {
+            Err(not_converged(self.levels.len() - 1))
+        }
+    }
+
+    // Every level but the last (all-zero) level is non-zero, and a polynomial of degree d has
+    // d + 1 non-zero levels
+    fn degree(&self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.converged_levels()?.len().saturating_sub(2))
+    }
+}
+
+impl Display for DifferencePyramid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let value_width = self
+            .levels
+            .iter()
+            .flatten()
+            .map(|value| value.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        // Keep cells an even width so each difference can sit exactly halfway between the two
+        // values above it
+        let cell_width = (value_width + 2).next_multiple_of(2);
+
+        for (depth, level) in self.levels.iter().enumerate() {
+            let mut line = " ".repeat(depth * cell_width / 2);
+
+            for (i, value) in level.iter().enumerate() {
+                let width = if i == 0 { value_width } else { cell_width };
+                line.push_str(&format!("{:>width$}", value));
+            }
+
+            writeln!(f, "{}", line)?;
         }
 
-        let derivative = Self::derive(values)?;
+        if !self.converged() {
+            writeln!(f, "{}", not_converged(self.levels.len() - 1))?;
+        }
 
-        Ok(values.first().unwrap() - Self::derive_previous(&derivative)?)
+        Ok(())
     }
 }
 
@@ -99,6 +369,7 @@ impl FromStr for Sequence {
 #[cfg(test)]
 mod test {
     use super::*;
+    use indoc::indoc;
 
     #[test]
     fn test_next() {
@@ -122,6 +393,252 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_wide_values() {
+        // These would all overflow a 32-bit integer
+        assert_eq!(
+            5_000_000_000,
+            Sequence::from_str("2000000000 3000000000 4000000000")
+                .unwrap()
+                .next()
+                .unwrap()
+        );
+
+        // Second differences of 1e9 at a large scale
+        assert_eq!(
+            16_000_000_000,
+            Sequence::from_str("1000000000 2000000000 4000000000 7000000000 11000000000")
+                .unwrap()
+                .next()
+                .unwrap()
+        );
+
+        assert_eq!(
+            -2_500_000_000,
+            Sequence::from_str("-1000000000 -1500000000 -2000000000")
+                .unwrap()
+                .next()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let sequence = Sequence::from_str(&format!(
+            "{} {} {}",
+            Value::MAX - 2,
+            Value::MAX - 1,
+            Value::MAX
+        ))
+        .unwrap();
+        assert!(sequence.next().is_err());
+        assert_eq!(Value::MAX - 3, sequence.previous().unwrap());
+
+        let sequence = Sequence::from_str(&format!(
+            "{} {} {}",
+            Value::MIN + 2,
+            Value::MIN + 1,
+            Value::MIN
+        ))
+        .unwrap();
+        assert!(sequence.next().is_err());
+
+        // Differences between extreme values overflow, too
+        assert!(
+            Sequence::from_str(&format!("{} {} {}", Value::MIN, Value::MAX, Value::MIN))
+                .unwrap()
+                .next()
+                .is_err()
+        );
+
+        assert!(checked_sum([Ok(Value::MAX), Ok(1)].into_iter()).is_err());
+        assert_eq!(3, checked_sum([Ok(1), Ok(2)].into_iter()).unwrap());
+    }
+
+    #[test]
+    fn test_next_n() {
+        let sequence = Sequence::from_str("10 13 16 21 30 45").unwrap();
+
+        assert_eq!(vec![68, 101, 146, 205], sequence.next_n(4).unwrap());
+        assert_eq!(vec![-19, -4, 5], sequence.previous_n(3).unwrap());
+        assert!(sequence.next_n(0).unwrap().is_empty());
+
+        let sequence = Sequence::from_str("1 3 6 10 15 21").unwrap();
+
+        assert_eq!(vec![28, 36, 45], sequence.next_n(3).unwrap());
+        assert_eq!(vec![1, 0, 0], sequence.previous_n(3).unwrap());
+
+        // Extending past the largest representable value overflows
+        let sequence = Sequence::from_str(&format!(
+            "{} {} {}",
+            Value::MAX - 20,
+            Value::MAX - 10,
+            Value::MAX
+        ))
+        .unwrap();
+
+        assert!(sequence.next_n(1).is_err());
+        assert_eq!(
+            vec![Value::MAX - 40, Value::MAX - 30],
+            sequence.previous_n(2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_value_after() {
+        let sequence = Sequence::from_str("10 13 16 21 30 45").unwrap();
+        let next_values = sequence.next_n(50).unwrap();
+        let previous_values = sequence.previous_n(50).unwrap();
+
+        for steps in 1..=50 {
+            assert_eq!(next_values[steps - 1], sequence.value_after(steps).unwrap());
+            assert_eq!(
+                previous_values[50 - steps],
+                sequence.value_before(steps).unwrap()
+            );
+        }
+
+        // Zero steps in either direction leads back to the ends of the sequence
+        assert_eq!(45, sequence.value_after(0).unwrap());
+        assert_eq!(10, sequence.value_before(0).unwrap());
+
+        // Triangular numbers; the value at index i is (i + 1)(i + 2) / 2
+        let sequence = Sequence::from_str("1 3 6 10 15 21").unwrap();
+        assert_eq!(
+            500_000_006_500_000_021,
+            sequence.value_after(1_000_000_000).unwrap()
+        );
+        assert_eq!(
+            499_999_998_500_000_001,
+            sequence.value_before(1_000_000_000).unwrap()
+        );
+
+        assert!(sequence.value_after(usize::MAX).is_err());
+        assert!(Sequence::from_str("0 1 2 4")
+            .unwrap()
+            .value_after(1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_difference_pyramid() {
+        let pyramid = Sequence::from_str("10 13 16 21 30 45")
+            .unwrap()
+            .difference_pyramid()
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                vec![10, 13, 16, 21, 30, 45],
+                vec![3, 3, 5, 9, 15],
+                vec![0, 2, 4, 6],
+                vec![2, 2, 2],
+                vec![0, 0],
+            ],
+            pyramid.levels
+        );
+
+        assert_eq!(
+            indoc! {"
+                10  13  16  21  30  45
+                   3   3   5   9  15
+                     0   2   4   6
+                       2   2   2
+                         0   0
+            "},
+            pyramid.to_string()
+        );
+
+        let pyramid = Sequence::from_str("-100 0 100")
+            .unwrap()
+            .difference_pyramid()
+            .unwrap();
+
+        // Cells widen to fit the widest value
+        assert_eq!(
+            indoc! {"
+                -100     0   100
+                    100   100
+                         0
+            "},
+            pyramid.to_string()
+        );
+
+        // Sequences that never converge still show as much of the pyramid as there is, along with
+        // a note about why extrapolation failed
+        let pyramid = Sequence::from_str("1 2 4 8 16")
+            .unwrap()
+            .difference_pyramid()
+            .unwrap();
+
+        assert!(!pyramid.converged());
+        assert!(pyramid.degree().is_err());
+
+        assert_eq!(
+            indoc! {"
+                 1   2   4   8  16
+                   1   2   4   8
+                     1   2   4
+                       1   2
+                         1
+                Sequence did not converge after 4 levels
+            "},
+            pyramid.to_string()
+        );
+    }
+
+    #[test]
+    fn test_degree() {
+        assert_eq!(
+            1,
+            Sequence::from_str("0 3 6 9 12 15")
+                .unwrap()
+                .degree()
+                .unwrap()
+        );
+        assert_eq!(
+            2,
+            Sequence::from_str("1 3 6 10 15 21")
+                .unwrap()
+                .degree()
+                .unwrap()
+        );
+        assert_eq!(
+            3,
+            Sequence::from_str("10 13 16 21 30 45")
+                .unwrap()
+                .degree()
+                .unwrap()
+        );
+        assert_eq!(0, Sequence::from_str("7 7 7").unwrap().degree().unwrap());
+        assert_eq!(0, Sequence::from_str("0 0 0").unwrap().degree().unwrap());
+
+        // Exactly enough values to pin down a degree-2 polynomial, plus one to confirm it
+        assert_eq!(2, Sequence::from_str("0 1 4 9").unwrap().degree().unwrap());
+    }
+
+    #[test]
+    fn test_not_converged() {
+        // Powers of two never run out of non-zero differences
+        let sequence = Sequence::from_str("1 2 4 8 16").unwrap();
+        let message = "Sequence did not converge after 4 levels";
+
+        assert_eq!(message, sequence.degree().unwrap_err().to_string());
+        assert_eq!(message, sequence.next().unwrap_err().to_string());
+        assert_eq!(message, sequence.previous().unwrap_err().to_string());
+        assert_eq!(message, sequence.next_n(3).unwrap_err().to_string());
+
+        // Not enough values to tell whether this is a line
+        assert_eq!(
+            "Sequence did not converge after 1 level",
+            Sequence::from_str("1 2")
+                .unwrap()
+                .next()
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
     #[test]
     fn test_previous() {
         assert_eq!(
@@ -154,7 +154,7 @@ impl PipeMap {
     }
 
     fn max_distance_from_start(&self) -> Result<usize, Box<dyn Error>> {
-        Ok((self.loop_length()? + 1) / 2)
+        Ok(self.loop_length()?.div_ceil(2))
     }
 }
 
@@ -178,7 +178,7 @@ impl FromStr for PipeMap {
             })
             .collect::<Result<_, _>>()?;
 
-        if pipes.len() % width != 0 {
+        if !pipes.len().is_multiple_of(width) {
             return Err("Inconsistent row width".into());
         }
 
@@ -163,7 +163,7 @@ impl SpringGroupState {
 
     fn is_plausible(&self) -> bool {
         if self.group_sizes.is_empty() {
-            if self.springs.iter().any(|&spring| spring == Spring::Damaged) {
+            if self.springs.contains(&Spring::Damaged) {
                 // We don't want to find any more damaged springs, but there are still some
                 // remaining; there are no possible arrangements to be had below this point.
                 false
@@ -227,10 +227,7 @@ impl SpringGroupState {
             .map(|(i, _)| i)
         {
             if start + group_size <= states.len() {
-                if !states[start..start + group_size]
-                    .iter()
-                    .any(|&state| state == Spring::Operational)
-                {
+                if !states[start..start + group_size].contains(&Spring::Operational) {
                     // We've found a group of damaged or potentially-damaged springs; can we
                     // "terminate" the group with the end of the states, an operational spring, or
                     // an unknown spring that we can assume is operational?
@@ -162,7 +162,7 @@ impl FromStr for MirrorField {
                 .map(Tile::try_from)
                 .collect::<Result<_, _>>()?;
 
-            if tiles.len() % width != 0 {
+            if !tiles.len().is_multiple_of(width) {
                 return Err("Non-rectangular field shape".into());
             }
 
@@ -166,7 +166,7 @@ impl FromStr for ParabolicDish {
                 .map(Tile::try_from)
                 .collect::<Result<_, _>>()?;
 
-            if tiles.len() % width == 0 {
+            if tiles.len().is_multiple_of(width) {
                 Ok(ParabolicDish { width, tiles })
             } else {
                 Err("Non-rectangular dish".into())
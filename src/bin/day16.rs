@@ -222,7 +222,7 @@ impl FromStr for BeamContraption {
                 .map(Tile::try_from)
                 .collect::<Result<_, _>>()?;
 
-            if tiles.len() % width == 0 {
+            if tiles.len().is_multiple_of(width) {
                 Ok(BeamContraption { width, tiles })
             } else {
                 Err("Non-rectangular beam cave".into())
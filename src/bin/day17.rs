@@ -116,11 +116,7 @@ impl CoolingMap {
 
         let positions: Box<dyn Iterator<Item = (usize, usize)>> = match start.direction {
             Direction::Horizontal => {
-                let min_x = if start_x < max_distance {
-                    0
-                } else {
-                    start_x - max_distance
-                };
+                let min_x = start_x.saturating_sub(max_distance);
 
                 let max_x = if start_x > self.width - 1 - max_distance {
                     self.width - 1
@@ -131,11 +127,7 @@ impl CoolingMap {
                 Box::new((min_x..=max_x).zip(iter::repeat(start_y)))
             }
             Direction::Vertical => {
-                let min_y = if start_y < max_distance {
-                    0
-                } else {
-                    start_y - max_distance
-                };
+                let min_y = start_y.saturating_sub(max_distance);
 
                 let max_y = if start_y > self.height() - 1 - max_distance {
                     self.height() - 1
@@ -214,7 +206,7 @@ impl FromStr for CoolingMap {
                 })
                 .collect::<Result<_, _>>()?;
 
-            if losses.len() % width == 0 {
+            if losses.len().is_multiple_of(width) {
                 Ok(CoolingMap { width, losses })
             } else {
                 Err("Non-rectangular map".into())
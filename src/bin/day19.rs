@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
-use std::ops::{Index, IndexMut};
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::path::Path;
 use std::str::FromStr;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -14,9 +17,53 @@ fn main() -> Result<(), Box<dyn Error>> {
             let mut part_sorter_string = String::new();
             File::open(path)?.read_to_string(&mut part_sorter_string)?;
 
-            PartSorter::from_str(part_sorter_string.as_str())?
+            match Path::new(path)
+                .extension()
+                .and_then(|extension| extension.to_str())
+            {
+                Some("json") => PartSorter::try_from(serde_json::from_str::<PartSorterDocument>(
+                    part_sorter_string.as_str(),
+                )?)?,
+                Some("toml") => PartSorter::try_from(toml::from_str::<PartSorterDocument>(
+                    part_sorter_string.as_str(),
+                )?)?,
+                _ => PartSorter::from_str(part_sorter_string.as_str())?,
+            }
         };
 
+        if let Some(format) = args
+            .iter()
+            .position(|arg| arg == "--export")
+            .and_then(|i| args.get(i + 1))
+        {
+            let document = part_sorter.to_document();
+
+            match format.as_str() {
+                "json" => println!("{}", serde_json::to_string_pretty(&document)?),
+                "toml" => println!("{}", toml::to_string_pretty(&document)?),
+                _ => return Err(format!("Unrecognized export format: {}", format).into()),
+            }
+
+            return Ok(());
+        }
+
+        let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) = part_sorter
+            .validate()
+            .into_iter()
+            .partition(Diagnostic::is_fatal);
+
+        for warning in &warnings {
+            eprintln!("Warning: {}", warning);
+        }
+
+        if !errors.is_empty() {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+
+            return Err(format!("Found {} problem(s) with workflows", errors.len()).into());
+        }
+
         println!(
             "Sum of ratings of accepted parts: {}",
             part_sorter.accepted_part_rating_sum()
@@ -24,16 +71,116 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         println!(
             "Distinct accepted part configurations: {}",
-            part_sorter.possible_accepted_parts()
+            part_sorter.possible_accepted_parts()?
         );
 
+        if args.iter().skip(2).any(|arg| arg == "--explain") {
+            for part in &part_sorter.parts {
+                let (accepted, trace) = part_sorter.process_part_with_trace(part);
+
+                println!();
+                println!(
+                    "{}: {}",
+                    part_sorter.components.format_part(part),
+                    if accepted { "accepted" } else { "rejected" }
+                );
+
+                for step in trace {
+                    println!(
+                        "  {}[{}]: {} -> {}",
+                        step.workflow_id,
+                        step.rule_index,
+                        part_sorter.components.format_condition(&step.condition),
+                        step.action
+                    );
+                }
+            }
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--stats") {
+            let statistics = part_sorter.statistics()?;
+
+            let mut workflow_ids: Vec<&String> = statistics.workflows.keys().collect();
+            workflow_ids.sort();
+
+            println!();
+
+            for workflow_id in workflow_ids {
+                let workflow_statistics = &statistics.workflows[workflow_id];
+
+                println!(
+                    "{}: {} part(s), volume {}",
+                    workflow_id, workflow_statistics.parts, workflow_statistics.volume
+                );
+
+                for (i, (rule, rule_statistics)) in part_sorter.workflows[workflow_id]
+                    .rules
+                    .iter()
+                    .zip(workflow_statistics.rules.iter())
+                    .enumerate()
+                {
+                    println!(
+                        "  [{}] {} -> {}: {} part(s), volume {}",
+                        i,
+                        part_sorter.components.format_condition(&rule.condition),
+                        rule.action,
+                        rule_statistics.parts,
+                        rule_statistics.volume
+                    );
+                }
+            }
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--regions-json") {
+            let accepted_regions: Vec<AcceptedRegionDocument> = part_sorter
+                .accepted_regions()?
+                .iter()
+                .map(|region| part_sorter.components.region_document(region))
+                .collect();
+
+            println!("{}", serde_json::to_string_pretty(&accepted_regions)?);
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--regions") {
+            let accepted_regions = part_sorter.accepted_regions()?;
+
+            println!();
+
+            for (i, region) in accepted_regions.iter().enumerate() {
+                println!(
+                    "Region {} (accepted by {}): {}",
+                    i,
+                    region.workflow_id,
+                    part_sorter.components.format_space(&region.space)
+                );
+            }
+
+            println!();
+
+            for part in &part_sorter.parts {
+                if let Some(i) = accepted_regions
+                    .iter()
+                    .position(|region| region.space.contains(part))
+                {
+                    println!(
+                        "{}: accepted (region {})",
+                        part_sorter.components.format_part(part),
+                        i
+                    );
+                } else {
+                    println!("{}: rejected", part_sorter.components.format_part(part));
+                }
+            }
+        }
+
         Ok(())
     } else {
-        Err("Usage: day19 INPUT_FILE_PATH".into())
+        Err("Usage: day19 INPUT_FILE_PATH [--explain] [--stats] [--regions] [--regions-json] [--export json|toml]".into())
     }
 }
 
 struct PartSorter {
+    components: Components,
     workflows: HashMap<String, Workflow>,
     parts: Vec<Part>,
 }
@@ -51,82 +198,440 @@ impl PartSorter {
     fn accepted_parts(&self) -> Vec<Part> {
         self.parts
             .iter()
-            .filter(|part| self.process_part(part, Self::INITIAL_RULE))
-            .copied()
+            .filter(|part| self.process_part(part, None))
+            .cloned()
             .collect()
     }
 
-    fn process_part(&self, part: &Part, workflow_id: &str) -> bool {
-        let workflow = self
-            .workflows
-            .get(workflow_id)
-            .expect("Referenced workflow must exist");
+    fn process_part(&self, part: &Part, mut statistics: Option<&mut Statistics>) -> bool {
+        let mut workflow_id = Self::INITIAL_RULE;
 
-        match workflow.apply(part) {
-            Action::Transfer(next_workflow_id) => {
-                self.process_part(part, next_workflow_id.as_str())
+        loop {
+            let workflow = self
+                .workflows
+                .get(workflow_id)
+                .expect("Referenced workflow must exist");
+
+            let (rule_index, rule) = workflow.matching_rule(part);
+
+            if let Some(statistics) = statistics.as_deref_mut() {
+                statistics.record_part(workflow, rule_index);
+            }
+
+            match &rule.action {
+                Action::Transfer(next_workflow_id) => workflow_id = next_workflow_id.as_str(),
+                Action::Accept => return true,
+                Action::Reject => return false,
             }
-            Action::Accept => true,
-            Action::Reject => false,
         }
     }
 
-    fn possible_accepted_parts(&self) -> u64 {
+    fn statistics(&self) -> Result<Statistics, Box<dyn Error>> {
+        let mut statistics = Statistics::default();
+
+        for part in &self.parts {
+            self.process_part(part, Some(&mut statistics));
+        }
+
+        self.explore_part_space(Some(&mut statistics))?;
+
+        Ok(statistics)
+    }
+
+    fn process_part_with_trace(&self, part: &Part) -> (bool, Vec<TraceStep>) {
+        let mut trace = Vec::new();
+        let mut workflow_id = String::from(Self::INITIAL_RULE);
+
+        loop {
+            let workflow = self
+                .workflows
+                .get(&workflow_id)
+                .expect("Referenced workflow must exist");
+
+            let (rule_index, rule) = workflow.matching_rule(part);
+
+            trace.push(TraceStep {
+                workflow_id,
+                rule_index,
+                condition: rule.condition.clone(),
+                action: rule.action.clone(),
+            });
+
+            match &rule.action {
+                Action::Transfer(next_workflow_id) => workflow_id = next_workflow_id.clone(),
+                Action::Accept => return (true, trace),
+                Action::Reject => return (false, trace),
+            }
+        }
+    }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let mut workflow_ids: Vec<&String> = self.workflows.keys().collect();
+        workflow_ids.sort();
+
+        if !self.workflows.contains_key(Self::INITIAL_RULE) {
+            diagnostics.push(Diagnostic::MissingInitialWorkflow);
+        }
+
+        for workflow_id in &workflow_ids {
+            let workflow = &self.workflows[*workflow_id];
+
+            match workflow
+                .rules
+                .iter()
+                .position(|rule| rule.condition == Condition::MatchAll)
+            {
+                Some(i) if i < workflow.rules.len() - 1 => {
+                    diagnostics.push(Diagnostic::UnreachableRules {
+                        workflow_id: workflow.id.clone(),
+                        first_rule_index: i + 1,
+                    })
+                }
+                Some(_) => {}
+                None => diagnostics.push(Diagnostic::MissingCatchAll(workflow.id.clone())),
+            }
+
+            for next_workflow_id in workflow.successors() {
+                if !self.workflows.contains_key(next_workflow_id) {
+                    diagnostics.push(Diagnostic::UndefinedWorkflow {
+                        workflow_id: next_workflow_id.clone(),
+                        referenced_by: workflow.id.clone(),
+                    });
+                }
+            }
+        }
+
+        if self.workflows.contains_key(Self::INITIAL_RULE) {
+            let mut reachable = HashSet::from([Self::INITIAL_RULE]);
+            let mut queue = VecDeque::from([Self::INITIAL_RULE]);
+
+            while let Some(workflow_id) = queue.pop_front() {
+                if let Some(workflow) = self.workflows.get(workflow_id) {
+                    for next_workflow_id in workflow.successors() {
+                        if reachable.insert(next_workflow_id.as_str()) {
+                            queue.push_back(next_workflow_id.as_str());
+                        }
+                    }
+                }
+            }
+
+            workflow_ids
+                .iter()
+                .filter(|workflow_id| !reachable.contains(workflow_id.as_str()))
+                .for_each(|workflow_id| {
+                    diagnostics.push(Diagnostic::UnreachableWorkflow((*workflow_id).clone()))
+                });
+        }
+
+        let mut visited = HashSet::new();
+
+        for workflow_id in &workflow_ids {
+            self.find_cycles(workflow_id, &mut Vec::new(), &mut visited, &mut diagnostics);
+        }
+
+        diagnostics
+    }
+
+    fn find_cycles<'a>(
+        &'a self,
+        workflow_id: &'a str,
+        path: &mut Vec<&'a str>,
+        visited: &mut HashSet<&'a str>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if let Some(start) = path.iter().position(|&id| id == workflow_id) {
+            diagnostics.push(Diagnostic::Cycle(
+                path[start..].iter().map(|&id| String::from(id)).collect(),
+            ));
+
+            return;
+        }
+
+        if !visited.insert(workflow_id) {
+            return;
+        }
+
+        if let Some(workflow) = self.workflows.get(workflow_id) {
+            path.push(workflow_id);
+
+            for next_workflow_id in workflow.successors() {
+                self.find_cycles(next_workflow_id, path, visited, diagnostics);
+            }
+
+            path.pop();
+        }
+    }
+
+    fn possible_accepted_parts(&self) -> Result<u128, Box<dyn Error>> {
+        // Accepted regions never overlap, so their total volume can't be more than the volume of the
+        // whole part space
+        self.accepted_regions()?
+            .iter()
+            .map(|region| region.space.volume())
+            .sum()
+    }
+
+    fn accepted_regions(&self) -> Result<Vec<AcceptedRegion>, Box<dyn Error>> {
+        self.explore_part_space(None)
+    }
+
+    fn explore_part_space(
+        &self,
+        mut statistics: Option<&mut Statistics>,
+    ) -> Result<Vec<AcceptedRegion>, Box<dyn Error>> {
         let mut stack = vec![(
+            String::from(Self::INITIAL_RULE),
             Action::Transfer(String::from(Self::INITIAL_RULE)),
-            PartSpace::default(),
+            PartSpace::full(&self.components),
         )];
-        let mut accepted_parts = 0;
+        let mut accepted_regions = Vec::new();
 
-        while let Some((action, space)) = stack.pop() {
+        while let Some((source_workflow_id, action, space)) = stack.pop() {
             match action {
                 Action::Transfer(workflow_id) => {
                     let workflow = self
                         .workflows
                         .get(&workflow_id)
                         .expect("Referenced workflow must exist");
-                    let mut remainder = space;
-
-                    for rule in &workflow.rules {
-                        match rule.condition {
-                            Condition::LessThan(component, value) => {
-                                let (selected, r) = remainder.partition_less_than(component, value);
-                                stack.push((rule.action.clone(), selected));
 
-                                remainder = r;
-                            }
-                            Condition::GreaterThan(component, value) => {
-                                let (selected, r) =
-                                    remainder.partition_greater_than(component, value);
-                                stack.push((rule.action.clone(), selected));
+                    if let Some(statistics) = statistics.as_deref_mut() {
+                        statistics.record_entry_volume(workflow, space.volume()?);
+                    }
 
-                                remainder = r;
+                    let mut remainder = vec![space];
+
+                    for (rule_index, rule) in workflow.rules.iter().enumerate() {
+                        match &rule.condition {
+                            Condition::Compare(component, operator, value) => {
+                                let mut next_remainder = Vec::new();
+
+                                for space in remainder {
+                                    let (selected, r) =
+                                        space.partition(*component, *operator, *value);
+
+                                    for space in selected {
+                                        if let Some(statistics) = statistics.as_deref_mut() {
+                                            statistics.record_rule_volume(
+                                                workflow,
+                                                rule_index,
+                                                space.volume()?,
+                                            );
+                                        }
+
+                                        stack.push((
+                                            workflow_id.clone(),
+                                            rule.action.clone(),
+                                            space,
+                                        ));
+                                    }
+
+                                    next_remainder.extend(r);
+                                }
+
+                                remainder = next_remainder;
                             }
                             Condition::MatchAll => {
-                                // This should be the last entry in the list of rules; no need to worry about the
-                                // remainder (but WE could set it to "empty" if we really wanted to).
-                                stack.push((rule.action.clone(), remainder));
+                                // This should be the last entry in the list of rules; anything after it can never
+                                // match, so there's nothing left over.
+                                for space in remainder.drain(..) {
+                                    if let Some(statistics) = statistics.as_deref_mut() {
+                                        statistics.record_rule_volume(
+                                            workflow,
+                                            rule_index,
+                                            space.volume()?,
+                                        );
+                                    }
+
+                                    stack.push((workflow_id.clone(), rule.action.clone(), space));
+                                }
                             }
                         }
                     }
                 }
-                Action::Accept => accepted_parts += space.volume(),
+                Action::Accept => accepted_regions.push(AcceptedRegion {
+                    workflow_id: source_workflow_id,
+                    space,
+                }),
                 Action::Reject => {}
             }
         }
 
-        accepted_parts
+        Ok(accepted_regions)
+    }
+
+    fn to_document(&self) -> PartSorterDocument {
+        let mut workflow_ids: Vec<&String> = self.workflows.keys().collect();
+        workflow_ids.sort();
+
+        PartSorterDocument {
+            workflows: workflow_ids
+                .iter()
+                .map(|workflow_id| {
+                    let workflow = &self.workflows[*workflow_id];
+
+                    WorkflowDocument {
+                        id: workflow.id.clone(),
+                        rules: workflow
+                            .rules
+                            .iter()
+                            .map(|rule| RuleDocument {
+                                condition: match &rule.condition {
+                                    Condition::Compare(component, operator, value) => {
+                                        Some(ConditionDocument {
+                                            component: String::from(
+                                                self.components.name(*component),
+                                            ),
+                                            operator: *operator,
+                                            value: *value,
+                                        })
+                                    }
+                                    Condition::MatchAll => None,
+                                },
+                                action: rule.action.clone(),
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+            parts: self
+                .parts
+                .iter()
+                .map(|part| {
+                    self.components
+                        .names
+                        .iter()
+                        .cloned()
+                        .zip(part.values.iter().copied())
+                        .collect()
+                })
+                .collect(),
+        }
     }
 }
 
+impl TryFrom<PartSorterDocument> for PartSorter {
+    type Error = Box<dyn Error>;
+
+    fn try_from(document: PartSorterDocument) -> Result<Self, Self::Error> {
+        let components = {
+            let part_names = document
+                .parts
+                .iter()
+                .flat_map(|part| part.keys())
+                .map(|name| name.as_str());
+
+            let condition_names = document
+                .workflows
+                .iter()
+                .flat_map(|workflow| workflow.rules.iter())
+                .filter_map(|rule| rule.condition.as_ref())
+                .map(|condition| condition.component.as_str());
+
+            Components::from_names(part_names.chain(condition_names))
+        };
+
+        let mut workflows = HashMap::new();
+
+        for workflow in document.workflows {
+            let rules = workflow
+                .rules
+                .into_iter()
+                .map(|rule| {
+                    let condition = match rule.condition {
+                        Some(condition) => Condition::Compare(
+                            components
+                                .get(&condition.component)
+                                .ok_or("Unrecognized component")?,
+                            condition.operator,
+                            condition.value,
+                        ),
+                        None => Condition::MatchAll,
+                    };
+
+                    Ok(Rule {
+                        condition,
+                        action: rule.action,
+                    })
+                })
+                .collect::<Result<_, Box<dyn Error>>>()?;
+
+            workflows.insert(
+                workflow.id.clone(),
+                Workflow {
+                    id: workflow.id,
+                    rules,
+                },
+            );
+        }
+
+        let parts = document
+            .parts
+            .iter()
+            .map(|values| {
+                let mut part = Part {
+                    values: ComponentValues::filled(&components, 0),
+                };
+
+                for (name, value) in values {
+                    part[components.get(name).ok_or("Unrecognized component")?] = *value;
+                }
+
+                Ok(part)
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        Ok(PartSorter {
+            components,
+            workflows,
+            parts,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PartSorterDocument {
+    workflows: Vec<WorkflowDocument>,
+    parts: Vec<BTreeMap<String, u32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkflowDocument {
+    id: String,
+    rules: Vec<RuleDocument>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RuleDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<ConditionDocument>,
+    action: Action,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ConditionDocument {
+    component: String,
+    operator: Operator,
+    value: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct AcceptedRegionDocument {
+    workflow_id: String,
+    ranges: BTreeMap<String, (u32, u32)>,
+}
+
 impl FromStr for PartSorter {
     type Err = Box<dyn Error>;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
         if let [workflows, parts] = string.split("\n\n").collect::<Vec<&str>>().as_slice() {
+            let components = Components::discover(workflows, parts);
+
             let workflows: Vec<Workflow> = workflows
                 .lines()
-                .map(Workflow::from_str)
+                .map(|line| Workflow::parse(line, &components))
                 .collect::<Result<_, _>>()?;
 
             let workflows: HashMap<String, Workflow> = workflows
@@ -136,10 +641,14 @@ impl FromStr for PartSorter {
 
             let parts: Vec<Part> = parts
                 .lines()
-                .map(Part::from_str)
+                .map(|line| Part::parse(line, &components))
                 .collect::<Result<_, _>>()?;
 
-            Ok(PartSorter { workflows, parts })
+            Ok(PartSorter {
+                components,
+                workflows,
+                parts,
+            })
         } else {
             Err("Could not separate workflows and parts".into())
         }
@@ -152,25 +661,38 @@ struct Workflow {
 }
 
 impl Workflow {
-    fn apply(&self, part: &Part) -> Action {
+    fn successors(&self) -> impl Iterator<Item = &String> {
+        let reachable_rules = self
+            .rules
+            .iter()
+            .position(|rule| rule.condition == Condition::MatchAll)
+            .map(|i| i + 1)
+            .unwrap_or(self.rules.len());
+
+        self.rules[..reachable_rules]
+            .iter()
+            .filter_map(|rule| match &rule.action {
+                Action::Transfer(workflow_id) => Some(workflow_id),
+                _ => None,
+            })
+    }
+
+    fn matching_rule(&self, part: &Part) -> (usize, &Rule) {
         self.rules
             .iter()
-            .find(|rule| rule.matches(part))
+            .enumerate()
+            .find(|(_, rule)| rule.matches(part))
             .expect("Workflows must have at least one catch-all rule")
-            .action
-            .clone()
     }
 }
 
-impl FromStr for Workflow {
-    type Err = Box<dyn Error>;
-
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
+impl Workflow {
+    fn parse(string: &str, components: &Components) -> Result<Self, Box<dyn Error>> {
         if let [id, rules] = string.split('{').collect::<Vec<&str>>().as_slice() {
             if let Some(rules) = rules.strip_suffix('}') {
                 let rules: Vec<Rule> = rules
                     .split(',')
-                    .map(Rule::from_str)
+                    .map(|rule| Rule::parse(rule, components))
                     .collect::<Result<_, _>>()?;
 
                 Ok(Workflow {
@@ -194,26 +716,30 @@ struct Rule {
 impl Rule {
     fn matches(&self, part: &Part) -> bool {
         match &self.condition {
-            Condition::LessThan(component, value) => part[*component] < *value,
-            Condition::GreaterThan(component, value) => part[*component] > *value,
+            Condition::Compare(component, operator, value) => {
+                operator.apply(part[*component], *value)
+            }
             Condition::MatchAll => true,
         }
     }
 }
 
-impl FromStr for Rule {
-    type Err = Box<dyn Error>;
-
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
+impl Rule {
+    fn parse(string: &str, components: &Components) -> Result<Self, Box<dyn Error>> {
         if let [condition, action] = string.split(':').collect::<Vec<&str>>().as_slice() {
-            let condition = if let [component, value] =
-                condition.split('<').collect::<Vec<&str>>().as_slice()
-            {
-                Condition::LessThan(Component::from_str(component)?, value.parse()?)
-            } else if let [component, value] =
-                condition.split('>').collect::<Vec<&str>>().as_slice()
-            {
-                Condition::GreaterThan(Component::from_str(component)?, value.parse()?)
+            let condition = if let Some(operator_start) = condition.find(is_operator_char) {
+                let value_start = condition[operator_start..]
+                    .find(|c: char| !is_operator_char(c))
+                    .map(|i| operator_start + i)
+                    .ok_or("Could not parse rule string")?;
+
+                Condition::Compare(
+                    components
+                        .get(&condition[..operator_start])
+                        .ok_or("Unrecognized component")?,
+                    Operator::from_str(&condition[operator_start..value_start])?,
+                    condition[value_start..].parse()?,
+                )
             } else {
                 return Err("Could not parse rule string".into());
             };
@@ -231,20 +757,189 @@ impl FromStr for Rule {
     }
 }
 
-#[derive(Eq, PartialEq)]
+fn is_operator_char(c: char) -> bool {
+    "<>=!".contains(c)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 enum Condition {
-    LessThan(Component, u32),
-    GreaterThan(Component, u32),
+    Compare(Component, Operator, u32),
     MatchAll,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+enum Operator {
+    #[serde(rename = "<")]
+    LessThan,
+    #[serde(rename = "<=")]
+    LessThanOrEqual,
+    #[serde(rename = ">")]
+    GreaterThan,
+    #[serde(rename = ">=")]
+    GreaterThanOrEqual,
+    #[serde(rename = "==")]
+    Equal,
+    #[serde(rename = "!=")]
+    NotEqual,
+}
+
+impl Operator {
+    fn apply(&self, lhs: u32, rhs: u32) -> bool {
+        match self {
+            Operator::LessThan => lhs < rhs,
+            Operator::LessThanOrEqual => lhs <= rhs,
+            Operator::GreaterThan => lhs > rhs,
+            Operator::GreaterThanOrEqual => lhs >= rhs,
+            Operator::Equal => lhs == rhs,
+            Operator::NotEqual => lhs != rhs,
+        }
+    }
+
+    fn negate(&self) -> Self {
+        match self {
+            Operator::LessThan => Operator::GreaterThanOrEqual,
+            Operator::LessThanOrEqual => Operator::GreaterThan,
+            Operator::GreaterThan => Operator::LessThanOrEqual,
+            Operator::GreaterThanOrEqual => Operator::LessThan,
+            Operator::Equal => Operator::NotEqual,
+            Operator::NotEqual => Operator::Equal,
+        }
+    }
+
+    // Returns the inclusive ranges of left-hand values for which this operator holds against the given right-hand
+    // value; values at the edges of the u32 domain may produce empty (and therefore omitted) ranges.
+    fn matching_ranges(&self, value: u32) -> Vec<(u32, u32)> {
+        let below = value.checked_sub(1).map(|end| (u32::MIN, end));
+        let above = value.checked_add(1).map(|start| (start, u32::MAX));
+
+        match self {
+            Operator::LessThan => below.into_iter().collect(),
+            Operator::LessThanOrEqual => vec![(u32::MIN, value)],
+            Operator::GreaterThan => above.into_iter().collect(),
+            Operator::GreaterThanOrEqual => vec![(value, u32::MAX)],
+            Operator::Equal => vec![(value, value)],
+            Operator::NotEqual => below.into_iter().chain(above).collect(),
+        }
+    }
+}
+
+impl FromStr for Operator {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "<" => Ok(Operator::LessThan),
+            "<=" => Ok(Operator::LessThanOrEqual),
+            ">" => Ok(Operator::GreaterThan),
+            ">=" => Ok(Operator::GreaterThanOrEqual),
+            "==" => Ok(Operator::Equal),
+            "!=" => Ok(Operator::NotEqual),
+            _ => Err("Unrecognized operator".into()),
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Operator::LessThan => "<",
+                Operator::LessThanOrEqual => "<=",
+                Operator::GreaterThan => ">",
+                Operator::GreaterThanOrEqual => ">=",
+                Operator::Equal => "==",
+                Operator::NotEqual => "!=",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 enum Action {
     Transfer(String),
     Accept,
     Reject,
 }
 
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Transfer(workflow_id) => write!(f, "{}", workflow_id),
+            Action::Accept => write!(f, "A"),
+            Action::Reject => write!(f, "R"),
+        }
+    }
+}
+
+impl TryFrom<String> for Action {
+    type Error = Box<dyn Error>;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        Action::from_str(string.as_str())
+    }
+}
+
+impl From<Action> for String {
+    fn from(action: Action) -> Self {
+        action.to_string()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Statistics {
+    workflows: HashMap<String, WorkflowStatistics>,
+}
+
+impl Statistics {
+    fn workflow_statistics(&mut self, workflow: &Workflow) -> &mut WorkflowStatistics {
+        self.workflows
+            .entry(workflow.id.clone())
+            .or_insert_with(|| WorkflowStatistics {
+                rules: vec![RuleStatistics::default(); workflow.rules.len()],
+                ..Default::default()
+            })
+    }
+
+    fn record_part(&mut self, workflow: &Workflow, rule_index: usize) {
+        let workflow_statistics = self.workflow_statistics(workflow);
+
+        workflow_statistics.parts += 1;
+        workflow_statistics.rules[rule_index].parts += 1;
+    }
+
+    fn record_entry_volume(&mut self, workflow: &Workflow, volume: u128) {
+        self.workflow_statistics(workflow).volume += volume;
+    }
+
+    fn record_rule_volume(&mut self, workflow: &Workflow, rule_index: usize, volume: u128) {
+        self.workflow_statistics(workflow).rules[rule_index].volume += volume;
+    }
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct WorkflowStatistics {
+    parts: u64,
+    volume: u128,
+    rules: Vec<RuleStatistics>,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+struct RuleStatistics {
+    parts: u64,
+    volume: u128,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct TraceStep {
+    workflow_id: String,
+    rule_index: usize,
+    condition: Condition,
+    action: Action,
+}
+
 impl FromStr for Action {
     type Err = Box<dyn Error>;
 
@@ -257,30 +952,279 @@ impl FromStr for Action {
     }
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(Debug, Eq, PartialEq)]
+enum Diagnostic {
+    MissingInitialWorkflow,
+    UndefinedWorkflow {
+        workflow_id: String,
+        referenced_by: String,
+    },
+    UnreachableWorkflow(String),
+    UnreachableRules {
+        workflow_id: String,
+        first_rule_index: usize,
+    },
+    MissingCatchAll(String),
+    Cycle(Vec<String>),
+}
+
+impl Diagnostic {
+    // Unreachable workflows and rules are harmless clutter, but everything else would keep some
+    // parts from being sorted
+    fn is_fatal(&self) -> bool {
+        !matches!(
+            self,
+            Diagnostic::UnreachableWorkflow(_) | Diagnostic::UnreachableRules { .. }
+        )
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::MissingInitialWorkflow => {
+                write!(f, "No initial workflow \"{}\"", PartSorter::INITIAL_RULE)
+            }
+            Diagnostic::UndefinedWorkflow {
+                workflow_id,
+                referenced_by,
+            } => write!(
+                f,
+                "Workflow \"{}\" references undefined workflow \"{}\"",
+                referenced_by, workflow_id
+            ),
+            Diagnostic::UnreachableWorkflow(workflow_id) => write!(
+                f,
+                "Workflow \"{}\" is unreachable from \"{}\"",
+                workflow_id,
+                PartSorter::INITIAL_RULE
+            ),
+            Diagnostic::UnreachableRules {
+                workflow_id,
+                first_rule_index,
+            } => write!(
+                f,
+                "Rules in workflow \"{}\" starting at index {} follow a catch-all rule",
+                workflow_id, first_rule_index
+            ),
+            Diagnostic::MissingCatchAll(workflow_id) => {
+                write!(f, "Workflow \"{}\" has no catch-all rule", workflow_id)
+            }
+            Diagnostic::Cycle(workflow_ids) => {
+                write!(f, "Workflows form a cycle: {}", workflow_ids.join(" -> "))
+            }
+        }
+    }
+}
+
+struct AcceptedRegion {
+    workflow_id: String,
+    space: PartSpace,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Components {
+    names: Vec<String>,
+    standard: bool,
+}
+
+impl Components {
+    const STANDARD_NAMES: [&'static str; 4] = ["x", "m", "a", "s"];
+
+    fn new(names: Vec<String>) -> Self {
+        // Keep the standard components in their usual order (regardless of the order in which they were discovered)
+        // so we can use the fast paths for looking them up by name and storing their values inline
+        let standard = names.len() == Self::STANDARD_NAMES.len()
+            && Self::STANDARD_NAMES
+                .iter()
+                .all(|standard_name| names.iter().any(|name| name == standard_name));
+
+        if standard {
+            Components {
+                names: Self::STANDARD_NAMES
+                    .iter()
+                    .map(|&name| String::from(name))
+                    .collect(),
+                standard,
+            }
+        } else {
+            Components { names, standard }
+        }
+    }
+
+    fn from_names<'a>(names: impl Iterator<Item = &'a str>) -> Self {
+        let mut unique_names: Vec<String> = Vec::new();
+
+        for name in names {
+            if !unique_names.iter().any(|existing| existing == name) {
+                unique_names.push(String::from(name));
+            }
+        }
+
+        Self::new(unique_names)
+    }
+
+    // Collects component names in the order in which they first appear, first in parts and then in workflow
+    // conditions
+    fn discover(workflows: &str, parts: &str) -> Self {
+        let part_names = parts
+            .lines()
+            .filter_map(|line| line.strip_prefix('{').and_then(|l| l.strip_suffix('}')))
+            .flat_map(|components| components.split(','))
+            .filter_map(|component| component.split_once('=').map(|(name, _)| name));
+
+        let condition_names = workflows
+            .lines()
+            .filter_map(|line| line.split_once('{').map(|(_, rules)| rules))
+            .flat_map(|rules| rules.trim_end_matches('}').split(','))
+            .filter_map(|rule| rule.split_once(':').map(|(condition, _)| condition))
+            .filter_map(|condition| {
+                condition
+                    .find(is_operator_char)
+                    .map(|operator_start| &condition[..operator_start])
+            });
+
+        Self::from_names(part_names.chain(condition_names))
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn get(&self, name: &str) -> Option<Component> {
+        if self.standard {
+            match name {
+                "x" => Some(Component(0)),
+                "m" => Some(Component(1)),
+                "a" => Some(Component(2)),
+                "s" => Some(Component(3)),
+                _ => None,
+            }
+        } else {
+            self.names
+                .iter()
+                .position(|existing| existing == name)
+                .map(Component)
+        }
+    }
+
+    fn name(&self, component: Component) -> &str {
+        self.names[component.0].as_str()
+    }
+
+    fn format_part(&self, part: &Part) -> String {
+        let components: Vec<String> = self
+            .names
+            .iter()
+            .zip(part.values.iter())
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        format!("{{{}}}", components.join(","))
+    }
+
+    fn format_space(&self, space: &PartSpace) -> String {
+        let ranges: Vec<String> = self
+            .names
+            .iter()
+            .zip(space.ranges.iter())
+            .map(|(name, (start, end))| format!("{}={}..={}", name, start, end))
+            .collect();
+
+        ranges.join(", ")
+    }
+
+    fn region_document(&self, region: &AcceptedRegion) -> AcceptedRegionDocument {
+        AcceptedRegionDocument {
+            workflow_id: region.workflow_id.clone(),
+            ranges: self
+                .names
+                .iter()
+                .cloned()
+                .zip(region.space.ranges.iter().copied())
+                .collect(),
+        }
+    }
+
+    fn format_condition(&self, condition: &Condition) -> String {
+        match condition {
+            Condition::Compare(component, operator, value) => {
+                format!("{}{}{}", self.name(*component), operator, value)
+            }
+            Condition::MatchAll => String::from("*"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Component(usize);
+
+// Per-component values for parts and part spaces; the standard x/m/a/s components fit inline, so sorting parts for
+// the usual puzzle never has to allocate, and anything else goes on the heap
+#[derive(Debug, Clone)]
+enum ComponentValues<T> {
+    Standard([T; 4]),
+    Custom(Vec<T>),
+}
+
+impl<T: Copy> ComponentValues<T> {
+    fn filled(components: &Components, value: T) -> Self {
+        if components.standard {
+            ComponentValues::Standard([value; 4])
+        } else {
+            ComponentValues::Custom(vec![value; components.len()])
+        }
+    }
+}
+
+impl<T> Deref for ComponentValues<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ComponentValues::Standard(values) => values,
+            ComponentValues::Custom(values) => values,
+        }
+    }
+}
+
+impl<T> DerefMut for ComponentValues<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            ComponentValues::Standard(values) => values,
+            ComponentValues::Custom(values) => values,
+        }
+    }
+}
+
+// Values are equal if they hold the same values, regardless of where they're stored
+impl<T: PartialEq> PartialEq for ComponentValues<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for ComponentValues<T> {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 struct Part {
-    x: u32,
-    m: u32,
-    a: u32,
-    s: u32,
+    values: ComponentValues<u32>,
 }
 
 impl Part {
     fn rating(&self) -> u32 {
-        self.x + self.m + self.a + self.s
+        self.values.iter().sum()
     }
-}
 
-impl FromStr for Part {
-    type Err = Box<dyn Error>;
-
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
-        if let Some(components) = string.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
-            let mut part = Part::default();
+    fn parse(string: &str, components: &Components) -> Result<Self, Box<dyn Error>> {
+        if let Some(values) = string.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            let mut part = Part {
+                values: ComponentValues::filled(components, 0),
+            };
 
-            for component in components.split(',') {
+            for component in values.split(',') {
                 if let [component, value] = component.split('=').collect::<Vec<&str>>().as_slice() {
-                    let component = Component::from_str(component)?;
+                    let component = components.get(component).ok_or("Unrecognized component")?;
                     let value = value.parse()?;
 
                     part[component] = value;
@@ -298,97 +1242,73 @@ impl Index<Component> for Part {
     type Output = u32;
 
     fn index(&self, component: Component) -> &Self::Output {
-        match component {
-            Component::X => &self.x,
-            Component::M => &self.m,
-            Component::A => &self.a,
-            Component::S => &self.s,
-        }
+        &self.values[component.0]
     }
 }
 
 impl IndexMut<Component> for Part {
     fn index_mut(&mut self, component: Component) -> &mut Self::Output {
-        match component {
-            Component::X => &mut self.x,
-            Component::M => &mut self.m,
-            Component::A => &mut self.a,
-            Component::S => &mut self.s,
-        }
+        &mut self.values[component.0]
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-enum Component {
-    X,
-    M,
-    A,
-    S,
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct PartSpace {
+    ranges: ComponentValues<(u32, u32)>,
 }
 
-impl FromStr for Component {
-    type Err = Box<dyn Error>;
+impl PartSpace {
+    const DEFAULT_RANGE: (u32, u32) = (1, 4000);
 
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
-        match string {
-            "x" => Ok(Component::X),
-            "m" => Ok(Component::M),
-            "a" => Ok(Component::A),
-            "s" => Ok(Component::S),
-            _ => Err("Unrecognized component".into()),
+    fn full(components: &Components) -> Self {
+        PartSpace {
+            ranges: ComponentValues::filled(components, Self::DEFAULT_RANGE),
         }
     }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-struct PartSpace {
-    x_range: (u32, u32),
-    m_range: (u32, u32),
-    a_range: (u32, u32),
-    s_range: (u32, u32),
-}
 
-impl PartSpace {
-    fn volume(&self) -> u64 {
-        [self.x_range, self.m_range, self.a_range, self.s_range]
+    // With enough components, even a u128 can't hold the volume of the full part space
+    fn volume(&self) -> Result<u128, Box<dyn Error>> {
+        self.ranges
             .iter()
-            .map(|(start, end)| ((end - start) + 1) as u64)
-            .product()
+            .try_fold(1u128, |volume, (start, end)| {
+                volume.checked_mul(((end - start) + 1) as u128)
+            })
+            .ok_or_else(|| "Part space is too large to measure".into())
     }
 
-    fn partition_less_than(&self, component: Component, value: u32) -> (Self, Self) {
-        let mut selected = *self;
-        let mut remainder = *self;
-
-        let original_range = self[component];
-
-        selected[component] = (original_range.0, value - 1);
-        remainder[component] = (value, original_range.1);
-
-        (selected, remainder)
+    fn contains(&self, part: &Part) -> bool {
+        self.ranges
+            .iter()
+            .zip(part.values.iter())
+            .all(|((start, end), value)| (start..=end).contains(&value))
     }
 
-    fn partition_greater_than(&self, component: Component, value: u32) -> (Self, Self) {
-        let mut selected = *self;
-        let mut remainder = *self;
-
-        let original_range = self[component];
+    fn partition(
+        &self,
+        component: Component,
+        operator: Operator,
+        value: u32,
+    ) -> (Vec<Self>, Vec<Self>) {
+        (
+            self.restrict(component, operator.matching_ranges(value)),
+            self.restrict(component, operator.negate().matching_ranges(value)),
+        )
+    }
 
-        selected[component] = (value + 1, original_range.1);
-        remainder[component] = (original_range.0, value);
+    fn restrict(&self, component: Component, ranges: Vec<(u32, u32)>) -> Vec<Self> {
+        let (start, end) = self[component];
 
-        (selected, remainder)
-    }
-}
+        ranges
+            .into_iter()
+            .map(|(range_start, range_end)| (range_start.max(start), range_end.min(end)))
+            .filter(|(range_start, range_end)| range_start <= range_end)
+            .map(|range| {
+                let mut space = self.clone();
+                space[component] = range;
 
-impl Default for PartSpace {
-    fn default() -> Self {
-        PartSpace {
-            x_range: (1, 4000),
-            m_range: (1, 4000),
-            a_range: (1, 4000),
-            s_range: (1, 4000),
-        }
+                space
+            })
+            .collect()
     }
 }
 
@@ -396,23 +1316,13 @@ impl Index<Component> for PartSpace {
     type Output = (u32, u32);
 
     fn index(&self, component: Component) -> &Self::Output {
-        match component {
-            Component::X => &self.x_range,
-            Component::M => &self.m_range,
-            Component::A => &self.a_range,
-            Component::S => &self.s_range,
-        }
+        &self.ranges[component.0]
     }
 }
 
 impl IndexMut<Component> for PartSpace {
     fn index_mut(&mut self, component: Component) -> &mut Self::Output {
-        match component {
-            Component::X => &mut self.x_range,
-            Component::M => &mut self.m_range,
-            Component::A => &mut self.a_range,
-            Component::S => &mut self.s_range,
-        }
+        &mut self.ranges[component.0]
     }
 }
 
@@ -421,6 +1331,20 @@ mod test {
     use super::*;
     use indoc::indoc;
 
+    const X: Component = Component(0);
+    const M: Component = Component(1);
+    const A: Component = Component(2);
+    const S: Component = Component(3);
+
+    fn standard_components() -> Components {
+        Components::new(
+            Components::STANDARD_NAMES
+                .iter()
+                .map(|&name| String::from(name))
+                .collect(),
+        )
+    }
+
     const TEST_SORTER_STRING: &str = indoc! {"
         px{a<2006:qkq,m>2090:A,rfg}
         pv{a>1716:R,A}
@@ -452,65 +1376,410 @@ mod test {
     fn test_possible_accepted_parts() {
         let part_sorter = PartSorter::from_str(TEST_SORTER_STRING).unwrap();
 
-        assert_eq!(167_409_079_868_000, part_sorter.possible_accepted_parts());
+        assert_eq!(
+            167_409_079_868_000,
+            part_sorter.possible_accepted_parts().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_accepted_regions() {
+        let part_sorter = PartSorter::from_str(TEST_SORTER_STRING).unwrap();
+        let accepted_regions = part_sorter.accepted_regions().unwrap();
+
+        assert!(accepted_regions
+            .iter()
+            .all(|region| part_sorter.workflows.contains_key(&region.workflow_id)));
+
+        for part in &part_sorter.parts {
+            assert_eq!(
+                part_sorter.process_part(part, None),
+                accepted_regions
+                    .iter()
+                    .any(|region| region.space.contains(part))
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_part_with_trace() {
+        let part_sorter = PartSorter::from_str(TEST_SORTER_STRING).unwrap();
+        let part = Part::parse("{x=787,m=2655,a=1222,s=2876}", &standard_components()).unwrap();
+
+        let expected_trace = vec![
+            TraceStep {
+                workflow_id: String::from("in"),
+                rule_index: 1,
+                condition: Condition::MatchAll,
+                action: Action::Transfer(String::from("qqz")),
+            },
+            TraceStep {
+                workflow_id: String::from("qqz"),
+                rule_index: 0,
+                condition: Condition::Compare(S, Operator::GreaterThan, 2770),
+                action: Action::Transfer(String::from("qs")),
+            },
+            TraceStep {
+                workflow_id: String::from("qs"),
+                rule_index: 1,
+                condition: Condition::MatchAll,
+                action: Action::Transfer(String::from("lnx")),
+            },
+            TraceStep {
+                workflow_id: String::from("lnx"),
+                rule_index: 0,
+                condition: Condition::Compare(M, Operator::GreaterThan, 1548),
+                action: Action::Accept,
+            },
+        ];
+
+        assert_eq!(
+            (true, expected_trace),
+            part_sorter.process_part_with_trace(&part)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(PartSorter::from_str(TEST_SORTER_STRING)
+            .unwrap()
+            .validate()
+            .is_empty());
+
+        let part_sorter = PartSorter::from_str(indoc! {"
+            in{x<10:a,m>20:b,c}
+            a{s<5:A,R,A}
+            b{a>7:in,nope}
+            c{x>3:R}
+            d{A}
+
+            {x=1,m=2,a=3,s=4}
+        "})
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                Diagnostic::UnreachableRules {
+                    workflow_id: String::from("a"),
+                    first_rule_index: 2,
+                },
+                Diagnostic::UndefinedWorkflow {
+                    workflow_id: String::from("nope"),
+                    referenced_by: String::from("b"),
+                },
+                Diagnostic::MissingCatchAll(String::from("c")),
+                Diagnostic::UnreachableWorkflow(String::from("d")),
+                Diagnostic::Cycle(vec![String::from("b"), String::from("in")]),
+            ],
+            part_sorter.validate()
+        );
+
+        assert_eq!(
+            vec![false, true, true, false, true],
+            part_sorter
+                .validate()
+                .iter()
+                .map(Diagnostic::is_fatal)
+                .collect::<Vec<bool>>()
+        );
     }
 
     #[test]
     fn test_part_space_volume() {
-        assert_eq!(4000 * 4000 * 4000 * 4000, PartSpace::default().volume());
+        assert_eq!(
+            4000 * 4000 * 4000 * 4000,
+            PartSpace::full(&standard_components()).volume().unwrap()
+        );
     }
 
     #[test]
     fn test_part_space_partition_less_than() {
         let expected_selected = PartSpace {
-            x_range: (1, 4000),
-            m_range: (1, 4000),
-            a_range: (1, 999),
-            s_range: (1, 4000),
+            ranges: ComponentValues::Standard([(1, 4000), (1, 4000), (1, 999), (1, 4000)]),
         };
 
         let expected_remainder = PartSpace {
-            x_range: (1, 4000),
-            m_range: (1, 4000),
-            a_range: (1000, 4000),
-            s_range: (1, 4000),
+            ranges: ComponentValues::Standard([(1, 4000), (1, 4000), (1000, 4000), (1, 4000)]),
         };
 
         assert_eq!(
-            (expected_selected, expected_remainder),
-            PartSpace::default().partition_less_than(Component::A, 1000)
+            (
+                vec![expected_selected.clone()],
+                vec![expected_remainder.clone()]
+            ),
+            PartSpace::full(&standard_components()).partition(A, Operator::LessThan, 1000)
         );
 
         assert_eq!(
-            PartSpace::default().volume(),
-            expected_selected.volume() + expected_remainder.volume()
+            PartSpace::full(&standard_components()).volume().unwrap(),
+            expected_selected.volume().unwrap() + expected_remainder.volume().unwrap()
         );
     }
 
     #[test]
     fn test_part_space_partition_greater_than() {
         let expected_selected = PartSpace {
-            x_range: (1, 4000),
-            m_range: (1001, 4000),
-            a_range: (1, 4000),
-            s_range: (1, 4000),
+            ranges: ComponentValues::Standard([(1, 4000), (1001, 4000), (1, 4000), (1, 4000)]),
         };
 
         let expected_remainder = PartSpace {
-            x_range: (1, 4000),
-            m_range: (1, 1000),
-            a_range: (1, 4000),
-            s_range: (1, 4000),
+            ranges: ComponentValues::Standard([(1, 4000), (1, 1000), (1, 4000), (1, 4000)]),
         };
 
         assert_eq!(
-            (expected_selected, expected_remainder),
-            PartSpace::default().partition_greater_than(Component::M, 1000)
+            (
+                vec![expected_selected.clone()],
+                vec![expected_remainder.clone()]
+            ),
+            PartSpace::full(&standard_components()).partition(M, Operator::GreaterThan, 1000)
+        );
+
+        assert_eq!(
+            PartSpace::full(&standard_components()).volume().unwrap(),
+            expected_selected.volume().unwrap() + expected_remainder.volume().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_part_space_partition_equality() {
+        let (selected, remainder) =
+            PartSpace::full(&standard_components()).partition(X, Operator::NotEqual, 1);
+
+        assert_eq!(
+            vec![PartSpace {
+                ranges: ComponentValues::Standard([(2, 4000), (1, 4000), (1, 4000), (1, 4000)]),
+            }],
+            selected
+        );
+
+        assert_eq!(
+            vec![PartSpace {
+                ranges: ComponentValues::Standard([(1, 1), (1, 4000), (1, 4000), (1, 4000)]),
+            }],
+            remainder
+        );
+
+        let (selected, remainder) =
+            PartSpace::full(&standard_components()).partition(S, Operator::Equal, 2000);
+
+        assert_eq!(
+            vec![PartSpace {
+                ranges: ComponentValues::Standard([(1, 4000), (1, 4000), (1, 4000), (2000, 2000)]),
+            }],
+            selected
+        );
+
+        assert_eq!(
+            vec![
+                PartSpace {
+                    ranges: ComponentValues::Standard([(1, 4000), (1, 4000), (1, 4000), (1, 1999)]),
+                },
+                PartSpace {
+                    ranges: ComponentValues::Standard([
+                        (1, 4000),
+                        (1, 4000),
+                        (1, 4000),
+                        (2001, 4000)
+                    ]),
+                }
+            ],
+            remainder
+        );
+    }
+
+    #[test]
+    fn test_extended_operators() {
+        let part_sorter = PartSorter::from_str(indoc! {"
+            in{x<=1000:A,m>=3001:lo,a==17:A,s!=5:R,A}
+            lo{m<=3500:R,A}
+
+            {x=1001,m=1,a=17,s=5}
+            {x=1001,m=3000,a=18,s=5}
+            {x=1001,m=3001,a=18,s=5}
+            {x=1001,m=3501,a=18,s=5}
+        "})
+        .unwrap();
+
+        assert_eq!(9573, part_sorter.accepted_part_rating_sum());
+
+        assert_eq!(
+            (1000 * 4000 * 4000 * 4000)
+                + (3000 * 500 * 4000 * 4000)
+                + (3000 * 3000 * 4000)
+                + (3000 * 3000 * 3999),
+            part_sorter.possible_accepted_parts().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_custom_components() {
+        let part_sorter = PartSorter::from_str(indoc! {"
+            in{p<10:A,q>5:R,A}
+
+            {p=1,q=2}
+            {p=20,q=6}
+            {p=20,q=1}
+        "})
+        .unwrap();
+
+        assert_eq!(
+            Components::new(vec![String::from("p"), String::from("q")]),
+            part_sorter.components
         );
 
+        // Only the standard components get inline storage
+        assert!(matches!(
+            PartSpace::full(&part_sorter.components).ranges,
+            ComponentValues::Custom(_)
+        ));
+        assert!(matches!(
+            PartSpace::full(&standard_components()).ranges,
+            ComponentValues::Standard(_)
+        ));
+        assert!(matches!(
+            part_sorter.parts[0].values,
+            ComponentValues::Custom(_)
+        ));
+
+        assert_eq!(24, part_sorter.accepted_part_rating_sum());
         assert_eq!(
-            PartSpace::default().volume(),
-            expected_selected.volume() + expected_remainder.volume()
+            (9 * 4000) + (3991 * 5),
+            part_sorter.possible_accepted_parts().unwrap()
         );
     }
+
+    #[test]
+    fn test_many_components() {
+        // Six components are enough to overflow a u64
+        let part_sorter = PartSorter::from_str(indoc! {"
+            in{a<4000:A,R}
+
+            {a=1,b=1,c=1,d=1,e=1,f=1}
+        "})
+        .unwrap();
+
+        assert_eq!(6, part_sorter.components.len());
+        assert_eq!(
+            3999 * 4000u128.pow(5),
+            part_sorter.possible_accepted_parts().unwrap()
+        );
+
+        let statistics = part_sorter.statistics().unwrap();
+        assert_eq!(4000u128.pow(6), statistics.workflows["in"].volume);
+        assert_eq!(4000u128.pow(5), statistics.workflows["in"].rules[1].volume);
+
+        // ...and eleven are enough to overflow a u128
+        let part_sorter = PartSorter::from_str(indoc! {"
+            in{a<4000:A,R}
+
+            {a=1,b=1,c=1,d=1,e=1,f=1,g=1,h=1,i=1,j=1,k=1}
+        "})
+        .unwrap();
+
+        assert!(part_sorter.possible_accepted_parts().is_err());
+        assert!(part_sorter.statistics().is_err());
+    }
+
+    #[test]
+    fn test_document_round_trip() {
+        let part_sorter = PartSorter::from_str(TEST_SORTER_STRING).unwrap();
+
+        let json = serde_json::to_string(&part_sorter.to_document()).unwrap();
+        let from_json =
+            PartSorter::try_from(serde_json::from_str::<PartSorterDocument>(&json).unwrap())
+                .unwrap();
+
+        assert_eq!(part_sorter.components, from_json.components);
+        assert_eq!(19114, from_json.accepted_part_rating_sum());
+        assert_eq!(
+            167_409_079_868_000,
+            from_json.possible_accepted_parts().unwrap()
+        );
+
+        let toml = toml::to_string(&part_sorter.to_document()).unwrap();
+        let from_toml =
+            PartSorter::try_from(toml::from_str::<PartSorterDocument>(&toml).unwrap()).unwrap();
+
+        assert_eq!(19114, from_toml.accepted_part_rating_sum());
+        assert_eq!(
+            167_409_079_868_000,
+            from_toml.possible_accepted_parts().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_document_from_json() {
+        let document: PartSorterDocument = serde_json::from_str(indoc! {r#"
+            {
+              "workflows": [
+                {
+                  "id": "in",
+                  "rules": [
+                    { "condition": { "component": "x", "operator": ">=", "value": 10 }, "action": "A" },
+                    { "action": "R" }
+                  ]
+                }
+              ],
+              "parts": [
+                { "x": 10, "m": 1, "a": 1, "s": 1 },
+                { "x": 9, "m": 1, "a": 1, "s": 1 }
+              ]
+            }
+        "#})
+        .unwrap();
+
+        let part_sorter = PartSorter::try_from(document).unwrap();
+
+        assert!(part_sorter.components.standard);
+        assert_eq!(13, part_sorter.accepted_part_rating_sum());
+        assert_eq!(
+            3991 * 4000 * 4000 * 4000,
+            part_sorter.possible_accepted_parts().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statistics() {
+        let part_sorter = PartSorter::from_str(TEST_SORTER_STRING).unwrap();
+        let statistics = part_sorter.statistics().unwrap();
+
+        assert_eq!(
+            WorkflowStatistics {
+                parts: 5,
+                volume: 4000 * 4000 * 4000 * 4000,
+                rules: vec![
+                    RuleStatistics {
+                        parts: 3,
+                        volume: 1350 * 4000 * 4000 * 4000,
+                    },
+                    RuleStatistics {
+                        parts: 2,
+                        volume: 2650 * 4000 * 4000 * 4000,
+                    },
+                ],
+            },
+            statistics.workflows["in"]
+        );
+
+        // Parts that reach a workflow must leave by exactly one rule, and the same goes for volume
+        for workflow_statistics in statistics.workflows.values() {
+            assert_eq!(
+                workflow_statistics.parts,
+                workflow_statistics
+                    .rules
+                    .iter()
+                    .map(|rule| rule.parts)
+                    .sum::<u64>()
+            );
+
+            assert_eq!(
+                workflow_statistics.volume,
+                workflow_statistics
+                    .rules
+                    .iter()
+                    .map(|rule| rule.volume)
+                    .sum::<u128>()
+            );
+        }
+    }
 }
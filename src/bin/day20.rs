@@ -1,4 +1,6 @@
-use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::env;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
@@ -21,6 +23,115 @@ fn main() -> Result<(), Box<dyn Error>> {
             println!("{}", pulse_machine);
         } */
 
+        if args.iter().skip(2).any(|arg| arg == "--validate") {
+            let mut pulse_machine_string = String::new();
+            File::open(path)?.read_to_string(&mut pulse_machine_string)?;
+
+            let (_, report) = PulseMachine::parse_with_report(
+                pulse_machine_string.as_str(),
+                &ModuleRegistry::default(),
+            )?;
+
+            println!("{}", report);
+
+            return Ok(());
+        }
+
+        if let Some(snapshot_presses) = args
+            .iter()
+            .position(|arg| arg == "--snapshot")
+            .and_then(|i| args.get(i + 1))
+        {
+            let snapshot_presses: u32 = snapshot_presses.parse()?;
+
+            let mut pulse_machine = {
+                let mut pulse_machine_string = String::new();
+                File::open(path)?.read_to_string(&mut pulse_machine_string)?;
+
+                PulseMachine::from_str(pulse_machine_string.as_str())?
+            };
+
+            for _ in 0..snapshot_presses {
+                pulse_machine.handle_button_press();
+            }
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&pulse_machine.snapshot())?
+            );
+
+            return Ok(());
+        }
+
+        if let Some(i) = args.iter().position(|arg| arg == "--until") {
+            if let [module_id, direction, pulse] = &args[i + 1..] {
+                let mut pulse_machine = {
+                    let mut pulse_machine_string = String::new();
+                    File::open(path)?.read_to_string(&mut pulse_machine_string)?;
+
+                    PulseMachine::from_str(pulse_machine_string.as_str())?
+                };
+
+                let direction = PulseDirection::from_str(direction)?;
+                let pulse = Pulse::from_str(pulse)?;
+
+                if let Some(presses) = pulse_machine.presses_until(module_id, direction, pulse) {
+                    println!(
+                        "Button presses until {} {} a {} pulse: {}",
+                        module_id, direction, pulse, presses
+                    );
+                } else {
+                    println!("{} never {} a {} pulse", module_id, direction, pulse);
+                }
+
+                return Ok(());
+            } else {
+                return Err(
+                    "Usage: day20 INPUT_FILE_PATH --until MODULE_ID sent|received low|high".into(),
+                );
+            }
+        }
+
+        if let Some(trace_presses) = args
+            .iter()
+            .position(|arg| arg == "--trace")
+            .and_then(|i| args.get(i + 1))
+        {
+            let trace_presses: u32 = trace_presses.parse()?;
+
+            let mut pulse_machine = {
+                let mut pulse_machine_string = String::new();
+                File::open(path)?.read_to_string(&mut pulse_machine_string)?;
+
+                PulseMachine::from_str(pulse_machine_string.as_str())?
+            };
+
+            if let Some(state_path) = args
+                .iter()
+                .position(|arg| arg == "--from-state")
+                .and_then(|i| args.get(i + 1))
+            {
+                let mut state_string = String::new();
+                File::open(state_path)?.read_to_string(&mut state_string)?;
+
+                pulse_machine.restore(&serde_json::from_str(state_string.as_str())?)?;
+            }
+
+            pulse_machine.enable_trace();
+
+            for press in 1..=trace_presses {
+                pulse_machine.handle_button_press();
+
+                println!("Button press {}:", press);
+
+                for traced_pulse in pulse_machine.take_trace() {
+                    println!("{}", traced_pulse);
+                }
+
+                println!();
+            }
+        }
+
         {
             let pulse_machine = {
                 let mut pulse_machine_string = String::new();
@@ -39,6 +150,18 @@ fn main() -> Result<(), Box<dyn Error>> {
             );
         }
 
+        if args.iter().skip(2).any(|arg| arg == "--analyze") {
+            let pulse_machine = {
+                let mut pulse_machine_string = String::new();
+                File::open(path)?.read_to_string(&mut pulse_machine_string)?;
+
+                PulseMachine::from_str(pulse_machine_string.as_str())?
+            };
+
+            println!();
+            println!("{}", pulse_machine.cycle_analysis("rx", 1 << 20)?);
+        }
+
         {
             // This is a little gross and involves knowing something about how the input is
             // structured. Basically, there are four "feeder" cycles with prime cycle lengths. Those
@@ -52,31 +175,28 @@ fn main() -> Result<(), Box<dyn Error>> {
                     PulseMachine::from_str(pulse_machine_string.as_str())?
                 };
 
-                let rx_input = pulse_machine
-                    .modules
-                    .values()
-                    .find(|module| module.destinations().contains(&String::from("rx")))
-                    .unwrap();
+                let rx_input = pulse_machine.inputs("rx")[0];
 
                 pulse_machine
-                    .modules
-                    .values()
-                    .filter(|module| module.destinations().contains(&String::from(rx_input.id())))
-                    .map(|module| String::from(module.id()))
+                    .inputs(&pulse_machine.ids[rx_input])
+                    .iter()
+                    .map(|&feeder| pulse_machine.ids[feeder].clone())
                     .collect()
             };
 
             let mut presses = 1;
 
             for feeder_id in feeders {
-                let pulse_machine = {
+                let mut pulse_machine = {
                     let mut pulse_machine_string = String::new();
                     File::open(path)?.read_to_string(&mut pulse_machine_string)?;
 
                     PulseMachine::from_str(pulse_machine_string.as_str())?
                 };
 
-                presses *= pulse_machine.button_presses_until_single_low_pulse(feeder_id.as_str());
+                presses *= pulse_machine
+                    .presses_until(feeder_id.as_str(), PulseDirection::Received, Pulse::Low)
+                    .ok_or("Feeder never receives a low pulse")?;
             }
 
             println!("Button presses until single low pulse to rx: {}", presses);
@@ -84,24 +204,34 @@ fn main() -> Result<(), Box<dyn Error>> {
 
         Ok(())
     } else {
-        Err("Usage: day20 INPUT_FILE_PATH".into())
+        Err(
+            "Usage: day20 INPUT_FILE_PATH [--validate] [--snapshot PRESSES] [--trace PRESSES [--from-state STATE_PATH]] [--analyze]"
+                .into(),
+        )
     }
 }
 
 struct PulseMachine {
-    modules: HashMap<String, Box<dyn Module>>,
+    ids: Vec<String>,
+    indices: HashMap<String, usize>,
+    // Not all destinations are defined modules; some are just sinks, and have no module at their index
+    modules: Vec<Option<Box<dyn Module>>>,
+    destinations: Vec<Vec<usize>>,
+    trace: Option<Vec<TracedPulse>>,
 }
 
 impl PulseMachine {
+    const BUTTON_ID: &'static str = "button";
+
     fn pulses(mut self, button_presses: u32) -> (u32, u32) {
-        let mut previous_states: Vec<String> = Vec::new();
-        let mut state_cache: HashMap<String, (u32, u32)> = HashMap::new();
+        let mut previous_states: Vec<StateKey> = Vec::new();
+        let mut state_cache: HashMap<StateKey, (u32, u32)> = HashMap::new();
 
         let mut low_pulses = 0;
         let mut high_pulses = 0;
 
         for _ in 0..button_presses {
-            let state = self.state();
+            let state = self.state_key();
 
             let pulses = self.handle_button_press();
 
@@ -152,92 +282,422 @@ impl PulseMachine {
         (low_pulses, high_pulses)
     }
 
-    fn button_presses_until_single_low_pulse(mut self, watched_module_id: &str) -> u64 {
+    // Presses the button until the given module sends or receives the given kind of pulse, returning `None` if the
+    // machine returns to a previously-seen state first (in which case the pulse will never appear)
+    fn presses_until(
+        &mut self,
+        module_id: &str,
+        direction: PulseDirection,
+        pulse: Pulse,
+    ) -> Option<u64> {
+        let module_index = *self.indices.get(module_id)?;
+
+        let mut previous_states = HashSet::new();
         let mut button_presses = 0;
 
-        loop {
+        while previous_states.insert(self.state_key()) {
+            let mut found = false;
+
+            self.press_button(|source, destination, p| {
+                let probed_module_index = match direction {
+                    PulseDirection::Sent => source,
+                    PulseDirection::Received => destination,
+                };
+
+                found |= p == pulse && probed_module_index == module_index;
+            });
+
             button_presses += 1;
 
-            if self.low_pulses_after_button_press(watched_module_id) == 1 {
-                return button_presses;
+            if found {
+                return Some(button_presses);
             }
         }
+
+        None
     }
 
-    fn handle_button_press(&mut self) -> (u32, u32) {
-        let mut pulse_queue: VecDeque<(String, String, Pulse)> = VecDeque::new();
-        pulse_queue.push_back((
-            String::from("button"),
-            String::from(Broadcaster::BROADCASTER_ID),
-            Pulse::Low,
-        ));
+    fn cycle_analysis(
+        mut self,
+        target_id: &str,
+        max_presses: u64,
+    ) -> Result<CycleAnalysis, Box<dyn Error>> {
+        let final_conjunction = match self.inputs(target_id).as_slice() {
+            [input]
+                if matches!(
+                    self.modules[*input].as_ref().map(|module| module.state()),
+                    Some(ModuleState::Conjunction { .. })
+                ) =>
+            {
+                *input
+            }
+            _ => return Err(format!("{} is not fed by a single conjunction", target_id).into()),
+        };
 
-        let mut low_pulses = 0;
-        let mut high_pulses = 0;
+        let mut feeders = self.inputs(&self.ids[final_conjunction]);
+        feeders.sort_by_key(|&feeder| &self.ids[feeder]);
 
-        while let Some((source, destination, pulse)) = pulse_queue.pop_front() {
-            match pulse {
-                Pulse::Low => low_pulses += 1,
-                Pulse::High => high_pulses += 1,
+        let broadcaster = self.indices[Broadcaster::BROADCASTER_ID];
+
+        // Each feeder should sit at the end of its own sub-circuit; find everything upstream of each feeder (short of
+        // the broadcaster, which drives all of them) and make sure no module belongs to more than one sub-circuit
+        let mut chains: Vec<ChainCycle> = Vec::new();
+        let mut claimed_modules: HashSet<usize> = HashSet::new();
+
+        for &feeder in &feeders {
+            let mut modules = HashSet::from([feeder]);
+            let mut queue = VecDeque::from([feeder]);
+
+            while let Some(module) = queue.pop_front() {
+                for (source, destinations) in self.destinations.iter().enumerate() {
+                    if source != broadcaster
+                        && destinations.contains(&module)
+                        && modules.insert(source)
+                    {
+                        queue.push_back(source);
+                    }
+                }
+            }
+
+            if modules
+                .iter()
+                .any(|module| claimed_modules.contains(module))
+            {
+                return Err(format!(
+                    "Sub-circuits feeding {} are not independent",
+                    self.ids[final_conjunction]
+                )
+                .into());
             }
 
-            // Not all outputs reference a module; some are just sinks
-            if let Some(destination) = self.modules.get_mut(&destination) {
-                Self::enqueue_pulses(&mut **destination, (pulse, &source), &mut pulse_queue);
+            claimed_modules.extend(modules.iter().copied());
+
+            let mut module_ids: Vec<String> = modules
+                .into_iter()
+                .map(|module| self.ids[module].clone())
+                .collect();
+
+            module_ids.sort();
+
+            chains.push(ChainCycle {
+                feeder_id: self.ids[feeder].clone(),
+                module_ids,
+                phase: 0,
+                period: 0,
+            });
+        }
+
+        // The final conjunction only sends a low pulse when the last pulse from every feeder was high, so watch for
+        // the first two presses in which each feeder sends a high pulse
+        let mut firings: Vec<Vec<u64>> = vec![Vec::new(); self.ids.len()];
+        let mut button_presses = 0;
+
+        while feeders.iter().any(|&feeder| firings[feeder].len() < 2) {
+            if button_presses >= max_presses {
+                return Err(format!(
+                    "Not all sub-circuits cycled within {} button presses",
+                    max_presses
+                )
+                .into());
             }
+
+            button_presses += 1;
+
+            self.press_button(|source, destination, pulse| {
+                if pulse == Pulse::High
+                    && destination == final_conjunction
+                    && firings[source].last() != Some(&button_presses)
+                {
+                    firings[source].push(button_presses);
+                }
+            });
+        }
+
+        for (chain, &feeder) in chains.iter_mut().zip(feeders.iter()) {
+            chain.phase = firings[feeder][0];
+            chain.period = firings[feeder][1] - firings[feeder][0];
+        }
+
+        Ok(CycleAnalysis {
+            target_id: String::from(target_id),
+            final_conjunction_id: self.ids[final_conjunction].clone(),
+            chains,
+        })
+    }
+
+    fn inputs(&self, module_id: &str) -> Vec<usize> {
+        if let Some(module) = self.indices.get(module_id) {
+            self.destinations
+                .iter()
+                .enumerate()
+                .filter(|(_, destinations)| destinations.contains(module))
+                .map(|(source, _)| source)
+                .collect()
+        } else {
+            Vec::new()
         }
+    }
+
+    fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    fn take_trace(&mut self) -> Vec<TracedPulse> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn handle_button_press(&mut self) -> (u32, u32) {
+        let mut low_pulses = 0;
+        let mut high_pulses = 0;
+
+        self.press_button(|_, _, pulse| match pulse {
+            Pulse::Low => low_pulses += 1,
+            Pulse::High => high_pulses += 1,
+        });
 
         (low_pulses, high_pulses)
     }
 
-    fn low_pulses_after_button_press(&mut self, watched_module_id: &str) -> u32 {
-        let mut pulse_queue: VecDeque<(String, String, Pulse)> = VecDeque::new();
+    fn press_button(&mut self, mut observe_pulse: impl FnMut(usize, usize, Pulse)) {
+        let mut pulse_queue: VecDeque<(usize, usize, Pulse)> = VecDeque::new();
         pulse_queue.push_back((
-            String::from("button"),
-            String::from(Broadcaster::BROADCASTER_ID),
+            self.indices[Self::BUTTON_ID],
+            self.indices[Broadcaster::BROADCASTER_ID],
             Pulse::Low,
         ));
 
-        let mut watched_low_pulses = 0;
-
         while let Some((source, destination, pulse)) = pulse_queue.pop_front() {
-            if pulse == Pulse::Low && destination.as_str() == watched_module_id {
-                watched_low_pulses += 1;
+            observe_pulse(source, destination, pulse);
+
+            if let Some(trace) = self.trace.as_mut() {
+                trace.push(TracedPulse {
+                    source: self.ids[source].clone(),
+                    destination: self.ids[destination].clone(),
+                    pulse,
+                });
             }
 
-            // Not all outputs reference a module; some are just sinks
-            if let Some(destination) = self.modules.get_mut(&destination) {
-                Self::enqueue_pulses(&mut **destination, (pulse, &source), &mut pulse_queue);
+            if let Some(module) = self.modules[destination].as_mut() {
+                if let Some(outbound_pulse) = module.handle_pulse(pulse, source) {
+                    for &next_destination in &self.destinations[destination] {
+                        pulse_queue.push_back((destination, next_destination, outbound_pulse));
+                    }
+                }
             }
         }
+    }
 
-        watched_low_pulses
+    fn snapshot(&self) -> MachineState {
+        MachineState {
+            modules: self
+                .ids
+                .iter()
+                .zip(self.modules.iter())
+                .filter_map(|(id, module)| {
+                    module.as_ref().map(|module| (id.clone(), module.state()))
+                })
+                .collect(),
+        }
     }
 
-    fn enqueue_pulses(
-        module: &mut (impl Module + ?Sized),
-        input_pulse: (Pulse, &str),
-        pulse_queue: &mut VecDeque<(String, String, Pulse)>,
-    ) {
-        module
-            .handle_pulse(input_pulse.0, input_pulse.1)
+    // A compact stand-in for a snapshot that's cheap to build and hash when checking whether the
+    // machine has returned to a previous state; unlike a snapshot, it can't be restored
+    fn state_key(&self) -> StateKey {
+        let mut key = StateKey::default();
+
+        self.modules
             .iter()
-            .for_each(|(destination, pulse)| {
-                pulse_queue.push_back((String::from(module.id()), destination.clone(), *pulse));
-            });
+            .flatten()
+            .for_each(|module| module.append_state_key(&mut key));
+
+        key
     }
 
-    fn state(&self) -> String {
-        let mut sorted_states: Vec<String> = self
+    fn restore(&mut self, state: &MachineState) -> Result<(), Box<dyn Error>> {
+        let defined_modules = self
             .modules
-            .values()
-            .filter(|module| module.id() != Broadcaster::BROADCASTER_ID)
-            .map(|module| format!("{}:{}", module.id(), module.state()))
-            .collect();
+            .iter()
+            .filter(|module| module.is_some())
+            .count();
+
+        if state.modules.len() != defined_modules
+            || !state.modules.keys().all(|id| {
+                self.indices
+                    .get(id)
+                    .is_some_and(|&index| self.modules[index].is_some())
+            })
+        {
+            return Err("Machine state does not match machine modules".into());
+        }
+
+        for (id, module_state) in &state.modules {
+            self.modules[self.indices[id]]
+                .as_mut()
+                .expect("Module must exist")
+                .restore(module_state)?;
+        }
+
+        Ok(())
+    }
+
+    fn parse(string: &str, registry: &ModuleRegistry) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::parse_with_report(string, registry)?.0)
+    }
+
+    fn parse_with_report(
+        string: &str,
+        registry: &ModuleRegistry,
+    ) -> Result<(Self, ValidationReport), Box<dyn Error>> {
+        let mut builder = PulseMachineBuilder::new();
+
+        for line in string.lines() {
+            builder.add_module(registry.parse_module(line)?)?;
+        }
+
+        Ok(builder.build())
+    }
+}
+
+struct PulseMachineBuilder {
+    pulse_machine: PulseMachine,
+    sources: Vec<Vec<usize>>,
+}
+
+impl PulseMachineBuilder {
+    fn new() -> Self {
+        let mut builder = PulseMachineBuilder {
+            pulse_machine: PulseMachine {
+                ids: Vec::new(),
+                indices: HashMap::new(),
+                modules: Vec::new(),
+                destinations: Vec::new(),
+                trace: None,
+            },
+            sources: Vec::new(),
+        };
+
+        let button = builder.intern(PulseMachine::BUTTON_ID);
+        let broadcaster = builder.intern(Broadcaster::BROADCASTER_ID);
+
+        builder.pulse_machine.destinations[button].push(broadcaster);
+        builder.sources[broadcaster].push(button);
+
+        builder
+    }
+
+    fn intern(&mut self, id: &str) -> usize {
+        if let Some(&index) = self.pulse_machine.indices.get(id) {
+            index
+        } else {
+            let index = self.pulse_machine.ids.len();
+
+            self.pulse_machine.ids.push(String::from(id));
+            self.pulse_machine.indices.insert(String::from(id), index);
+            self.pulse_machine.modules.push(None);
+            self.pulse_machine.destinations.push(Vec::new());
+            self.sources.push(Vec::new());
+
+            index
+        }
+    }
+
+    fn add_module(&mut self, definition: ModuleDefinition) -> Result<(), Box<dyn Error>> {
+        let ModuleDefinition {
+            mut module,
+            destination_ids,
+        } = definition;
+
+        let index = self.intern(module.id());
+
+        if self.pulse_machine.modules[index].is_some() {
+            return Err(format!("Duplicate module: {}", module.id()).into());
+        }
+
+        // Wire up any modules we've already seen that send pulses to this one…
+        for &source in &self.sources[index] {
+            module.add_input(source, &self.pulse_machine.ids[source]);
+        }
+
+        self.pulse_machine.modules[index] = Some(module);
+
+        // …and then wire this module up to any destinations we've already seen
+        for destination_id in destination_ids {
+            let destination = self.intern(&destination_id);
+
+            self.pulse_machine.destinations[index].push(destination);
+            self.sources[destination].push(index);
 
-        sorted_states.sort();
+            if let Some(destination_module) = self.pulse_machine.modules[destination].as_mut() {
+                destination_module.add_input(index, &self.pulse_machine.ids[index]);
+            }
+        }
 
-        sorted_states.join(";")
+        Ok(())
+    }
+
+    fn build(self) -> (PulseMachine, ValidationReport) {
+        let ids = &self.pulse_machine.ids;
+        let button = self.pulse_machine.indices[PulseMachine::BUTTON_ID];
+
+        let mut unknown_destinations: Vec<(String, Vec<String>)> = Vec::new();
+        let mut unreferenced_modules: Vec<String> = Vec::new();
+
+        for (index, module) in self.pulse_machine.modules.iter().enumerate() {
+            if module.is_none() && index != button {
+                let mut sources: Vec<String> = self.sources[index]
+                    .iter()
+                    .map(|&source| ids[source].clone())
+                    .collect();
+
+                sources.sort();
+                unknown_destinations.push((ids[index].clone(), sources));
+            } else if module.is_some() && self.sources[index].is_empty() {
+                unreferenced_modules.push(ids[index].clone());
+            }
+        }
+
+        unknown_destinations.sort();
+        unreferenced_modules.sort();
+
+        (
+            self.pulse_machine,
+            ValidationReport {
+                unknown_destinations,
+                unreferenced_modules,
+            },
+        )
+    }
+}
+
+struct ValidationReport {
+    unknown_destinations: Vec<(String, Vec<String>)>,
+    unreferenced_modules: Vec<String>,
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.unknown_destinations.is_empty() && self.unreferenced_modules.is_empty() {
+            return write!(
+                f,
+                "All destinations are defined and all modules are referenced"
+            );
+        }
+
+        let mut lines = Vec::new();
+
+        for (destination_id, source_ids) in &self.unknown_destinations {
+            lines.push(format!(
+                "Unknown destination {} (referenced by {})",
+                destination_id,
+                source_ids.join(", ")
+            ));
+        }
+
+        for module_id in &self.unreferenced_modules {
+            lines.push(format!("Unreferenced module {}", module_id));
+        }
+
+        write!(f, "{}", lines.join("\n"))
     }
 }
 
@@ -245,43 +705,64 @@ impl FromStr for PulseMachine {
     type Err = Box<dyn Error>;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        let mut modules: HashMap<String, Box<dyn Module>> = HashMap::new();
-        let mut conjunctions: Vec<Conjunction> = Vec::new();
+        Self::parse(string, &ModuleRegistry::default())
+    }
+}
 
-        for line in string.lines() {
-            if line.starts_with(Broadcaster::BROADCASTER_ID) {
-                let broadcaster = Broadcaster::from_str(line)?;
-                modules.insert(String::from(broadcaster.id()), Box::new(broadcaster));
-            } else if line.starts_with('%') {
-                let flip_flop = FlipFlop::from_str(line)?;
-                modules.insert(String::from(flip_flop.id()), Box::new(flip_flop));
-            } else if line.starts_with('&') {
-                let conjunction = Conjunction::from_str(line)?;
-                conjunctions.push(conjunction);
-            } else {
-                return Err("Could not parse line".into());
+type ModuleConstructor = fn(&str) -> Box<dyn Module>;
+
+struct ModuleDefinition {
+    module: Box<dyn Module>,
+    destination_ids: Vec<String>,
+}
+
+struct ModuleRegistry {
+    constructors: Vec<(String, ModuleConstructor)>,
+}
+
+impl ModuleRegistry {
+    fn register(&mut self, prefix: &str, constructor: ModuleConstructor) {
+        self.constructors.retain(|(p, _)| p != prefix);
+        self.constructors.push((String::from(prefix), constructor));
+    }
+
+    fn parse_module(&self, line: &str) -> Result<ModuleDefinition, Box<dyn Error>> {
+        if let [name, destinations] = line.split(" -> ").collect::<Vec<&str>>().as_slice() {
+            let destinations: Vec<String> = destinations.split(", ").map(String::from).collect();
+
+            if *name == Broadcaster::BROADCASTER_ID {
+                return Ok(ModuleDefinition {
+                    module: Box::new(Broadcaster),
+                    destination_ids: destinations,
+                });
             }
-        }
 
-        for conjunction in conjunctions.iter_mut() {
-            let inputs: Vec<String> = modules
-                .values()
-                .filter(|module| {
-                    module
-                        .destinations()
-                        .contains(&String::from(conjunction.id()))
+            // Prefer the longest matching prefix so that, say, "%%" can coexist with "%"
+            self.constructors
+                .iter()
+                .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map(|(prefix, constructor)| ModuleDefinition {
+                    module: constructor(&name[prefix.len()..]),
+                    destination_ids: destinations,
                 })
-                .map(|module| String::from(module.id()))
-                .collect();
-
-            inputs.iter().for_each(|input| conjunction.add_input(input));
+                .ok_or_else(|| format!("Unrecognized module type: {}", name).into())
+        } else {
+            Err("Could not parse module definition".into())
         }
+    }
+}
 
-        conjunctions.into_iter().for_each(|conjunction| {
-            modules.insert(String::from(conjunction.id()), Box::new(conjunction));
-        });
+impl Default for ModuleRegistry {
+    fn default() -> Self {
+        let mut registry = ModuleRegistry {
+            constructors: Vec::new(),
+        };
+
+        registry.register("%", |id| Box::new(FlipFlop::new(id)));
+        registry.register("&", |id| Box::new(Conjunction::new(id)));
 
-        Ok(PulseMachine { modules })
+        registry
     }
 }
 
@@ -294,19 +775,18 @@ impl Display for PulseMachine {
         writeln!(f, "\tnode [shape=ellipse];")?;
         writeln!(f)?;
 
-        for (id, module) in &self.modules {
-            for destination in module.destinations() {
-                if let Some(destination_module) = self.modules.get(destination) {
-                    writeln!(
-                        f,
-                        "\t\"{}{}\" -> \"{}{}\";",
-                        module.prefix(),
-                        id,
-                        destination_module.prefix(),
-                        destination_module.id()
-                    )?;
-                } else {
-                    writeln!(f, "\t\"{}{}\" -> \"{}\";", module.prefix(), id, destination)?;
+        let label = |index: usize| {
+            if let Some(module) = &self.modules[index] {
+                format!("{}{}", module.prefix(), self.ids[index])
+            } else {
+                self.ids[index].clone()
+            }
+        };
+
+        for (source, module) in self.modules.iter().enumerate() {
+            if module.is_some() {
+                for &destination in &self.destinations[source] {
+                    writeln!(f, "\t\"{}\" -> \"{}\";", label(source), label(destination))?;
                 }
             }
         }
@@ -319,23 +799,43 @@ impl Display for PulseMachine {
 
 trait Module {
     fn id(&self) -> &str;
-    fn destinations(&self) -> &[String];
-    fn handle_pulse(&mut self, pulse: Pulse, source: &str) -> Vec<(String, Pulse)>;
-    fn state(&self) -> String;
+    fn handle_pulse(&mut self, pulse: Pulse, source: usize) -> Option<Pulse>;
     fn prefix(&self) -> String;
+
+    fn state(&self) -> ModuleState {
+        ModuleState::Stateless
+    }
+
+    fn restore(&mut self, state: &ModuleState) -> Result<(), Box<dyn Error>> {
+        match state {
+            ModuleState::Stateless => Ok(()),
+            _ => Err(format!("Unexpected state for module {}", self.id()).into()),
+        }
+    }
+
+    fn add_input(&mut self, _input: usize, _input_id: &str) {}
+
+    // Modules with state should override this to append their state without building a snapshot
+    fn append_state_key(&self, key: &mut StateKey) {
+        match self.state() {
+            ModuleState::Stateless => {}
+            ModuleState::FlipFlop { on } => key.push(on),
+            ModuleState::Conjunction { inputs } => inputs
+                .values()
+                .for_each(|&pulse| key.push(pulse == Pulse::High)),
+        }
+    }
 }
 
 struct FlipFlop {
     id: String,
-    destinations: Vec<String>,
     on: bool,
 }
 
 impl FlipFlop {
-    fn new(id: &str, destinations: Vec<String>) -> Self {
+    fn new(id: &str) -> Self {
         Self {
             id: String::from(id),
-            destinations,
             on: false,
         }
     }
@@ -346,33 +846,30 @@ impl Module for FlipFlop {
         &self.id
     }
 
-    fn destinations(&self) -> &[String] {
-        &self.destinations
-    }
-
-    fn handle_pulse(&mut self, pulse: Pulse, _: &str) -> Vec<(String, Pulse)> {
-        let mut pulses = Vec::with_capacity(1);
-
+    fn handle_pulse(&mut self, pulse: Pulse, _: usize) -> Option<Pulse> {
         if pulse == Pulse::Low {
             self.on = !self.on;
 
-            let outbound_pulse = if self.on { Pulse::High } else { Pulse::Low };
-
-            pulses.extend(
-                self.destinations
-                    .iter()
-                    .map(|destination| (destination.clone(), outbound_pulse)),
-            );
+            Some(if self.on { Pulse::High } else { Pulse::Low })
+        } else {
+            None
         }
+    }
+
+    fn state(&self) -> ModuleState {
+        ModuleState::FlipFlop { on: self.on }
+    }
 
-        pulses
+    fn append_state_key(&self, key: &mut StateKey) {
+        key.push(self.on);
     }
 
-    fn state(&self) -> String {
-        if self.on {
-            String::from("on")
+    fn restore(&mut self, state: &ModuleState) -> Result<(), Box<dyn Error>> {
+        if let ModuleState::FlipFlop { on } = state {
+            self.on = *on;
+            Ok(())
         } else {
-            String::from("off")
+            Err(format!("Unexpected state for flip-flop {}", self.id).into())
         }
     }
 
@@ -381,43 +878,24 @@ impl Module for FlipFlop {
     }
 }
 
-impl FromStr for FlipFlop {
-    type Err = Box<dyn Error>;
-
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
-        if let Some(definition) = string.strip_prefix('%') {
-            if let [id, destinations] = definition.split(" -> ").collect::<Vec<&str>>().as_slice() {
-                let destinations: Vec<String> =
-                    destinations.split(", ").map(String::from).collect();
-
-                Ok(FlipFlop::new(id, destinations))
-            } else {
-                Err("Could not parse flip-flip definition".into())
-            }
-        } else {
-            Err("String does not begin with a '%'".into())
-        }
-    }
-}
-
 struct Conjunction {
     id: String,
-    destinations: Vec<String>,
-    inputs: HashMap<String, Pulse>,
+    inputs: Vec<usize>,
+    input_ids: Vec<String>,
+    memory: Vec<Pulse>,
+    high_inputs: usize,
 }
 
 impl Conjunction {
-    fn new(id: &str, destinations: Vec<String>) -> Self {
+    fn new(id: &str) -> Self {
         Self {
             id: String::from(id),
-            destinations,
-            inputs: HashMap::new(),
+            inputs: Vec::new(),
+            input_ids: Vec::new(),
+            memory: Vec::new(),
+            high_inputs: 0,
         }
     }
-
-    fn add_input(&mut self, input_id: &str) {
-        self.inputs.insert(String::from(input_id), Pulse::Low);
-    }
 }
 
 impl Module for Conjunction {
@@ -425,64 +903,74 @@ impl Module for Conjunction {
         &self.id
     }
 
-    fn destinations(&self) -> &[String] {
-        &self.destinations
-    }
+    fn handle_pulse(&mut self, pulse: Pulse, source: usize) -> Option<Pulse> {
+        if let Some(i) = self.inputs.iter().position(|&input| input == source) {
+            match (self.memory[i], pulse) {
+                (Pulse::Low, Pulse::High) => self.high_inputs += 1,
+                (Pulse::High, Pulse::Low) => self.high_inputs -= 1,
+                _ => {}
+            }
 
-    fn handle_pulse(&mut self, pulse: Pulse, source: &str) -> Vec<(String, Pulse)> {
-        self.inputs.insert(String::from(source), pulse);
+            self.memory[i] = pulse;
+        }
 
-        let outbound_pulse = if self.inputs.values().all(|pulse| pulse == &Pulse::High) {
+        Some(if self.high_inputs == self.inputs.len() {
             Pulse::Low
         } else {
             Pulse::High
-        };
+        })
+    }
 
-        self.destinations
-            .iter()
-            .map(|destination| (destination.clone(), outbound_pulse))
-            .collect()
+    fn state(&self) -> ModuleState {
+        ModuleState::Conjunction {
+            inputs: self
+                .input_ids
+                .iter()
+                .cloned()
+                .zip(self.memory.iter().copied())
+                .collect(),
+        }
     }
 
-    fn state(&self) -> String {
-        let mut sorted_inputs: Vec<String> = self
-            .inputs
-            .iter()
-            .map(|(id, pulse)| format!("{}={}", id, pulse))
-            .collect();
+    fn restore(&mut self, state: &ModuleState) -> Result<(), Box<dyn Error>> {
+        if let ModuleState::Conjunction { inputs } = state {
+            if inputs.len() != self.input_ids.len()
+                || !self.input_ids.iter().all(|id| inputs.contains_key(id))
+            {
+                return Err(format!("Mismatched inputs for conjunction {}", self.id).into());
+            }
 
-        sorted_inputs.sort();
+            self.memory = self.input_ids.iter().map(|id| inputs[id]).collect();
+            self.high_inputs = self
+                .memory
+                .iter()
+                .filter(|&&pulse| pulse == Pulse::High)
+                .count();
 
-        sorted_inputs.join(",")
+            Ok(())
+        } else {
+            Err(format!("Unexpected state for conjunction {}", self.id).into())
+        }
     }
 
     fn prefix(&self) -> String {
         String::from("&")
     }
-}
 
-impl FromStr for Conjunction {
-    type Err = Box<dyn Error>;
-
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
-        if let Some(definition) = string.strip_prefix('&') {
-            if let [id, destinations] = definition.split(" -> ").collect::<Vec<&str>>().as_slice() {
-                let destinations: Vec<String> =
-                    destinations.split(", ").map(String::from).collect();
+    fn add_input(&mut self, input: usize, input_id: &str) {
+        self.inputs.push(input);
+        self.input_ids.push(String::from(input_id));
+        self.memory.push(Pulse::Low);
+    }
 
-                Ok(Conjunction::new(id, destinations))
-            } else {
-                Err("Could not parse conjunction definition".into())
-            }
-        } else {
-            Err("String does not begin with a '&'".into())
-        }
+    fn append_state_key(&self, key: &mut StateKey) {
+        self.memory
+            .iter()
+            .for_each(|&pulse| key.push(pulse == Pulse::High));
     }
 }
 
-struct Broadcaster {
-    destinations: Vec<String>,
-}
+struct Broadcaster;
 
 impl Broadcaster {
     const BROADCASTER_ID: &'static str = "broadcaster";
@@ -493,46 +981,206 @@ impl Module for Broadcaster {
         Broadcaster::BROADCASTER_ID
     }
 
-    fn destinations(&self) -> &[String] {
-        &self.destinations
+    fn handle_pulse(&mut self, pulse: Pulse, _: usize) -> Option<Pulse> {
+        Some(pulse)
     }
 
-    fn handle_pulse(&mut self, pulse: Pulse, _: &str) -> Vec<(String, Pulse)> {
-        self.destinations
-            .iter()
-            .map(|destination| (destination.clone(), pulse))
-            .collect()
+    fn prefix(&self) -> String {
+        String::new()
     }
+}
 
-    fn state(&self) -> String {
-        String::new()
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct TracedPulse {
+    source: String,
+    destination: String,
+    pulse: Pulse,
+}
+
+impl Display for TracedPulse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -{}-> {}", self.source, self.pulse, self.destination)
     }
+}
 
-    fn prefix(&self) -> String {
-        String::new()
+struct CycleAnalysis {
+    target_id: String,
+    final_conjunction_id: String,
+    chains: Vec<ChainCycle>,
+}
+
+impl CycleAnalysis {
+    // If every chain first fires after exactly one period, then all chains fire together at multiples of the least
+    // common multiple of their periods; otherwise, the phases would need to be lined up by other means
+    fn presses(&self) -> Option<u64> {
+        if self.chains.iter().all(|chain| chain.phase == chain.period) {
+            self.chains
+                .iter()
+                .map(|chain| chain.period)
+                .reduce(least_common_multiple)
+        } else {
+            None
+        }
     }
 }
 
-impl FromStr for Broadcaster {
-    type Err = Box<dyn Error>;
+impl Display for CycleAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} is fed by conjunction {}, which sends a low pulse only after the most recent pulse from each of its {} \
+            inputs was high.",
+            self.target_id,
+            self.final_conjunction_id,
+            self.chains.len()
+        )?;
+
+        for chain in &self.chains {
+            writeln!(
+                f,
+                "  {} ({} modules): first sends a high pulse on press {}, then every {} presses",
+                chain.feeder_id,
+                chain.module_ids.len(),
+                chain.phase,
+                chain.period
+            )?;
+        }
 
-    fn from_str(string: &str) -> Result<Self, Self::Err> {
-        if let Some(destinations) = string.strip_prefix("broadcaster -> ") {
-            Ok(Broadcaster {
-                destinations: destinations.split(", ").map(String::from).collect(),
-            })
+        let periods: Vec<String> = self
+            .chains
+            .iter()
+            .map(|chain| chain.period.to_string())
+            .collect();
+
+        if let Some(presses) = self.presses() {
+            write!(
+                f,
+                "Each sub-circuit fires on multiples of its period, so they first fire together after \
+                lcm({}) = {} presses.",
+                periods.join(", "),
+                presses
+            )
         } else {
-            Err("Could not parse broadcaster string".into())
+            write!(
+                f,
+                "Not every sub-circuit fires on multiples of its period, so the least common multiple of the periods \
+                ({}) does not give the answer.",
+                periods.join(", ")
+            )
         }
     }
 }
 
+struct ChainCycle {
+    feeder_id: String,
+    module_ids: Vec<String>,
+    phase: u64,
+    period: u64,
+}
+
+// Use the Euclidean Algorithm to find the GCD
+fn greatest_common_divisor(a: u64, b: u64) -> u64 {
+    if a == 0 {
+        b
+    } else if b == 0 {
+        a
+    } else {
+        let max = cmp::max(a, b);
+        let min = cmp::min(a, b);
+
+        greatest_common_divisor(min, max % min)
+    }
+}
+
+fn least_common_multiple(a: u64, b: u64) -> u64 {
+    (a * b) / greatest_common_divisor(a, b)
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PulseDirection {
+    Sent,
+    Received,
+}
+
+impl FromStr for PulseDirection {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "sent" => Ok(PulseDirection::Sent),
+            "received" => Ok(PulseDirection::Received),
+            _ => Err("Unrecognized pulse direction".into()),
+        }
+    }
+}
+
+impl Display for PulseDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PulseDirection::Sent => "sent",
+                PulseDirection::Received => "received",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+struct MachineState {
+    modules: BTreeMap<String, ModuleState>,
+}
+
+// Module states packed into bits in module order (flip-flops contribute one bit, conjunctions one
+// bit per input); only meaningful when compared against keys from the same machine
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+struct StateKey {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl StateKey {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+
+        if bit {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+
+        self.len += 1;
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModuleState {
+    Stateless,
+    FlipFlop { on: bool },
+    Conjunction { inputs: BTreeMap<String, Pulse> },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 enum Pulse {
     Low,
     High,
 }
 
+impl FromStr for Pulse {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "low" => Ok(Pulse::Low),
+            "high" => Ok(Pulse::High),
+            _ => Err("Unrecognized pulse".into()),
+        }
+    }
+}
+
 impl Display for Pulse {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -579,4 +1227,252 @@ mod test {
             .pulses(1000)
         );
     }
+
+    #[test]
+    fn test_trace() {
+        let mut pulse_machine = PulseMachine::from_str(indoc! {"
+            broadcaster -> a, b, c
+            %a -> b
+            %b -> c
+            %c -> inv
+            &inv -> a
+        "})
+        .unwrap();
+
+        pulse_machine.enable_trace();
+        pulse_machine.handle_button_press();
+
+        let trace: Vec<String> = pulse_machine
+            .take_trace()
+            .iter()
+            .map(|traced_pulse| traced_pulse.to_string())
+            .collect();
+
+        assert_eq!(
+            indoc! {"
+                button -low-> broadcaster
+                broadcaster -low-> a
+                broadcaster -low-> b
+                broadcaster -low-> c
+                a -high-> b
+                b -high-> c
+                c -high-> inv
+                inv -low-> a
+                a -low-> b
+                b -low-> c
+                c -low-> inv
+                inv -high-> a
+            "}
+            .lines()
+            .collect::<Vec<&str>>(),
+            trace
+        );
+
+        assert!(pulse_machine.take_trace().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut pulse_machine = PulseMachine::from_str(indoc! {"
+            broadcaster -> a
+            %a -> inv, con
+            &inv -> b
+            %b -> con
+            &con -> output
+        "})
+        .unwrap();
+
+        let initial_state = pulse_machine.snapshot();
+        let initial_state_key = pulse_machine.state_key();
+
+        pulse_machine.handle_button_press();
+        assert_ne!(initial_state_key, pulse_machine.state_key());
+        let checkpoint: MachineState =
+            serde_json::from_str(&serde_json::to_string(&pulse_machine.snapshot()).unwrap())
+                .unwrap();
+
+        assert_ne!(initial_state, checkpoint);
+
+        let pulses_after_checkpoint = pulse_machine.handle_button_press();
+
+        pulse_machine.restore(&checkpoint).unwrap();
+        assert_eq!(checkpoint, pulse_machine.snapshot());
+        assert_eq!(pulses_after_checkpoint, pulse_machine.handle_button_press());
+
+        pulse_machine.restore(&initial_state).unwrap();
+        assert_eq!(initial_state, pulse_machine.snapshot());
+        assert_eq!(initial_state_key, pulse_machine.state_key());
+        assert_eq!((4250, 2750), pulse_machine.pulses(1000));
+    }
+
+    #[test]
+    fn test_presses_until() {
+        let pulse_machine_string = indoc! {"
+            broadcaster -> a
+            %a -> inv, con
+            &inv -> b
+            %b -> con
+            &con -> output
+        "};
+
+        let presses_until = |module_id, direction, pulse| {
+            PulseMachine::from_str(pulse_machine_string)
+                .unwrap()
+                .presses_until(module_id, direction, pulse)
+        };
+
+        assert_eq!(
+            Some(1),
+            presses_until("output", PulseDirection::Received, Pulse::Low)
+        );
+        assert_eq!(
+            Some(2),
+            presses_until("inv", PulseDirection::Sent, Pulse::High)
+        );
+        assert_eq!(
+            Some(3),
+            presses_until("b", PulseDirection::Sent, Pulse::Low)
+        );
+        assert_eq!(
+            None,
+            presses_until("broadcaster", PulseDirection::Sent, Pulse::High)
+        );
+        assert_eq!(
+            None,
+            presses_until("nope", PulseDirection::Received, Pulse::Low)
+        );
+    }
+
+    #[test]
+    fn test_cycle_analysis() {
+        // Two independent counters: one that resets after 3 presses and one that resets after 5
+        let pulse_machine_string = indoc! {"
+            broadcaster -> a0, b0
+            %a0 -> a1, ca
+            %a1 -> ca
+            &ca -> fa, a0
+            &fa -> final
+            %b0 -> b1, cb
+            %b1 -> b2
+            %b2 -> cb
+            &cb -> fb, b0, b1
+            &fb -> final
+            &final -> rx
+        "};
+
+        let analysis = PulseMachine::from_str(pulse_machine_string)
+            .unwrap()
+            .cycle_analysis("rx", 1000)
+            .unwrap();
+
+        assert_eq!("final", analysis.final_conjunction_id);
+
+        assert_eq!(
+            vec![(String::from("fa"), 4, 3, 3), (String::from("fb"), 5, 5, 5)],
+            analysis
+                .chains
+                .iter()
+                .map(|chain| (
+                    chain.feeder_id.clone(),
+                    chain.module_ids.len(),
+                    chain.phase,
+                    chain.period
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(Some(15), analysis.presses());
+
+        assert_eq!(
+            Some(15),
+            PulseMachine::from_str(pulse_machine_string)
+                .unwrap()
+                .presses_until("rx", PulseDirection::Received, Pulse::Low)
+        );
+    }
+
+    #[test]
+    fn test_parse_order_independence() {
+        let pulse_machine = PulseMachine::from_str(indoc! {"
+            &con -> output
+            %b -> con
+            &inv -> b
+            %a -> inv, con
+            broadcaster -> a
+        "})
+        .unwrap();
+
+        assert_eq!((4250, 2750), pulse_machine.pulses(1000));
+    }
+
+    #[test]
+    fn test_validation_report() {
+        let (_, report) = PulseMachine::parse_with_report(
+            indoc! {"
+                broadcaster -> a
+                %a -> inv, con
+                &inv -> b, nowhere
+                %b -> con
+                &con -> output
+                %lonely -> a
+            "},
+            &ModuleRegistry::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                (String::from("nowhere"), vec![String::from("inv")]),
+                (String::from("output"), vec![String::from("con")]),
+            ],
+            report.unknown_destinations
+        );
+
+        assert_eq!(vec![String::from("lonely")], report.unreferenced_modules);
+    }
+
+    struct Inverter {
+        id: String,
+    }
+
+    impl Module for Inverter {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn handle_pulse(&mut self, pulse: Pulse, _: usize) -> Option<Pulse> {
+            Some(match pulse {
+                Pulse::Low => Pulse::High,
+                Pulse::High => Pulse::Low,
+            })
+        }
+
+        fn prefix(&self) -> String {
+            String::from("!")
+        }
+    }
+
+    #[test]
+    fn test_custom_module_type() {
+        let mut registry = ModuleRegistry::default();
+        registry.register("!", |id| {
+            Box::new(Inverter {
+                id: String::from(id),
+            })
+        });
+
+        let pulse_machine_string = indoc! {"
+            broadcaster -> inv
+            !inv -> output
+        "};
+
+        assert!(PulseMachine::from_str(pulse_machine_string).is_err());
+
+        assert_eq!(
+            (2000, 1000),
+            PulseMachine::parse(pulse_machine_string, &registry)
+                .unwrap()
+                .pulses(1000)
+        );
+    }
 }
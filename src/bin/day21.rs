@@ -1,7 +1,9 @@
+use advent_of_code_2023::grid_render::{write_grid, Color, Glyph};
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
@@ -17,31 +19,205 @@ fn main() -> Result<(), Box<dyn Error>> {
             GardenMap::from_str(garden_map_string.as_str())?
         };
 
-        println!(
-            "Garden plots reachable in 64 steps: {}",
-            garden_map.reachable_garden_plots(64)
-        );
+        let starts: Vec<(usize, usize)> = {
+            let mut starts = Vec::new();
+
+            for (i, _) in args.iter().enumerate().filter(|(_, arg)| *arg == "--start") {
+                let (x, y) = args
+                    .get(i + 1)
+                    .and_then(|start| start.split_once(','))
+                    .ok_or("Start position must be given as X,Y")?;
+
+                starts.push((x.parse()?, y.parse()?));
+            }
+
+            starts
+        };
+
+        let garden_map = if let Some(&(x, y)) = starts.first() {
+            garden_map.with_start(x, y)?
+        } else {
+            garden_map
+        };
+
+        let mut start_indices = vec![garden_map.start_index];
+
+        for &(x, y) in starts.iter().skip(1) {
+            start_indices.push(garden_map.index(x, y)?);
+        }
+
+        let infinite = args.iter().skip(2).any(|arg| arg == "--infinite");
+        let distance_grid = garden_map.distance_grid();
+
+        if let Some(max_steps) = args
+            .iter()
+            .position(|arg| arg == "--curve")
+            .and_then(|i| args.get(i + 1))
+        {
+            let curve = if infinite {
+                garden_map.infinite_reachable_garden_plot_curve(max_steps.parse()?)
+            } else {
+                distance_grid.reachable_garden_plot_curve(max_steps.parse()?)
+            };
+
+            println!("steps,reachable");
+
+            for (steps, reachable) in curve.iter().enumerate() {
+                println!("{},{}", steps, reachable);
+            }
+
+            return Ok(());
+        }
+
+        if let Some(target_steps) = args
+            .iter()
+            .position(|arg| arg == "--extrapolate")
+            .and_then(|i| args.get(i + 1))
+        {
+            let target_steps = target_steps.parse()?;
+
+            println!(
+                "Garden plots reachable in {} steps on an infinite map (extrapolated): {}",
+                target_steps,
+                garden_map.extrapolated_reachable_garden_plots(target_steps)?
+            );
+
+            return Ok(());
+        }
+
+        let steps: Vec<u32> = {
+            let mut steps = Vec::new();
+
+            for (i, _) in args.iter().enumerate().filter(|(_, arg)| *arg == "--steps") {
+                let values = args.get(i + 1).ok_or("--steps requires a value")?;
+
+                for value in values.split(',') {
+                    steps.push(value.parse()?);
+                }
+            }
+
+            if steps.is_empty() {
+                steps.push(64);
+            }
+
+            steps
+        };
+
+        let visualize = args.iter().skip(2).any(|arg| arg == "--visualize");
+
+        for steps in steps {
+            if visualize {
+                println!("{}", garden_map.reachability_map(&distance_grid, steps));
+            }
+
+            if infinite {
+                println!(
+                    "Garden plots reachable in {} steps on an infinite map: {}",
+                    steps,
+                    garden_map.infinite_reachable_garden_plots(steps)
+                );
+            } else if start_indices.len() > 1 {
+                let (reachable, _) =
+                    garden_map.reachable_garden_plots_from_all(&start_indices, steps);
+
+                println!(
+                    "Garden plots reachable in {} steps from any of {} start positions: {}",
+                    steps,
+                    start_indices.len(),
+                    reachable
+                );
+            } else {
+                println!(
+                    "Garden plots reachable in {} steps: {}",
+                    steps,
+                    distance_grid.reachable_garden_plots(steps)
+                );
+            }
+        }
 
         Ok(())
     } else {
-        Err("Usage: day21 INPUT_FILE_PATH".into())
+        Err(
+            "Usage: day21 INPUT_FILE_PATH [--steps N[,N...]]... [--start X,Y] [--infinite] [--curve MAX_STEPS] [--extrapolate STEPS] [--visualize]"
+                .into(),
+        )
     }
 }
 
 struct GardenMap {
     width: usize,
     tiles: Vec<Tile>,
+    start_index: usize,
 }
 
 impl GardenMap {
-    fn reachable_garden_plots(&self, steps: u32) -> u32 {
-        let start_index = self
-            .tiles
+    fn with_start(self, x: usize, y: usize) -> Result<Self, Box<dyn Error>> {
+        let start_index = self.index(x, y)?;
+
+        Ok(GardenMap {
+            start_index,
+            ..self
+        })
+    }
+
+    fn index(&self, x: usize, y: usize) -> Result<usize, Box<dyn Error>> {
+        if x >= self.width || y >= self.height() {
+            return Err(format!("Start position ({}, {}) is outside the map", x, y).into());
+        }
+
+        let index = (y * self.width) + x;
+
+        if self.tiles[index] == Tile::Rock {
+            return Err(format!("Start position ({}, {}) is a rock", x, y).into());
+        }
+
+        Ok(index)
+    }
+
+    fn distance_grid(&self) -> DistanceGrid {
+        self.distance_grid_from(self.start_index)
+    }
+
+    fn reachable_garden_plots_from_all(
+        &self,
+        start_indices: &[usize],
+        steps: u32,
+    ) -> (u32, Vec<DistanceGrid>) {
+        let distance_grids: Vec<DistanceGrid> = start_indices
             .iter()
-            .position(|t| t == &Tile::Start)
-            .expect("Map must have a start tile");
+            .map(|&start_index| self.distance_grid_from(start_index))
+            .collect();
+
+        // We can't just take the minimum distance from any start tile, since start tiles with
+        // different parities can reach the same plot in an even or odd number of steps
+        let reachable = (0..self.tiles.len())
+            .filter(|&index| {
+                distance_grids.iter().any(|distance_grid| {
+                    let distance = distance_grid.distances[index];
+                    distance <= steps && distance % 2 == steps % 2
+                })
+            })
+            .count() as u32;
+
+        (reachable, distance_grids)
+    }
+
+    fn reachability_map<'a>(
+        &'a self,
+        distance_grid: &'a DistanceGrid,
+        steps: u32,
+    ) -> ReachabilityMap<'a> {
+        ReachabilityMap {
+            garden_map: self,
+            distance_grid,
+            steps,
+        }
+    }
 
+    fn distance_grid_from(&self, start_index: usize) -> DistanceGrid {
         let mut distances = vec![u32::MAX; self.tiles.len()];
+        distances[start_index] = 0;
+
         let mut exploration_queue = BinaryHeap::new();
 
         exploration_queue.push(ExplorationQueueEntry::new(start_index, 0));
@@ -64,13 +240,82 @@ impl GardenMap {
             }
         }
 
-        // If a tile is within the maximum distance, the elf can just keep going back and forth
-        // from an adjacent tile to "run out the clock" and hit the target number of steps as long
-        // as the distance is even/odd, matching whether the target number of steps is even/odd.
+        DistanceGrid {
+            width: self.width,
+            distances,
+        }
+    }
+
+    fn infinite_reachable_garden_plots(&self, steps: u32) -> u64 {
+        self.infinite_distances(steps)
+            .values()
+            .filter(|&&distance| distance % 2 == steps % 2)
+            .count() as u64
+    }
+
+    fn infinite_reachable_garden_plot_curve(&self, max_steps: u32) -> Vec<u64> {
+        reachable_garden_plot_curve(self.infinite_distances(max_steps).into_values(), max_steps)
+    }
+
+    fn extrapolated_reachable_garden_plots(&self, steps: u64) -> Result<u64, Box<dyn Error>> {
+        if self.width != self.height() {
+            return Err("Extrapolation requires a square map".into());
+        }
+
+        // Once the reachable region has spread past the first few copies of the map, it grows by
+        // whole copies every `width` steps, and so the count grows quadratically when sampled at
+        // that interval. We sample at the same offset within a map-width as the target.
+        let period = self.width as u64;
+        let offset = steps % period;
+
+        if steps < offset + (period * 3) {
+            return Ok(self.infinite_reachable_garden_plots(steps as u32));
+        }
+
+        let curve = self.infinite_reachable_garden_plot_curve((offset + (period * 3)) as u32);
+
+        let samples: Vec<(u64, u64)> = (0..4)
+            .map(|i| offset + (period * i))
+            .map(|sample_steps| (sample_steps, curve[sample_steps as usize]))
+            .collect();
+
+        extrapolate_quadratic([samples[0], samples[1], samples[2]], samples[3], steps)
+    }
+
+    fn infinite_distances(&self, max_steps: u32) -> HashMap<(i64, i64), u32> {
+        // The map repeats infinitely in every direction, so positions are unbounded and we look up
+        // tiles modulo the map's dimensions. Every step has the same cost, so a plain
+        // breadth-first search finds shortest distances.
+        let start = (
+            (self.start_index % self.width) as i64,
+            (self.start_index / self.width) as i64,
+        );
+
+        let mut distances = HashMap::from([(start, 0)]);
+        let mut exploration_queue = VecDeque::from([(start, 0)]);
+
+        while let Some(((x, y), distance)) = exploration_queue.pop_front() {
+            if distance == max_steps {
+                continue;
+            }
+
+            for neighbor in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if self.infinite_tile(neighbor) != &Tile::Rock && !distances.contains_key(&neighbor)
+                {
+                    distances.insert(neighbor, distance + 1);
+                    exploration_queue.push_back((neighbor, distance + 1));
+                }
+            }
+        }
+
         distances
-            .iter()
-            .filter(|&&distance| distance <= steps && distance % 2 == steps % 2)
-            .count() as u32
+    }
+
+    fn infinite_tile(&self, (x, y): (i64, i64)) -> &Tile {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height() as i64) as usize;
+
+        &self.tiles[(y * self.width) + x]
     }
 
     fn neighboring_garden_plot_indices(&self, index: usize) -> Vec<usize> {
@@ -116,10 +361,25 @@ impl FromStr for GardenMap {
                 .map(Tile::try_from)
                 .collect::<Result<_, _>>()?;
 
-            if tiles.len() % width == 0 {
-                Ok(GardenMap { width, tiles })
-            } else {
-                Err("Non-rectangular garden map".into())
+            if !tiles.len().is_multiple_of(width) {
+                return Err("Non-rectangular garden map".into());
+            }
+
+            let start_indices: Vec<usize> = tiles
+                .iter()
+                .enumerate()
+                .filter(|(_, tile)| tile == &&Tile::Start)
+                .map(|(i, _)| i)
+                .collect();
+
+            match start_indices.as_slice() {
+                [start_index] => Ok(GardenMap {
+                    width,
+                    tiles,
+                    start_index: *start_index,
+                }),
+                [] => Err("Map must have a start tile".into()),
+                _ => Err("Map must have exactly one start tile".into()),
             }
         } else {
             Err("String contains no lines".into())
@@ -127,6 +387,154 @@ impl FromStr for GardenMap {
     }
 }
 
+struct DistanceGrid {
+    width: usize,
+    distances: Vec<u32>,
+}
+
+impl DistanceGrid {
+    fn distance(&self, x: usize, y: usize) -> Option<u32> {
+        if x >= self.width {
+            return None;
+        }
+
+        self.distances
+            .get((y * self.width) + x)
+            .filter(|&&distance| distance != u32::MAX)
+            .copied()
+    }
+
+    fn reachable_garden_plots(&self, steps: u32) -> u32 {
+        // If a tile is within the maximum distance, the elf can just keep going back and forth
+        // from an adjacent tile to "run out the clock" and hit the target number of steps as long
+        // as the distance is even/odd, matching whether the target number of steps is even/odd.
+        self.distances
+            .iter()
+            .filter(|&&distance| distance <= steps && distance % 2 == steps % 2)
+            .count() as u32
+    }
+
+    fn reachable_garden_plot_curve(&self, max_steps: u32) -> Vec<u64> {
+        reachable_garden_plot_curve(
+            self.distances
+                .iter()
+                .copied()
+                .filter(|&distance| distance <= max_steps),
+            max_steps,
+        )
+    }
+}
+
+fn reachable_garden_plot_curve(distances: impl Iterator<Item = u32>, max_steps: u32) -> Vec<u64> {
+    // Tally how many plots are first reached at each distance; a plot at a given distance is
+    // reachable in any number of steps at least that large with the same parity
+    let mut first_reached = vec![0u64; max_steps as usize + 1];
+
+    distances.for_each(|distance| first_reached[distance as usize] += 1);
+
+    let mut curve = Vec::with_capacity(first_reached.len());
+
+    for steps in 0..first_reached.len() {
+        let previous = if steps >= 2 { curve[steps - 2] } else { 0 };
+        curve.push(previous + first_reached[steps]);
+    }
+
+    curve
+}
+
+fn extrapolate_quadratic(
+    samples: [(u64, u64); 3],
+    check: (u64, u64),
+    target: u64,
+) -> Result<u64, Box<dyn Error>> {
+    let [(x0, y0), (x1, y1), (x2, y2)] = samples;
+
+    if x1 <= x0 || x2 < x1 || check.0 < x2 || x2 - x1 != x1 - x0 || check.0 - x2 != x1 - x0 {
+        return Err("Samples must be evenly spaced and in increasing order".into());
+    }
+
+    let interval = x1 - x0;
+
+    if target < x0 || !(target - x0).is_multiple_of(interval) {
+        return Err(format!(
+            "Target {} is not a whole number of intervals past the first sample",
+            target
+        )
+        .into());
+    }
+
+    // Newton's forward differences; with evenly-spaced samples, the value `n` intervals past the
+    // first sample is y0 + n * Δ + n * (n - 1) / 2 * Δ²
+    let (y0, y1, y2) = (y0 as i128, y1 as i128, y2 as i128);
+    let first_difference = y1 - y0;
+    let second_difference = y2 - (2 * y1) + y0;
+
+    let evaluate = |n: i128| y0 + (n * first_difference) + ((n * (n - 1)) / 2 * second_difference);
+
+    if evaluate(3) != check.1 as i128 {
+        return Err(format!(
+            "Samples don't fit a quadratic; expected {} at {} steps, but found {}",
+            evaluate(3),
+            check.0,
+            check.1
+        )
+        .into());
+    }
+
+    u64::try_from(evaluate(((target - x0) / interval) as i128))
+        .map_err(|_| "Extrapolated value out of range".into())
+}
+
+struct ReachabilityMap<'a> {
+    garden_map: &'a GardenMap,
+    distance_grid: &'a DistanceGrid,
+    steps: u32,
+}
+
+impl ReachabilityMap<'_> {
+    const EVEN_COLOR: Color = Color::GREEN;
+    const ODD_COLOR: Color = Color::BLUE;
+    const ROCK_COLOR: Color = Color::GRAY;
+}
+
+impl Display for ReachabilityMap<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.garden_map.width;
+        let height = self.garden_map.tiles.len() / width;
+
+        write_grid(f, width, height, |x, y| {
+            let index = (y * width) + x;
+
+            match (
+                &self.garden_map.tiles[index],
+                self.distance_grid.distance(x, y),
+            ) {
+                (Tile::Rock, _) => Glyph::colored('#', Self::ROCK_COLOR),
+                (_, Some(distance)) if distance <= self.steps => {
+                    // Plots with the same parity as the step count are the ones that are actually
+                    // reachable; the others would be reachable with one more step
+                    let color = if distance.is_multiple_of(2) {
+                        Self::EVEN_COLOR
+                    } else {
+                        Self::ODD_COLOR
+                    };
+
+                    let c = if index == self.garden_map.start_index {
+                        'S'
+                    } else if distance % 2 == self.steps % 2 {
+                        'O'
+                    } else {
+                        'o'
+                    };
+
+                    Glyph::colored(c, color)
+                }
+                _ => Glyph::plain('.'),
+            }
+        })
+    }
+}
+
 #[derive(Eq, PartialEq)]
 struct ExplorationQueueEntry {
     index: usize,
@@ -177,23 +585,124 @@ mod test {
     use super::*;
     use indoc::indoc;
 
+    const TEST_MAP_STRING: &str = indoc! {"
+        ...........
+        .....###.#.
+        .###.##..#.
+        ..#.#...#..
+        ....#.#....
+        .##..S####.
+        .##..#...#.
+        .......##..
+        .##.#.####.
+        .##..##.##.
+        ...........
+    "};
+
     #[test]
     fn test_reachable_garden_plots() {
+        let garden_map = GardenMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert_eq!(16, garden_map.distance_grid().reachable_garden_plots(6));
+    }
+
+    #[test]
+    fn test_infinite_reachable_garden_plots() {
+        let garden_map = GardenMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert_eq!(16, garden_map.infinite_reachable_garden_plots(6));
+        assert_eq!(50, garden_map.infinite_reachable_garden_plots(10));
+        assert_eq!(1594, garden_map.infinite_reachable_garden_plots(50));
+        assert_eq!(6536, garden_map.infinite_reachable_garden_plots(100));
+
+        let curve = garden_map.infinite_reachable_garden_plot_curve(100);
+
+        assert_eq!(16, curve[6]);
+        assert_eq!(50, curve[10]);
+        assert_eq!(1594, curve[50]);
+        assert_eq!(6536, curve[100]);
+    }
+
+    #[test]
+    fn test_extrapolate_quadratic() {
+        let f = |n: u64| (3 * n * n) + (2 * n) + 1;
+
+        assert_eq!(
+            f(1000),
+            extrapolate_quadratic(
+                [(65, f(0)), (196, f(1)), (327, f(2))],
+                (458, f(3)),
+                65 + (131 * 1000)
+            )
+            .unwrap()
+        );
+
+        assert!(extrapolate_quadratic(
+            [(65, f(0)), (196, f(1)), (327, f(2))],
+            (458, f(3) + 1),
+            65 + (131 * 1000)
+        )
+        .is_err());
+
+        assert!(extrapolate_quadratic(
+            [(65, f(0)), (196, f(1)), (327, f(2))],
+            (458, f(3)),
+            66 + (131 * 1000)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_start_override() {
         let garden_map = GardenMap::from_str(indoc! {"
-            ...........
-            .....###.#.
-            .###.##..#.
-            ..#.#...#..
-            ....#.#....
-            .##..S####.
-            .##..#...#.
-            .......##..
-            .##.#.####.
-            .##..##.##.
-            ...........
+            .....
+            .....
+            ..S..
+            .....
+            .....
         "})
         .unwrap();
 
-        assert_eq!(16, garden_map.reachable_garden_plots(6));
+        assert_eq!(9, garden_map.distance_grid().reachable_garden_plots(2));
+
+        let garden_map = garden_map.with_start(0, 0).unwrap();
+        assert_eq!(4, garden_map.distance_grid().reachable_garden_plots(2));
+
+        assert!(garden_map.with_start(5, 0).is_err());
+        assert!(GardenMap::from_str("S.S\n...\n").is_err());
+    }
+
+    #[test]
+    fn test_reachable_garden_plots_from_all() {
+        let garden_map = GardenMap::from_str(indoc! {"
+            S....
+            .....
+            .....
+            .....
+            .....
+        "})
+        .unwrap();
+
+        let (reachable, distance_grids) =
+            garden_map.reachable_garden_plots_from_all(&[0, 1, 24], 2);
+
+        // Plots within two steps of (0, 0) with even parity, plus plots within two steps of
+        // (1, 0) with even parity, plus plots within two steps of (4, 4) with even parity
+        assert_eq!(4 + 5 + 4, reachable);
+
+        assert_eq!(3, distance_grids.len());
+        assert_eq!(Some(0), distance_grids[0].distance(0, 0));
+        assert_eq!(Some(1), distance_grids[1].distance(0, 0));
+        assert_eq!(Some(8), distance_grids[2].distance(0, 0));
+    }
+
+    #[test]
+    fn test_reachable_garden_plot_curve() {
+        let garden_map = GardenMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert_eq!(
+            vec![1, 2, 4, 6, 9, 13, 16],
+            garden_map.distance_grid().reachable_garden_plot_curve(6)
+        );
     }
 }
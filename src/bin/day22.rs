@@ -1,52 +1,149 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Read;
 use std::str::FromStr;
-use std::{env, iter};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
 
     if let Some(path) = args.get(1) {
-        let brick_stack: BrickStack = BufReader::new(File::open(path)?)
-            .lines()
-            .map_while(Result::ok)
-            .map(|line| Brick::from_str(line.as_str()))
-            .collect::<Result<_, _>>()?;
-
-        println!(
-            "Disintegratable bricks: {}",
-            brick_stack.removable_bricks().len()
-        );
+        let mut brick_stack_string = String::new();
+        File::open(path)?.read_to_string(&mut brick_stack_string)?;
+
+        if args.iter().skip(2).any(|arg| arg == "--validate") {
+            let diagnostics = BrickStack::diagnostics(brick_stack_string.as_str())?;
+
+            if diagnostics.is_empty() {
+                println!("No problems found");
+            } else {
+                diagnostics
+                    .iter()
+                    .for_each(|diagnostic| println!("{}", diagnostic));
+            }
+
+            return Ok(());
+        }
+
+        let brick_stack = BrickStack::from_str(brick_stack_string.as_str())?;
 
+        if args.iter().skip(2).any(|arg| arg == "--dot") {
+            print!("{}", brick_stack);
+            return Ok(());
+        }
+
+        let removable_bricks = brick_stack.removable_bricks();
+
+        println!("Disintegratable bricks: {}", removable_bricks.len());
         println!("Falling brick sum: {}", brick_stack.disintegration_sum());
 
+        if args.iter().skip(2).any(|arg| arg == "--list") {
+            let list = |brick_ids: Vec<&BrickId>| {
+                brick_ids
+                    .iter()
+                    .map(|brick_id| brick_id.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+
+            println!("Disintegratable: {}", list(removable_bricks));
+            println!("Load-bearing: {}", list(brick_stack.chaos_bricks()));
+        }
+
+        if let Some(name) = args
+            .iter()
+            .position(|arg| arg == "--falling")
+            .and_then(|i| args.get(i + 1))
+        {
+            let brick_id = brick_stack.find_brick_id(name).ok_or("No such brick")?;
+            let falling_bricks = brick_stack.falling_bricks(brick_id);
+
+            println!(
+                "Removing {} ({}) would cause {} other bricks to fall:",
+                brick_id,
+                brick_stack.brick(brick_id).unwrap(),
+                falling_bricks.len()
+            );
+
+            for falling_brick_id in &falling_bricks {
+                println!(
+                    "  {} ({})",
+                    falling_brick_id,
+                    brick_stack.brick(falling_brick_id).unwrap()
+                );
+            }
+        }
+
         Ok(())
     } else {
-        Err("Usage: day22 INPUT_FILE_PATH".into())
+        Err("Usage: day22 INPUT_FILE_PATH [--validate] [--dot] [--list] [--falling BRICK]".into())
+    }
+}
+
+// Bricks are identified by the line on which they appear in the input and, optionally, by a label
+// like the ones in the puzzle text (e.g. `1,0,1~1,2,1   <- A`)
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct BrickId {
+    line: usize,
+    label: Option<String>,
+}
+
+impl BrickId {
+    fn matches(&self, name: &str) -> bool {
+        self.label.as_deref() == Some(name) || self.line.to_string() == name
+    }
+}
+
+impl Display for BrickId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "{} (line {})", label, self.line),
+            None => write!(f, "line {}", self.line),
+        }
     }
 }
 
 #[derive(Clone)]
 struct BrickStack {
     bricks: Vec<Brick>,
+    ids: Vec<BrickId>,
+    supporters: Vec<Vec<usize>>,
 }
 
 impl BrickStack {
     fn settle_bricks(&mut self) -> usize {
-        // Reverse sort order so we can easily pop the bricks closest to the ground from the end
-        // of the list
-        self.bricks.sort_by_key(|b| std::cmp::Reverse(b.min_z()));
+        {
+            let mut order: Vec<usize> = (0..self.bricks.len()).collect();
+            order.sort_by_key(|&i| self.bricks[i].min_z());
+
+            self.bricks = order.iter().map(|&i| self.bricks[i]).collect();
+            self.ids = order.iter().map(|&i| self.ids[i].clone()).collect();
+        }
+
+        let width = self.bricks.iter().map(|b| b.max_x()).max().unwrap_or(0) as usize + 1;
+        let depth = self.bricks.iter().map(|b| b.max_y()).max().unwrap_or(0) as usize + 1;
+
+        // For each vertical column, track the height of the highest settled brick and which brick
+        // that was; a falling brick comes to rest just above the highest column in its footprint,
+        // and it's supported by whichever bricks top out at exactly that height.
+        let mut heights = vec![0; width * depth];
+        let mut top_bricks: Vec<Option<usize>> = vec![None; width * depth];
 
-        let mut settled_bricks: Vec<Brick> = Vec::with_capacity(self.bricks.len());
         let mut bricks_moved = 0;
 
-        while let Some(mut brick) = self.bricks.pop() {
-            let floor_z = settled_bricks
+        self.supporters = Vec::with_capacity(self.bricks.len());
+
+        for (i, brick) in self.bricks.iter_mut().enumerate() {
+            let columns: Vec<usize> = brick
+                .footprint()
+                .map(|(x, y)| (y as usize * width) + x as usize)
+                .collect();
+
+            let floor_z = columns
                 .iter()
-                .filter(|settled_brick| settled_brick.shares_vertical_column(&brick))
-                .map(|settled_brick| settled_brick.max_z())
+                .map(|&column| heights[column])
                 .max()
                 .unwrap_or(0);
 
@@ -54,70 +151,107 @@ impl BrickStack {
                 bricks_moved += 1;
             }
 
-            settled_bricks.push(brick);
-        }
-
-        self.bricks = settled_bricks;
-
-        bricks_moved
-    }
+            let mut supporters: Vec<usize> = columns
+                .iter()
+                .filter(|&&column| floor_z > 0 && heights[column] == floor_z)
+                .filter_map(|&column| top_bricks[column])
+                .collect();
 
-    fn removable_bricks(&self) -> HashSet<&Brick> {
-        let max_z = self
-            .bricks
-            .iter()
-            .map(|brick| brick.max_z())
-            .max()
-            .unwrap_or(0) as usize;
+            supporters.sort();
+            supporters.dedup();
 
-        let mut bricks_by_top_z: Vec<Vec<&Brick>> =
-            Vec::from_iter(iter::repeat_with(Vec::new).take(max_z + 1));
+            self.supporters.push(supporters);
 
-        self.bricks.iter().for_each(|brick| {
-            bricks_by_top_z[brick.max_z() as usize].push(brick);
-        });
+            for &column in &columns {
+                heights[column] = brick.max_z();
+                top_bricks[column] = Some(i);
+            }
+        }
 
-        let mut removable_bricks: HashSet<&Brick> = HashSet::from_iter(self.bricks.iter());
+        bricks_moved
+    }
 
-        for brick in &self.bricks {
-            let supporting_bricks: Vec<&&Brick> = bricks_by_top_z[brick.min_z() as usize - 1]
-                .iter()
-                .filter(|supporting_brick| supporting_brick.shares_vertical_column(brick))
-                .collect();
+    fn removable_bricks(&self) -> Vec<&BrickId> {
+        let mut removable = vec![true; self.bricks.len()];
 
-            if supporting_bricks.len() == 1 {
+        for supporters in &self.supporters {
+            if supporters.len() == 1 {
                 // This brick is supported only by a single brick, and so we can't remove that one
                 // supporting brick.
-                removable_bricks.remove(supporting_bricks[0]);
+                removable[supporters[0]] = false;
             }
         }
 
-        removable_bricks
+        self.bricks_by_line(|i| removable[i])
+    }
+
+    fn chaos_bricks(&self) -> Vec<&BrickId> {
+        let removable_bricks: HashSet<&BrickId> = HashSet::from_iter(self.removable_bricks());
+
+        self.bricks_by_line(|i| !removable_bricks.contains(&self.ids[i]))
+    }
+
+    fn bricks_by_line(&self, filter: impl Fn(usize) -> bool) -> Vec<&BrickId> {
+        let mut brick_ids: Vec<&BrickId> = (0..self.bricks.len())
+            .filter(|&i| filter(i))
+            .map(|i| &self.ids[i])
+            .collect();
+
+        brick_ids.sort_by_key(|brick_id| brick_id.line);
+
+        brick_ids
+    }
+
+    fn find_brick_id(&self, name: &str) -> Option<&BrickId> {
+        self.ids.iter().find(|brick_id| brick_id.matches(name))
+    }
+
+    fn brick(&self, brick_id: &BrickId) -> Option<&Brick> {
+        self.position(brick_id).map(|i| &self.bricks[i])
+    }
+
+    fn position(&self, brick_id: &BrickId) -> Option<usize> {
+        self.ids.iter().position(|id| id == brick_id)
     }
 
-    fn chaos_bricks(&self) -> HashSet<&Brick> {
-        let mut chaos_bricks = HashSet::from_iter(self.bricks.iter());
+    fn falling_bricks(&self, brick_id: &BrickId) -> Vec<BrickId> {
+        let Some(removed) = self.position(brick_id) else {
+            return Vec::new();
+        };
 
-        self.removable_bricks().iter().for_each(|b| {
-            chaos_bricks.remove(b);
-        });
+        // Bricks are stored in settling order, so every brick's supporters come before it, and a
+        // single pass upward is enough to find every brick whose supporters have all fallen
+        let mut fallen = vec![false; self.bricks.len()];
+        fallen[removed] = true;
+
+        let mut falling_bricks = Vec::new();
+
+        for i in removed + 1..self.bricks.len() {
+            if !self.supporters[i].is_empty()
+                && self.supporters[i]
+                    .iter()
+                    .all(|&supporter| fallen[supporter])
+            {
+                fallen[i] = true;
+                falling_bricks.push(self.ids[i].clone());
+            }
+        }
 
-        chaos_bricks
+        falling_bricks
     }
 
     fn disintegration_sum(&self) -> usize {
         self.chaos_bricks()
             .iter()
-            .map(|removable_brick| {
+            .map(|&brick_id| {
                 let mut cloned_stack = self.clone();
 
-                cloned_stack.bricks.remove(
-                    cloned_stack
-                        .bricks
-                        .iter()
-                        .position(|b| &b == removable_brick)
-                        .expect("Cloned stack must contain removable brick"),
-                );
+                let position = cloned_stack
+                    .position(brick_id)
+                    .expect("Cloned stack must contain removable brick");
+
+                cloned_stack.bricks.remove(position);
+                cloned_stack.ids.remove(position);
 
                 cloned_stack.settle_bricks()
             })
@@ -125,15 +259,151 @@ impl BrickStack {
     }
 }
 
-impl FromIterator<Brick> for BrickStack {
-    fn from_iter<T: IntoIterator<Item = Brick>>(iter: T) -> Self {
+impl BrickStack {
+    fn diagnostics(string: &str) -> Result<Vec<Diagnostic>, Box<dyn Error>> {
+        let (bricks, ids) = Self::parse_bricks(string)?;
+
+        Ok(Self::validate(&bricks, &ids))
+    }
+
+    fn parse_bricks(string: &str) -> Result<(Vec<Brick>, Vec<BrickId>), Box<dyn Error>> {
+        let mut bricks = Vec::new();
+        let mut ids = Vec::new();
+
+        for (i, line) in string.lines().enumerate() {
+            let (definition, label) = match line.split_once("<-") {
+                Some((definition, label)) => (definition, Some(String::from(label.trim()))),
+                None => (line, None),
+            };
+
+            bricks.push(Brick::from_str(definition.trim())?);
+            ids.push(BrickId { line: i + 1, label });
+        }
+
+        Ok((bricks, ids))
+    }
+
+    fn validate(bricks: &[Brick], ids: &[BrickId]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (brick, brick_id) in bricks.iter().zip(ids) {
+            if brick.is_degenerate() {
+                diagnostics.push(Diagnostic::Degenerate(brick_id.clone(), *brick));
+            }
+        }
+
+        let mut occupied_cells: HashMap<(u32, u32, u32), usize> = HashMap::new();
+        let mut overlapping_pairs: BTreeSet<(usize, usize)> = BTreeSet::new();
+
+        for (i, brick) in bricks.iter().enumerate() {
+            for cell in brick.cells() {
+                if let Some(&other) = occupied_cells.get(&cell) {
+                    overlapping_pairs.insert((other, i));
+                } else {
+                    occupied_cells.insert(cell, i);
+                }
+            }
+        }
+
+        for (a, b) in overlapping_pairs {
+            diagnostics.push(Diagnostic::Overlap(
+                (ids[a].clone(), bricks[a]),
+                (ids[b].clone(), bricks[b]),
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+impl FromStr for BrickStack {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (bricks, ids) = Self::parse_bricks(string)?;
+
+        // Validate bricks in their original positions, since overlapping or malformed bricks would
+        // settle in arbitrary ways
+        let diagnostics = Self::validate(&bricks, &ids);
+
+        if !diagnostics.is_empty() {
+            return Err(diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+                .into());
+        }
+
         let mut brick_stack = BrickStack {
-            bricks: iter.into_iter().collect(),
+            bricks,
+            ids,
+            supporters: Vec::new(),
         };
 
         brick_stack.settle_bricks();
 
-        brick_stack
+        Ok(brick_stack)
+    }
+}
+
+enum Diagnostic {
+    Degenerate(BrickId, Brick),
+    Overlap((BrickId, Brick), (BrickId, Brick)),
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::Degenerate(brick_id, brick) => write!(
+                f,
+                "Brick {} at {} extends along more than one axis",
+                brick_id, brick
+            ),
+            Diagnostic::Overlap((a_id, a), (b_id, b)) => {
+                write!(
+                    f,
+                    "Brick {} at {} overlaps brick {} at {}",
+                    a_id, a, b_id, b
+                )
+            }
+        }
+    }
+}
+
+impl Display for BrickStack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "digraph brick_stack {{")?;
+
+        // Lay the graph out from bottom to top so edges point upward from supporting bricks to the
+        // bricks they support, just like the physical stack
+        writeln!(f, "\trankdir=BT;")?;
+        writeln!(f, "\tnode [shape=box];")?;
+        writeln!(f)?;
+
+        for (brick, brick_id) in self.bricks.iter().zip(&self.ids) {
+            writeln!(
+                f,
+                "\tbrick{} [label=\"{}\\n{}\"];",
+                brick_id.line, brick_id, brick
+            )?;
+        }
+
+        writeln!(f)?;
+
+        for (i, supporters) in self.supporters.iter().enumerate() {
+            for &supporter in supporters {
+                writeln!(
+                    f,
+                    "\tbrick{} -> brick{};",
+                    self.ids[supporter].line, self.ids[i].line
+                )?;
+            }
+        }
+
+        writeln!(f, "}}")?;
+
+        Ok(())
     }
 }
 
@@ -151,6 +421,41 @@ impl Brick {
         self.ends[0].2.max(self.ends[1].2)
     }
 
+    fn max_x(&self) -> u32 {
+        self.ends[0].0.max(self.ends[1].0)
+    }
+
+    fn max_y(&self) -> u32 {
+        self.ends[0].1.max(self.ends[1].1)
+    }
+
+    fn footprint(&self) -> impl Iterator<Item = (u32, u32)> {
+        let min_x = self.ends[0].0.min(self.ends[1].0);
+        let max_x = self.max_x();
+        let min_y = self.ends[0].1.min(self.ends[1].1);
+        let max_y = self.max_y();
+
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    }
+
+    fn is_degenerate(&self) -> bool {
+        let [(x1, y1, z1), (x2, y2, z2)] = self.ends;
+
+        [x1 != x2, y1 != y2, z1 != z2]
+            .iter()
+            .filter(|&&differs| differs)
+            .count()
+            > 1
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        let min_z = self.min_z();
+        let max_z = self.max_z();
+
+        self.footprint()
+            .flat_map(move |(x, y)| (min_z..=max_z).map(move |z| (x, y, z)))
+    }
+
     fn lower_to(&mut self, target_z: u32) -> bool {
         debug_assert!(
             target_z <= self.min_z(),
@@ -166,32 +471,13 @@ impl Brick {
 
         delta_z > 0
     }
+}
 
-    fn shares_vertical_column(&self, other: &Brick) -> bool {
-        let self_x_range = (
-            self.ends[0].0.min(self.ends[1].0),
-            self.ends[0].0.max(self.ends[1].0),
-        );
-        let other_x_range = (
-            other.ends[0].0.min(other.ends[1].0),
-            other.ends[0].0.max(other.ends[1].0),
-        );
-
-        let self_y_range = (
-            self.ends[0].1.min(self.ends[1].1),
-            self.ends[0].1.max(self.ends[1].1),
-        );
-        let other_y_range = (
-            other.ends[0].1.min(other.ends[1].1),
-            other.ends[0].1.max(other.ends[1].1),
-        );
-
-        Self::range_overlaps(self_x_range, other_x_range)
-            && Self::range_overlaps(self_y_range, other_y_range)
-    }
+impl Display for Brick {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [(x1, y1, z1), (x2, y2, z2)] = self.ends;
 
-    fn range_overlaps(a: (u32, u32), b: (u32, u32)) -> bool {
-        a.0 <= b.1 && a.1 >= b.0
+        write!(f, "{},{},{}~{},{},{}", x1, y1, z1, x2, y2, z2)
     }
 }
 
@@ -234,23 +520,89 @@ mod test {
 
     #[test]
     fn test_removable_bricks() {
-        let brick_stack: BrickStack = TEST_BRICKS_STRING
-            .lines()
-            .map(Brick::from_str)
-            .collect::<Result<_, _>>()
-            .unwrap();
+        let brick_stack = BrickStack::from_str(TEST_BRICKS_STRING).unwrap();
 
         assert_eq!(5, brick_stack.removable_bricks().len());
     }
 
     #[test]
     fn test_disintegration_sum() {
-        let brick_stack: BrickStack = TEST_BRICKS_STRING
-            .lines()
-            .map(Brick::from_str)
-            .collect::<Result<_, _>>()
-            .unwrap();
+        let brick_stack = BrickStack::from_str(TEST_BRICKS_STRING).unwrap();
 
         assert_eq!(7, brick_stack.disintegration_sum());
     }
+
+    #[test]
+    fn test_falling_bricks() {
+        let brick_stack = BrickStack::from_str(TEST_BRICKS_STRING).unwrap();
+
+        let falling_lines = |name: &str| -> Vec<usize> {
+            brick_stack
+                .falling_bricks(brick_stack.find_brick_id(name).unwrap())
+                .iter()
+                .map(|brick_id| brick_id.line)
+                .collect()
+        };
+
+        assert_eq!(vec![2, 3, 4, 5, 6, 7], falling_lines("1"));
+        assert_eq!(Vec::<usize>::new(), falling_lines("2"));
+        assert_eq!(vec![7], falling_lines("6"));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(BrickStack::diagnostics(TEST_BRICKS_STRING)
+            .unwrap()
+            .is_empty());
+
+        let invalid_bricks_string = indoc! {"
+            1,0,1~1,2,1
+            0,1,1~2,1,1
+            0,0,3~1,1,3
+        "};
+
+        let diagnostics = BrickStack::diagnostics(invalid_bricks_string).unwrap();
+
+        assert_eq!(2, diagnostics.len());
+
+        assert!(matches!(
+            &diagnostics[0],
+            Diagnostic::Degenerate(brick_id, _) if brick_id.line == 3
+        ));
+
+        assert!(matches!(
+            &diagnostics[1],
+            Diagnostic::Overlap((a, _), (b, _)) if a.line == 1 && b.line == 2
+        ));
+
+        assert!(BrickStack::from_str(invalid_bricks_string).is_err());
+    }
+
+    #[test]
+    fn test_labeled_bricks() {
+        let brick_stack = BrickStack::from_str(indoc! {"
+            1,0,1~1,2,1   <- A
+            0,0,2~2,0,2   <- B
+            0,2,3~2,2,3   <- C
+            0,0,4~0,2,4   <- D
+            2,0,5~2,2,5   <- E
+            0,1,6~2,1,6   <- F
+            1,1,8~1,1,9   <- G
+        "})
+        .unwrap();
+
+        let labels = |brick_ids: Vec<&BrickId>| -> Vec<String> {
+            brick_ids
+                .iter()
+                .filter_map(|brick_id| brick_id.label.clone())
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["B", "C", "D", "E", "G"],
+            labels(brick_stack.removable_bricks())
+        );
+
+        assert_eq!(vec!["A", "F"], labels(brick_stack.chaos_bricks()));
+    }
 }
@@ -1,8 +1,13 @@
+use advent_of_code_2023::grid_render::{write_grid, Color, Glyph};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::env;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
+use std::time::Instant;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
@@ -15,19 +20,108 @@ fn main() -> Result<(), Box<dyn Error>> {
             HikingMap::from_str(hiking_map_string.as_str())?
         };
 
-        println!(
-            "Longest hike without climbing: {}",
-            hiking_map.longest_hike(false)
-        );
+        let algorithms = if args.iter().skip(2).any(|arg| arg == "--benchmark") {
+            vec![Algorithm::DepthFirst, Algorithm::Bitmask, Algorithm::Dag]
+        } else if let Some(algorithm) = args
+            .iter()
+            .position(|arg| arg == "--algorithm")
+            .and_then(|i| args.get(i + 1))
+        {
+            vec![Algorithm::from_str(algorithm)?]
+        } else {
+            vec![Algorithm::DepthFirst]
+        };
 
-        println!(
-            "Longest hike with climbing: {}",
-            hiking_map.longest_hike(true)
-        );
+        let slope_rules = if let Some(slope_rule) = args
+            .iter()
+            .position(|arg| arg == "--slopes")
+            .and_then(|i| args.get(i + 1))
+        {
+            vec![SlopeRule::from_str(slope_rule)?]
+        } else {
+            vec![SlopeRule::Slippery, SlopeRule::Climbable]
+        };
+
+        if args.iter().skip(2).any(|arg| arg == "--visualize") {
+            for &slope_rule in &slope_rules {
+                if let Some(route) = hiking_map.longest_hike_route(slope_rule) {
+                    println!(
+                        "Longest hike with {} slopes ({} steps):",
+                        slope_rule,
+                        route.len() - 1
+                    );
+
+                    println!("{}", hiking_map.route_map(&route));
+                } else {
+                    println!("No hike reaches the exit with {} slopes", slope_rule);
+                }
+            }
+        }
+
+        if args.iter().skip(2).any(|arg| arg == "--count") {
+            for &slope_rule in &slope_rules {
+                let summary = hiking_map.hike_summary(slope_rule)?;
+
+                println!(
+                    "Hikes with {} slopes: {} in total, {} of the longest length ({})",
+                    slope_rule, summary.total_count, summary.longest_count, summary.longest
+                );
+            }
+
+            return Ok(());
+        }
+
+        if let Some(max_steps) = args
+            .iter()
+            .position(|arg| arg == "--max-steps")
+            .and_then(|i| args.get(i + 1))
+        {
+            let max_steps = max_steps.parse()?;
+
+            for &slope_rule in &slope_rules {
+                match hiking_map.longest_hike_within(slope_rule, max_steps) {
+                    Some(longest_hike) => println!(
+                        "Longest hike with {} slopes within {} steps: {}",
+                        slope_rule, max_steps, longest_hike
+                    ),
+                    None => println!(
+                        "Exit not reachable with {} slopes within {} steps",
+                        slope_rule, max_steps
+                    ),
+                }
+            }
+
+            return Ok(());
+        }
+
+        for algorithm in algorithms {
+            for &slope_rule in &slope_rules {
+                let start = Instant::now();
+
+                let longest_hike = match algorithm {
+                    Algorithm::DepthFirst => hiking_map
+                        .longest_hike(slope_rule)
+                        .ok_or("No path to the exit")?,
+                    Algorithm::Bitmask => hiking_map.longest_hike_bitmask(slope_rule)?,
+                    Algorithm::Dag => hiking_map.longest_hike_dag(slope_rule)?,
+                };
+
+                println!(
+                    "Longest hike with {} slopes: {} ({}, {:?})",
+                    slope_rule,
+                    longest_hike,
+                    algorithm,
+                    start.elapsed()
+                );
+            }
+        }
 
         Ok(())
     } else {
-        Err("Usage: day23 INPUT_FILE_PATH".into())
+        Err(
+            "Usage: day23 INPUT_FILE_PATH [--slopes slippery|climbable|impassable] [--algorithm dfs|bitmask|dag] [--benchmark] [--visualize] [--max-steps N] [--count]"
+                .into(),
+        )
     }
 }
 
@@ -37,29 +131,34 @@ struct HikingMap {
 }
 
 impl HikingMap {
-    fn longest_hike(&self, allow_climbing: bool) -> usize {
-        // Subtract 1 from the total distance because we're counting steps, not tiles visited, and
+    fn longest_hike(&self, slope_rule: SlopeRule) -> Option<usize> {
+        // Subtract 1 from the route length because we're counting steps, not tiles visited, and
         // the starting tile doesn't count as a "step"
-        self.explore_from_state(1, vec![false; self.tiles.len()], allow_climbing)
-            .unwrap()
-            - 1
+        self.longest_hike_route(slope_rule)
+            .map(|route| route.len() - 1)
+    }
+
+    fn longest_hike_route(&self, slope_rule: SlopeRule) -> Option<Vec<usize>> {
+        self.explore_from_state(1, vec![false; self.tiles.len()], Vec::new(), slope_rule)
     }
 
     fn explore_from_state(
         &self,
         mut position: usize,
         mut explored_tiles: Vec<bool>,
-        allow_climbing: bool,
-    ) -> Option<usize> {
+        mut route: Vec<usize>,
+        slope_rule: SlopeRule,
+    ) -> Option<Vec<usize>> {
         loop {
             explored_tiles[position] = true;
+            route.push(position);
 
             if position == self.tiles.len() - 2 {
                 // We've reached the exit!
-                return Some(explored_tiles.iter().filter(|&&t| t).count());
+                return Some(route);
             }
 
-            let mut neighbors = self.explorable_neighbor_indices(position, allow_climbing);
+            let mut neighbors = self.explorable_neighbor_indices(position, slope_rule);
             neighbors.retain(|&neighbor| !explored_tiles[neighbor]);
 
             if neighbors.is_empty() {
@@ -73,47 +172,161 @@ impl HikingMap {
                 return neighbors
                     .iter()
                     .filter_map(|neighbor| {
-                        self.explore_from_state(*neighbor, explored_tiles.clone(), allow_climbing)
+                        self.explore_from_state(
+                            *neighbor,
+                            explored_tiles.clone(),
+                            route.clone(),
+                            slope_rule,
+                        )
                     })
-                    .max();
+                    .max_by_key(|route| route.len());
+            }
+        }
+    }
+
+    fn route_map<'a>(&'a self, route: &[usize]) -> RouteMap<'a> {
+        let mut on_route = vec![false; self.tiles.len()];
+        route.iter().for_each(|&index| on_route[index] = true);
+
+        RouteMap {
+            hiking_map: self,
+            on_route,
+        }
+    }
+
+    fn longest_hike_bitmask(&self, slope_rule: SlopeRule) -> Result<usize, Box<dyn Error>> {
+        Ok(self.hike_summary(slope_rule)?.longest)
+    }
+
+    fn longest_hike_dag(&self, slope_rule: SlopeRule) -> Result<usize, Box<dyn Error>> {
+        let junction_graph = self.junction_graph(slope_rule);
+
+        if let Some(topological_order) = junction_graph.topological_order() {
+            junction_graph
+                .longest_path_in_order(&topological_order)
+                .ok_or("No path to the exit".into())
+        } else {
+            // Climbing (or an unusual map) can make corridors traversable in both directions, and
+            // then there's no shortcut; fall back to the general search
+            self.longest_hike_bitmask(slope_rule)
+        }
+    }
+
+    fn hike_summary(&self, slope_rule: SlopeRule) -> Result<HikeSummary, Box<dyn Error>> {
+        let junction_graph = self.junction_graph(slope_rule);
+
+        if junction_graph.edges.len() > 64 {
+            return Err("Too many junctions to track visited junctions in a bitmask".into());
+        }
+
+        let mut memo = HashMap::new();
+
+        junction_graph
+            .longest_path(junction_graph.start, 1 << junction_graph.start, &mut memo)
+            .ok_or("No path to the exit".into())
+    }
+
+    fn longest_hike_within(&self, slope_rule: SlopeRule, max_steps: usize) -> Option<usize> {
+        let junction_graph = self.junction_graph(slope_rule);
+
+        let mut visited = vec![false; junction_graph.edges.len()];
+        visited[junction_graph.start] = true;
+
+        let mut longest_hike = None;
+
+        junction_graph.longest_path_within(
+            junction_graph.start,
+            &mut visited,
+            0,
+            max_steps,
+            &mut longest_hike,
+        );
+
+        longest_hike
+    }
+
+    fn junction_graph(&self, slope_rule: SlopeRule) -> JunctionGraph {
+        let start_index = 1;
+        let exit_index = self.tiles.len() - 2;
+
+        // The map is mostly long, narrow corridors; the only interesting decisions happen at
+        // junctions where three or more paths meet, so we collapse each corridor into a single
+        // weighted edge between junctions (or the start/exit tiles).
+        let junction_indices: Vec<usize> = (0..self.tiles.len())
+            .filter(|&index| {
+                index == start_index
+                    || index == exit_index
+                    || (self.tiles[index] != Tile::Forest
+                        && self
+                            .explorable_neighbor_indices(index, SlopeRule::Climbable)
+                            .len()
+                            >= 3)
+            })
+            .collect();
+
+        let nodes: HashMap<usize, usize> = junction_indices
+            .iter()
+            .enumerate()
+            .map(|(node, &index)| (index, node))
+            .collect();
+
+        let mut edges = vec![Vec::new(); junction_indices.len()];
+
+        for (node, &junction_index) in junction_indices.iter().enumerate() {
+            for neighbor in self.explorable_neighbor_indices(junction_index, slope_rule) {
+                let mut previous = junction_index;
+                let mut position = neighbor;
+                let mut distance = 1;
+
+                while !nodes.contains_key(&position) {
+                    let next = self
+                        .explorable_neighbor_indices(position, slope_rule)
+                        .into_iter()
+                        .find(|&next| next != previous);
+
+                    if let Some(next) = next {
+                        previous = position;
+                        position = next;
+                        distance += 1;
+                    } else {
+                        // Dead end
+                        break;
+                    }
+                }
+
+                if let Some(&destination) = nodes.get(&position) {
+                    edges[node].push((destination, distance));
+                }
             }
         }
+
+        JunctionGraph {
+            start: nodes[&start_index],
+            exit: nodes[&exit_index],
+            edges,
+        }
     }
 
-    fn explorable_neighbor_indices(&self, index: usize, allow_climbing: bool) -> Vec<usize> {
+    fn explorable_neighbor_indices(&self, index: usize, slope_rule: SlopeRule) -> Vec<usize> {
         let mut neighbor_indices = Vec::with_capacity(4);
 
         let x = index % self.width;
         let y = index / self.width;
 
-        if x > 0
-            && (self.tiles[index - 1] == Tile::Path
-                || self.tiles[index - 1] == Tile::Slope(Direction::Left)
-                || (self.tiles[index - 1] != Tile::Forest && allow_climbing))
-        {
+        if x > 0 && slope_rule.can_enter(&self.tiles[index - 1], Direction::Left) {
             neighbor_indices.push(index - 1);
         }
 
-        if x < self.width - 1
-            && (self.tiles[index + 1] == Tile::Path
-                || self.tiles[index + 1] == Tile::Slope(Direction::Right)
-                || (self.tiles[index + 1] != Tile::Forest && allow_climbing))
-        {
+        if x < self.width - 1 && slope_rule.can_enter(&self.tiles[index + 1], Direction::Right) {
             neighbor_indices.push(index + 1);
         }
 
-        if y > 0
-            && (self.tiles[index - self.width] == Tile::Path
-                || self.tiles[index - self.width] == Tile::Slope(Direction::Up)
-                || (self.tiles[index - self.width] != Tile::Forest && allow_climbing))
-        {
+        if y > 0 && slope_rule.can_enter(&self.tiles[index - self.width], Direction::Up) {
             neighbor_indices.push(index - self.width);
         }
 
         if y < self.height() - 1
-            && (self.tiles[index + self.width] == Tile::Path
-                || self.tiles[index + self.width] == Tile::Slope(Direction::Down)
-                || (self.tiles[index + self.width] != Tile::Forest && allow_climbing))
+            && slope_rule.can_enter(&self.tiles[index + self.width], Direction::Down)
         {
             neighbor_indices.push(index + self.width);
         }
@@ -126,6 +339,264 @@ impl HikingMap {
     }
 }
 
+struct JunctionGraph {
+    start: usize,
+    exit: usize,
+    edges: Vec<Vec<(usize, usize)>>,
+}
+
+impl JunctionGraph {
+    fn longest_path(
+        &self,
+        node: usize,
+        visited: u64,
+        memo: &mut HashMap<(usize, u64), Option<HikeSummary>>,
+    ) -> Option<HikeSummary> {
+        if node == self.exit {
+            return Some(HikeSummary {
+                longest: 0,
+                longest_count: 1,
+                total_count: 1,
+            });
+        }
+
+        if let Some(&summary) = memo.get(&(node, visited)) {
+            return summary;
+        }
+
+        let mut summary: Option<HikeSummary> = None;
+
+        for &(neighbor, distance) in &self.edges[node] {
+            if visited & (1 << neighbor) != 0 {
+                continue;
+            }
+
+            if let Some(continuation) = self.longest_path(neighbor, visited | (1 << neighbor), memo)
+            {
+                let length = continuation.longest + distance;
+
+                summary = Some(match summary {
+                    None => HikeSummary {
+                        longest: length,
+                        ..continuation
+                    },
+                    Some(summary) => HikeSummary {
+                        longest: summary.longest.max(length),
+                        longest_count: match summary.longest.cmp(&length) {
+                            Ordering::Less => continuation.longest_count,
+                            Ordering::Equal => summary.longest_count + continuation.longest_count,
+                            Ordering::Greater => summary.longest_count,
+                        },
+                        total_count: summary.total_count + continuation.total_count,
+                    },
+                });
+            }
+        }
+
+        memo.insert((node, visited), summary);
+
+        summary
+    }
+
+    fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut in_degrees = vec![0; self.edges.len()];
+
+        self.edges
+            .iter()
+            .flatten()
+            .for_each(|&(neighbor, _)| in_degrees[neighbor] += 1);
+
+        let mut ready: Vec<usize> = (0..self.edges.len())
+            .filter(|&node| in_degrees[node] == 0)
+            .collect();
+
+        let mut topological_order = Vec::with_capacity(self.edges.len());
+
+        while let Some(node) = ready.pop() {
+            topological_order.push(node);
+
+            for &(neighbor, _) in &self.edges[node] {
+                in_degrees[neighbor] -= 1;
+
+                if in_degrees[neighbor] == 0 {
+                    ready.push(neighbor);
+                }
+            }
+        }
+
+        // If we couldn't put every node in order, the graph must have a cycle
+        if topological_order.len() == self.edges.len() {
+            Some(topological_order)
+        } else {
+            None
+        }
+    }
+
+    fn longest_path_in_order(&self, topological_order: &[usize]) -> Option<usize> {
+        let mut longest_paths: Vec<Option<usize>> = vec![None; self.edges.len()];
+        longest_paths[self.start] = Some(0);
+
+        for &node in topological_order {
+            if let Some(length) = longest_paths[node] {
+                for &(neighbor, distance) in &self.edges[node] {
+                    longest_paths[neighbor] = longest_paths[neighbor].max(Some(length + distance));
+                }
+            }
+        }
+
+        longest_paths[self.exit]
+    }
+
+    fn longest_path_within(
+        &self,
+        node: usize,
+        visited: &mut [bool],
+        length: usize,
+        max_length: usize,
+        longest_path: &mut Option<usize>,
+    ) {
+        if node == self.exit {
+            *longest_path = (*longest_path).max(Some(length));
+            return;
+        }
+
+        // Unlike the unconstrained search, the best way to continue from here depends on how much
+        // of the budget we've already spent, so there's no memoizing on visited junctions alone
+        for &(neighbor, distance) in &self.edges[node] {
+            if !visited[neighbor] && length + distance <= max_length {
+                visited[neighbor] = true;
+
+                self.longest_path_within(
+                    neighbor,
+                    visited,
+                    length + distance,
+                    max_length,
+                    longest_path,
+                );
+
+                visited[neighbor] = false;
+            }
+        }
+    }
+}
+
+struct RouteMap<'a> {
+    hiking_map: &'a HikingMap,
+    on_route: Vec<bool>,
+}
+
+impl RouteMap<'_> {
+    const ROUTE_COLOR: Color = Color::BOLD_YELLOW;
+}
+
+impl Display for RouteMap<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.hiking_map.width;
+        let height = self.hiking_map.tiles.len() / width;
+
+        write_grid(f, width, height, |x, y| {
+            let tile = &self.hiking_map.tiles[(y * width) + x];
+
+            if self.on_route[(y * width) + x] {
+                // Match the puzzle's worked examples, which mark the route with `O`, but keep
+                // slopes visible so it's clear which ones the route climbs
+                let c = match tile {
+                    Tile::Slope(_) => tile.as_char(),
+                    _ => 'O',
+                };
+
+                Glyph::colored(c, Self::ROUTE_COLOR)
+            } else {
+                Glyph::plain(tile.as_char())
+            }
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct HikeSummary {
+    longest: usize,
+    longest_count: u64,
+    total_count: u64,
+}
+
+#[derive(Copy, Clone)]
+enum SlopeRule {
+    // Slopes can only be entered in the direction they point
+    Slippery,
+    // Slopes behave like normal paths
+    Climbable,
+    // Slopes behave like forest
+    Impassable,
+}
+
+impl SlopeRule {
+    fn can_enter(&self, tile: &Tile, direction: Direction) -> bool {
+        match tile {
+            Tile::Path => true,
+            Tile::Forest => false,
+            Tile::Slope(slope_direction) => match self {
+                SlopeRule::Slippery => slope_direction == &direction,
+                SlopeRule::Climbable => true,
+                SlopeRule::Impassable => false,
+            },
+        }
+    }
+}
+
+impl FromStr for SlopeRule {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "slippery" => Ok(SlopeRule::Slippery),
+            "climbable" => Ok(SlopeRule::Climbable),
+            "impassable" => Ok(SlopeRule::Impassable),
+            _ => Err(format!("Unrecognized slope rule: {}", s).into()),
+        }
+    }
+}
+
+impl Display for SlopeRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlopeRule::Slippery => write!(f, "slippery"),
+            SlopeRule::Climbable => write!(f, "climbable"),
+            SlopeRule::Impassable => write!(f, "impassable"),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Algorithm {
+    DepthFirst,
+    Bitmask,
+    Dag,
+}
+
+impl FromStr for Algorithm {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dfs" => Ok(Algorithm::DepthFirst),
+            "bitmask" => Ok(Algorithm::Bitmask),
+            "dag" => Ok(Algorithm::Dag),
+            _ => Err(format!("Unrecognized algorithm: {}", s).into()),
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Algorithm::DepthFirst => write!(f, "dfs"),
+            Algorithm::Bitmask => write!(f, "bitmask"),
+            Algorithm::Dag => write!(f, "dag"),
+        }
+    }
+}
+
 impl FromStr for HikingMap {
     type Err = Box<dyn Error>;
 
@@ -139,7 +610,7 @@ impl FromStr for HikingMap {
                 .map(Tile::try_from)
                 .collect::<Result<_, _>>()?;
 
-            if tiles.len() % width == 0 {
+            if tiles.len().is_multiple_of(width) {
                 Ok(HikingMap { width, tiles })
             } else {
                 Err("Non-rectangular hiking map".into())
@@ -157,6 +628,19 @@ enum Tile {
     Slope(Direction),
 }
 
+impl Tile {
+    fn as_char(&self) -> char {
+        match self {
+            Tile::Path => '.',
+            Tile::Forest => '#',
+            Tile::Slope(Direction::Up) => '^',
+            Tile::Slope(Direction::Down) => 'v',
+            Tile::Slope(Direction::Left) => '<',
+            Tile::Slope(Direction::Right) => '>',
+        }
+    }
+}
+
 impl TryFrom<char> for Tile {
     type Error = Box<dyn Error>;
 
@@ -215,17 +699,147 @@ mod test {
     #[test]
     fn test_longest_hike() {
         assert_eq!(
-            94,
+            Some(94),
             HikingMap::from_str(TEST_MAP_STRING)
                 .unwrap()
-                .longest_hike(false)
+                .longest_hike(SlopeRule::Slippery)
         );
 
         assert_eq!(
-            154,
+            Some(154),
+            HikingMap::from_str(TEST_MAP_STRING)
+                .unwrap()
+                .longest_hike(SlopeRule::Climbable)
+        );
+    }
+
+    #[test]
+    fn test_slope_rules() {
+        let hiking_map = HikingMap::from_str(indoc! {"
+            #.#######
+            #.......#
+            #v#####.#
+            #.#...#.#
+            #...#...#
+            #######.#
+        "})
+        .unwrap();
+
+        assert_eq!(Some(13), hiking_map.longest_hike(SlopeRule::Slippery));
+        assert_eq!(Some(13), hiking_map.longest_hike(SlopeRule::Climbable));
+        assert_eq!(Some(11), hiking_map.longest_hike(SlopeRule::Impassable));
+
+        assert_eq!(
+            None,
             HikingMap::from_str(TEST_MAP_STRING)
                 .unwrap()
-                .longest_hike(true)
+                .longest_hike(SlopeRule::Impassable)
+        );
+    }
+
+    #[test]
+    fn test_longest_hike_route() {
+        let hiking_map = HikingMap::from_str(TEST_MAP_STRING).unwrap();
+        let route = hiking_map.longest_hike_route(SlopeRule::Slippery).unwrap();
+
+        assert_eq!(95, route.len());
+        assert_eq!(Some(&1), route.first());
+        assert_eq!(Some(&(hiking_map.tiles.len() - 2)), route.last());
+
+        // Every step should move to an adjacent tile
+        assert!(route.windows(2).all(|step| {
+            let (a, b) = (step[0], step[1]);
+            a.abs_diff(b) == 1 || a.abs_diff(b) == hiking_map.width
+        }));
+    }
+
+    #[test]
+    fn test_longest_hike_within() {
+        let hiking_map = HikingMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert_eq!(
+            Some(94),
+            hiking_map.longest_hike_within(SlopeRule::Slippery, 1000)
+        );
+        assert_eq!(
+            Some(94),
+            hiking_map.longest_hike_within(SlopeRule::Slippery, 94)
+        );
+        assert_eq!(
+            Some(90),
+            hiking_map.longest_hike_within(SlopeRule::Slippery, 93)
+        );
+        assert_eq!(
+            None,
+            hiking_map.longest_hike_within(SlopeRule::Slippery, 73)
+        );
+        assert_eq!(
+            Some(154),
+            hiking_map.longest_hike_within(SlopeRule::Climbable, 1000)
+        );
+    }
+
+    #[test]
+    fn test_longest_hike_dag() {
+        let hiking_map = HikingMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert!(hiking_map
+            .junction_graph(SlopeRule::Slippery)
+            .topological_order()
+            .is_some());
+        assert!(hiking_map
+            .junction_graph(SlopeRule::Climbable)
+            .topological_order()
+            .is_none());
+
+        assert_eq!(
+            94,
+            hiking_map.longest_hike_dag(SlopeRule::Slippery).unwrap()
+        );
+        assert_eq!(
+            154,
+            hiking_map.longest_hike_dag(SlopeRule::Climbable).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hike_summary() {
+        let hiking_map = HikingMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert_eq!(
+            HikeSummary {
+                longest: 94,
+                longest_count: 1,
+                total_count: 6,
+            },
+            hiking_map.hike_summary(SlopeRule::Slippery).unwrap()
+        );
+
+        assert_eq!(
+            HikeSummary {
+                longest: 154,
+                longest_count: 1,
+                total_count: 12,
+            },
+            hiking_map.hike_summary(SlopeRule::Climbable).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_longest_hike_bitmask() {
+        let hiking_map = HikingMap::from_str(TEST_MAP_STRING).unwrap();
+
+        assert_eq!(
+            94,
+            hiking_map
+                .longest_hike_bitmask(SlopeRule::Slippery)
+                .unwrap()
+        );
+        assert_eq!(
+            154,
+            hiking_map
+                .longest_hike_bitmask(SlopeRule::Climbable)
+                .unwrap()
         );
     }
 }
@@ -0,0 +1,100 @@
+use std::fmt::{Display, Formatter};
+
+// An ANSI foreground color for highlighting cells when rendering a grid to a terminal
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Color(&'static str);
+
+impl Color {
+    pub const RED: Color = Color("\x1b[31m");
+    pub const GREEN: Color = Color("\x1b[32m");
+    pub const BLUE: Color = Color("\x1b[34m");
+    pub const CYAN: Color = Color("\x1b[36m");
+    pub const GRAY: Color = Color("\x1b[90m");
+    pub const BOLD_YELLOW: Color = Color("\x1b[1;33m");
+
+    const RESET: &'static str = "\x1b[0m";
+}
+
+// A single character in a rendered grid, which may or may not be highlighted
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Glyph {
+    c: char,
+    color: Option<Color>,
+}
+
+impl Glyph {
+    pub fn plain(c: char) -> Self {
+        Glyph { c, color: None }
+    }
+
+    pub fn colored(c: char, color: Color) -> Self {
+        Glyph {
+            c,
+            color: Some(color),
+        }
+    }
+}
+
+impl Display for Glyph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.color {
+            Some(color) => write!(f, "{}{}{}", color.0, self.c, Color::RESET),
+            None => write!(f, "{}", self.c),
+        }
+    }
+}
+
+// Writes a `width` by `height` grid one row per line, asking `glyph` for the contents of each
+// position in row-major order
+pub fn write_grid(
+    f: &mut Formatter<'_>,
+    width: usize,
+    height: usize,
+    mut glyph: impl FnMut(usize, usize) -> Glyph,
+) -> std::fmt::Result {
+    for y in 0..height {
+        for x in 0..width {
+            write!(f, "{}", glyph(x, y))?;
+        }
+
+        writeln!(f)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Checkerboard;
+
+    impl Display for Checkerboard {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write_grid(f, 3, 2, |x, y| {
+                if (x + y).is_multiple_of(2) {
+                    Glyph::colored('#', Color::GRAY)
+                } else {
+                    Glyph::plain('.')
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn test_glyph() {
+        assert_eq!(".", Glyph::plain('.').to_string());
+        assert_eq!(
+            "\x1b[1;33m*\x1b[0m",
+            Glyph::colored('*', Color::BOLD_YELLOW).to_string()
+        );
+    }
+
+    #[test]
+    fn test_write_grid() {
+        assert_eq!(
+            "\x1b[90m#\x1b[0m.\x1b[90m#\x1b[0m\n.\x1b[90m#\x1b[0m.\n",
+            Checkerboard.to_string()
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::HashSet;
+use std::error::Error;
+
+// A map from integers to integers made up of non-overlapping intervals, each of which shifts the
+// values it covers by a fixed offset; values that aren't covered by any interval map to themselves
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct IntervalMap {
+    // Sorted by start and non-overlapping, which means ends are sorted, too
+    intervals: Vec<Interval>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Interval {
+    pub start: u64,
+
+    // End exclusive
+    pub end: u64,
+
+    pub offset: i64,
+}
+
+impl IntervalMap {
+    pub fn new(mut intervals: Vec<Interval>) -> Result<Self, Box<dyn Error>> {
+        intervals.retain(|interval| interval.start < interval.end);
+        intervals.sort_by_key(|interval| interval.start);
+
+        if let Some(window) = intervals
+            .windows(2)
+            .find(|window| window[0].end > window[1].start)
+        {
+            return Err(format!(
+                "Ranges {}..{} and {}..{} overlap",
+                window[0].start, window[0].end, window[1].start, window[1].end
+            )
+            .into());
+        }
+
+        Ok(IntervalMap { intervals })
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn map(&self, value: u64) -> u64 {
+        self.overlapping_intervals(value, value.saturating_add(1))
+            .first()
+            .and_then(|interval| interval.map(value))
+            .unwrap_or(value)
+    }
+
+    // Finds all of the intervals that overlap [start, end) with a couple binary searches
+    fn overlapping_intervals(&self, start: u64, end: u64) -> &[Interval] {
+        let first = self
+            .intervals
+            .partition_point(|interval| interval.end <= start);
+        let last = self
+            .intervals
+            .partition_point(|interval| interval.start < end);
+
+        &self.intervals[first..last.max(first)]
+    }
+
+    // Maps every value in [start, end) through this map, splitting the input wherever it crosses an
+    // interval boundary; values that aren't covered by any interval map to themselves
+    pub fn map_range(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let mut mapped_ranges = Vec::new();
+        let mut cursor = start;
+
+        for interval in self.overlapping_intervals(start, end) {
+            let piece_start = interval.start.max(cursor);
+            let piece_end = interval.end.min(end);
+
+            if piece_start > cursor {
+                mapped_ranges.push((cursor, piece_start));
+            }
+
+            if piece_start < piece_end {
+                mapped_ranges.push((interval.shift(piece_start), interval.shift(piece_end)));
+                cursor = piece_end;
+            }
+        }
+
+        if cursor < end {
+            mapped_ranges.push((cursor, end));
+        }
+
+        mapped_ranges
+    }
+
+    // What input values lead to output values in [start, end)? There may be several input ranges
+    // for any given output range, since values that aren't covered by any interval map to
+    // themselves.
+    pub fn invert(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let mut inverted_ranges: Vec<(u64, u64)> = self
+            .intervals
+            .iter()
+            .filter_map(|interval| {
+                let output_start = interval.shift(interval.start).max(start);
+                let output_end = interval.shift(interval.end).min(end);
+
+                if output_start < output_end {
+                    interval
+                        .invert(output_start)
+                        .map(|input_start| (input_start, input_start + (output_end - output_start)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut cursor = start;
+
+        for interval in self.overlapping_intervals(start, end) {
+            if interval.start > cursor {
+                inverted_ranges.push((cursor, interval.start));
+            }
+
+            cursor = cursor.max(interval.end);
+        }
+
+        if cursor < end {
+            inverted_ranges.push((cursor, end));
+        }
+
+        inverted_ranges.sort();
+        inverted_ranges
+    }
+
+    // Builds a single map equivalent to applying this map and then the given map
+    pub fn compose(&self, next: &IntervalMap) -> IntervalMap {
+        let mut boundaries = HashSet::new();
+        boundaries.insert(0);
+        boundaries.insert(u64::MAX);
+
+        self.intervals.iter().for_each(|interval| {
+            boundaries.insert(interval.start);
+            boundaries.insert(interval.end);
+        });
+
+        // Any value that maps to one of the next map's boundaries is a boundary, too
+        next.intervals.iter().for_each(|interval| {
+            for value in [interval.start, interval.end] {
+                for (start, _) in self.invert(value, value.saturating_add(1)) {
+                    boundaries.insert(start);
+                }
+            }
+        });
+
+        let mut boundaries = Vec::from_iter(boundaries);
+        boundaries.sort();
+
+        let intervals: Vec<Interval> = boundaries
+            .windows(2)
+            .filter_map(|window| {
+                if let [start, end] = window {
+                    let offset = next.map(self.map(*start)) as i64 - *start as i64;
+
+                    Some(Interval {
+                        start: *start,
+                        end: *end,
+                        offset,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        IntervalMap {
+            intervals: defragment(intervals),
+        }
+    }
+}
+
+impl Interval {
+    pub fn map(&self, value: u64) -> Option<u64> {
+        if value >= self.start && value < self.end {
+            Some(self.shift(value))
+        } else {
+            None
+        }
+    }
+
+    // Applies this interval's offset without checking bounds; useful for exclusive range ends
+    pub fn shift(&self, value: u64) -> u64 {
+        (value as i64 + self.offset) as u64
+    }
+
+    // What input value, if any, leads to the given output value?
+    pub fn invert(&self, value: u64) -> Option<u64> {
+        if value >= self.shift(self.start) && value < self.shift(self.end) {
+            Some((value as i64 - self.offset) as u64)
+        } else {
+            None
+        }
+    }
+}
+
+// Finds the intersection of two start-inclusive, end-exclusive ranges, if they have one
+pub fn overlap(a: (u64, u64), b: (u64, u64)) -> Option<(u64, u64)> {
+    let start = a.0.max(b.0);
+    let end = a.1.min(b.1);
+
+    if start < end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+// "Defragments" sorted, non-overlapping intervals by merging adjacent intervals with identical
+// offsets
+fn defragment(intervals: Vec<Interval>) -> Vec<Interval> {
+    let mut defragmented: Vec<Interval> = Vec::with_capacity(intervals.len());
+
+    for interval in intervals {
+        match defragmented.last_mut() {
+            Some(previous)
+                if previous.end == interval.start && previous.offset == interval.offset =>
+            {
+                previous.end = interval.end;
+            }
+            _ => defragmented.push(interval),
+        }
+    }
+
+    defragmented
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn interval(start: u64, end: u64, offset: i64) -> Interval {
+        Interval { start, end, offset }
+    }
+
+    #[test]
+    fn test_new() {
+        let interval_map = IntervalMap::new(vec![
+            interval(20, 30, 5),
+            interval(0, 0, 1),
+            interval(0, 10, -1),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            &[interval(0, 10, -1), interval(20, 30, 5)],
+            interval_map.intervals()
+        );
+
+        assert!(IntervalMap::new(vec![interval(0, 10, 1), interval(9, 12, 1)]).is_err());
+        assert!(IntervalMap::new(vec![interval(0, 10, 1), interval(10, 12, 1)]).is_ok());
+    }
+
+    #[test]
+    fn test_map() {
+        let interval_map =
+            IntervalMap::new(vec![interval(10, 20, 5), interval(20, 30, -20)]).unwrap();
+
+        assert_eq!(9, interval_map.map(9));
+        assert_eq!(15, interval_map.map(10));
+        assert_eq!(24, interval_map.map(19));
+        assert_eq!(0, interval_map.map(20));
+        assert_eq!(30, interval_map.map(30));
+        assert_eq!(u64::MAX, interval_map.map(u64::MAX));
+
+        assert_eq!(7, IntervalMap::default().map(7));
+    }
+
+    #[test]
+    fn test_map_range() {
+        let interval_map =
+            IntervalMap::new(vec![interval(10, 20, 5), interval(20, 30, -20)]).unwrap();
+
+        assert_eq!(vec![(0, 5)], interval_map.map_range(0, 5));
+        assert_eq!(
+            vec![(5, 10), (15, 25), (0, 10), (30, 35)],
+            interval_map.map_range(5, 35)
+        );
+        assert_eq!(vec![(17, 25), (0, 2)], interval_map.map_range(12, 22));
+        assert!(interval_map.map_range(12, 12).is_empty());
+    }
+
+    #[test]
+    fn test_invert() {
+        let interval_map =
+            IntervalMap::new(vec![interval(10, 20, 5), interval(20, 30, -20)]).unwrap();
+
+        // 5 is unmapped, and 25 maps to 5
+        assert_eq!(vec![(5, 6), (25, 26)], interval_map.invert(5, 6));
+
+        // 15 maps to 20, but 20 itself maps somewhere else
+        assert_eq!(vec![(15, 16)], interval_map.invert(20, 21));
+
+        assert_eq!(
+            vec![(0, 10), (10, 20), (20, 30), (30, 40)],
+            interval_map.invert(0, 40)
+        );
+
+        for value in 0..40 {
+            for (start, end) in interval_map.invert(value, value + 1) {
+                assert_eq!(1, end - start);
+                assert_eq!(value, interval_map.map(start));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let first = IntervalMap::new(vec![interval(10, 20, 5), interval(20, 30, -20)]).unwrap();
+        let second = IntervalMap::new(vec![interval(0, 8, 100), interval(24, 40, 1)]).unwrap();
+
+        let composed = first.compose(&second);
+
+        for value in 0..100 {
+            assert_eq!(second.map(first.map(value)), composed.map(value));
+        }
+
+        for window in composed.intervals().windows(2) {
+            assert_eq!(window[0].end, window[1].start);
+            assert_ne!(window[0].offset, window[1].offset);
+        }
+
+        // Composing a map with its own inverse should leave a single identity interval
+        let swap = IntervalMap::new(vec![interval(10, 20, 10), interval(20, 30, -10)]).unwrap();
+
+        assert_eq!(&[interval(0, u64::MAX, 0)], swap.compose(&swap).intervals());
+    }
+
+    #[test]
+    fn test_interval() {
+        let interval = interval(98, 100, -48);
+
+        assert_eq!(Some(50), interval.map(98));
+        assert_eq!(None, interval.map(100));
+        assert_eq!(Some(99), interval.invert(51));
+        assert_eq!(None, interval.invert(49));
+    }
+
+    #[test]
+    fn test_overlap() {
+        assert_eq!(Some((5, 10)), overlap((0, 10), (5, 20)));
+        assert_eq!(None, overlap((0, 10), (10, 20)));
+        assert_eq!(Some((3, 4)), overlap((3, 4), (0, 20)));
+    }
+}
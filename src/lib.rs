@@ -0,0 +1,2 @@
+pub mod grid_render;
+pub mod interval_map;